@@ -0,0 +1,334 @@
+use unifs::block::BlockDevice;
+use unifs::{self, DirEntry, Inode, InodeKind, Superblock, BLOCK_SIZE, DIR_ENTRY_SIZE, MAX_DATA_SIZE};
+
+use crate::Error;
+
+const BITS_PER_BLOCK: u32 = (BLOCK_SIZE * 8) as u32;
+
+/// What `check` has learned about the file system so far.
+struct Checker<'a> {
+    device: &'a mut dyn BlockDevice,
+    superblock: Superblock,
+    inodes: Vec<Inode>,
+    /// Inode owning each data block.
+    owners: Vec<Option<u32>>,
+    /// Whether a directory entry leads to each inode.
+    reached: Vec<bool>,
+    report: &'a mut dyn FnMut(String),
+    count: usize,
+}
+
+impl Checker<'_> {
+    fn problem(&mut self, message: String) {
+        (self.report)(message);
+        self.count += 1;
+    }
+
+    /// Reads every inode and records which blocks it uses.
+    fn check_inodes(&mut self) -> Result<(), Error> {
+        for number in 1..self.superblock.inode_count {
+            let inode = match unifs::read_inode(self.device, &self.superblock, number) {
+                Ok(inode) => inode,
+                Err(unifs::UnifsError::Corrupted) => {
+                    self.problem(format!("inode {}: Unknown kind", number));
+                    Inode::FREE
+                }
+                Err(error) => return Err(error.into()),
+            };
+            self.inodes[number as usize] = inode;
+            if inode.kind == InodeKind::Free {
+                continue;
+            }
+
+            if inode.size as usize > MAX_DATA_SIZE {
+                self.problem(format!("inode {}: Size {} is larger than {}", number, inode.size, MAX_DATA_SIZE));
+                continue;
+            }
+            for &block in &inode.blocks[..inode.count_blocks()] {
+                match self.owners.get(block as usize) {
+                    _ if !self.superblock.is_data_block(block) => {
+                        self.problem(format!("inode {}: Block {} lies outside the data", number, block))
+                    }
+                    Some(Some(owner)) => {
+                        let owner = *owner;
+                        self.problem(format!("inode {}: Block {} belongs to inode {} as well", number, block, owner))
+                    }
+                    _ => self.owners[block as usize] = Some(number),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Follows the directory entries from `number`, the directory `path`
+    /// leads to.
+    fn check_directory(&mut self, number: u32, path: &str) -> Result<(), Error> {
+        let inode = self.inodes[number as usize];
+        if !(inode.size as usize).is_multiple_of(DIR_ENTRY_SIZE) {
+            self.problem(format!("{}: Size {} is not a multiple of {}", path, inode.size, DIR_ENTRY_SIZE));
+        }
+
+        let mut data = [0; MAX_DATA_SIZE];
+        let size = match unifs::read_data(self.device, &self.superblock, &inode, &mut data) {
+            Ok(size) => size,
+            // already reported by `check_inodes`
+            Err(unifs::UnifsError::Corrupted) => return Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut names = Vec::new();
+        for bytes in data[..size].chunks_exact(DIR_ENTRY_SIZE) {
+            let entry = match DirEntry::decode(bytes) {
+                Ok(entry) if entry.inode == 0 => continue,
+                Ok(entry) => entry,
+                Err(_) => {
+                    self.problem(format!("{}: Malformed entry", path));
+                    continue;
+                }
+            };
+            let child_path = format!("{}/{}", path.trim_end_matches('/'), entry.name());
+
+            if entry.name().is_empty() || names.contains(&entry.name().to_string()) {
+                self.problem(format!("{}: Empty or repeated name", child_path));
+            }
+            names.push(entry.name().to_string());
+
+            let Some(child) = self.inodes.get(entry.inode as usize).copied() else {
+                self.problem(format!("{}: Inode {} does not exist", child_path, entry.inode));
+                continue;
+            };
+            if child.kind == InodeKind::Free {
+                self.problem(format!("{}: Inode {} is free", child_path, entry.inode));
+                continue;
+            }
+            if child.kind != entry.kind {
+                self.problem(format!("{}: Entry and inode {} differ in kind", child_path, entry.inode));
+            }
+            if child.parent != number {
+                self.problem(format!("{}: Parent is inode {}, not {}", child_path, child.parent, number));
+            }
+            if self.reached[entry.inode as usize] {
+                self.problem(format!("{}: Inode {} is in more than one place", child_path, entry.inode));
+                continue;
+            }
+
+            self.reached[entry.inode as usize] = true;
+            if child.kind == InodeKind::Directory {
+                self.check_directory(entry.inode, &child_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the bitmap at `start` with `is_used` for its first `count`
+    /// bits.
+    fn check_bitmap(&mut self, name: &str, start: u32, count: u32, is_used: &dyn Fn(u32) -> bool) -> Result<(), Error> {
+        let mut block = [0; BLOCK_SIZE];
+
+        for number in 0..count {
+            if number % BITS_PER_BLOCK == 0 {
+                self.device.read((start + number / BITS_PER_BLOCK) as u64, &mut block)?;
+            }
+            let bit = number % BITS_PER_BLOCK;
+            let is_set = block[bit as usize / 8] & (1 << (bit % 8)) != 0;
+
+            if is_set != is_used(number) {
+                let state = if is_set { "in use" } else { "free" };
+                self.problem(format!("{} bitmap: {} {} is marked {}", name, name, number, state));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the inodes, directories and bitmaps of the file system on
+/// `device` agree with each other, hands every problem to `report` and
+/// returns how many there were.
+pub fn check(device: &mut dyn BlockDevice, report: &mut dyn FnMut(String)) -> Result<usize, Error> {
+    let superblock = unifs::read_superblock(device)?;
+    let pending = unifs::pending_journal(device)?;
+
+    let mut checker = Checker {
+        device,
+        superblock,
+        inodes: vec![Inode::FREE; superblock.inode_count as usize],
+        owners: vec![None; superblock.block_count as usize],
+        reached: vec![false; superblock.inode_count as usize],
+        report,
+        count: 0,
+    };
+
+    if pending > 0 {
+        checker.problem(format!("journal: Holds an unfinished update of {} blocks", pending));
+    }
+
+    checker.check_inodes()?;
+    let root = superblock.root_inode;
+    match checker.inodes[root as usize] {
+        inode if inode.kind != InodeKind::Directory => checker.problem(format!("/: Inode {} is no directory", root)),
+        inode => {
+            if inode.parent != root {
+                checker.problem(format!("/: Parent is inode {}, not itself", inode.parent));
+            }
+            checker.reached[root as usize] = true;
+            checker.check_directory(root, "/")?;
+        }
+    }
+
+    for number in 1..superblock.inode_count {
+        if checker.inodes[number as usize].kind != InodeKind::Free && !checker.reached[number as usize] {
+            checker.problem(format!("inode {}: In use, but in no directory", number));
+        }
+    }
+
+    let inodes = checker.inodes.clone();
+    let is_inode_used = |number: u32| number == 0 || inodes[number as usize].kind != InodeKind::Free;
+    checker.check_bitmap("inode", superblock.inode_bitmap_start, superblock.inode_count, &is_inode_used)?;
+
+    let owners = checker.owners.clone();
+    let is_block_used = |block: u32| block < superblock.data_start || owners[block as usize].is_some();
+    let block_bitmap = superblock.block_bitmap_start;
+    checker.check_bitmap("block", block_bitmap, superblock.block_count, &is_block_used)?;
+
+    let used_inodes = (1..superblock.inode_count).filter(|&number| is_inode_used(number)).count() as u32;
+    if superblock.free_inodes != superblock.inode_count - used_inodes - 1 {
+        let free = superblock.inode_count - used_inodes - 1;
+        checker.problem(format!("superblock: {} free inodes recorded, {} found", superblock.free_inodes, free));
+    }
+    let used_blocks = (0..superblock.block_count).filter(|&block| is_block_used(block)).count() as u32;
+    if superblock.free_blocks != superblock.block_count - used_blocks {
+        let free = superblock.block_count - used_blocks;
+        checker.problem(format!("superblock: {} free blocks recorded, {} found", superblock.free_blocks, free));
+    }
+
+    Ok(checker.count)
+}
+
+#[cfg(test)]
+mod tests {
+    use unifs::block::{sectors_of, BlockError, SECTOR_SIZE};
+    use unifs::{DateTime, INODE_SIZE, INODES_PER_BLOCK};
+
+    use super::*;
+    use crate::tree::{self, Node};
+
+    const NOW: DateTime = DateTime {
+        year: 2024,
+        month: 5,
+        day: 17,
+        hour: 12,
+        minute: 30,
+        second: 45,
+    };
+
+    /// A disk image in memory.
+    struct Memory(Vec<u8>);
+
+    impl BlockDevice for Memory {
+        fn sector_count(&self) -> u64 {
+            (self.0.len() / SECTOR_SIZE) as u64
+        }
+
+        fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+            sectors_of(self, lba, buffer)?;
+            let start = lba as usize * SECTOR_SIZE;
+            buffer.copy_from_slice(&self.0[start..start + buffer.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+            sectors_of(self, lba, buffer)?;
+            let start = lba as usize * SECTOR_SIZE;
+            self.0[start..start + buffer.len()].copy_from_slice(buffer);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), BlockError> {
+            Ok(())
+        }
+    }
+
+    /// An image holding the files `a` and `b`, inodes 2 and 3, and the
+    /// layout of its file system.
+    fn image() -> (Memory, Superblock) {
+        let mut device = Memory(vec![0; 1024 * SECTOR_SIZE]);
+        let superblock = unifs::format(&mut device, NOW).unwrap();
+
+        let mut root = Node::new("root", InodeKind::Directory, NOW).unwrap();
+        for (name, text) in [("a", "first\n"), ("b", "second\n")] {
+            let mut file = Node::new(name, InodeKind::File, NOW).unwrap();
+            file.data = text.as_bytes().to_vec();
+            root.insert(name, file, NOW).unwrap();
+        }
+        tree::save(&mut device, &root, NOW).unwrap();
+        (device, superblock)
+    }
+
+    fn write_inode(device: &mut Memory, superblock: &Superblock, number: u32, inode: &Inode) {
+        let start = (superblock.inode_table_start as usize + number as usize / INODES_PER_BLOCK) * BLOCK_SIZE;
+        let offset = start + number as usize % INODES_PER_BLOCK * INODE_SIZE;
+        inode.encode(&mut device.0[offset..offset + INODE_SIZE]);
+    }
+
+    fn problems(device: &mut Memory) -> Vec<String> {
+        let mut problems = Vec::new();
+        let count = check(device, &mut |problem| problems.push(problem)).unwrap();
+        assert_eq!(count, problems.len());
+        problems
+    }
+
+    #[test]
+    fn saved_image_has_no_problems() {
+        let (mut device, _) = image();
+
+        assert_eq!(problems(&mut device), Vec::<String>::new());
+    }
+
+    #[test]
+    fn block_used_twice_is_reported() {
+        let (mut device, superblock) = image();
+        let first = unifs::read_inode(&mut device, &superblock, 2).unwrap();
+        let mut second = unifs::read_inode(&mut device, &superblock, 3).unwrap();
+        let own_block = second.blocks[0];
+        second.blocks[0] = first.blocks[0];
+        write_inode(&mut device, &superblock, 3, &second);
+
+        let problems = problems(&mut device);
+        assert!(problems.contains(&format!("inode 3: Block {} belongs to inode 2 as well", first.blocks[0])));
+        // the block the file no longer uses is still marked
+        assert!(problems.contains(&format!("block bitmap: block {} is marked in use", own_block)));
+    }
+
+    #[test]
+    fn inode_without_directory_entry_is_reported() {
+        let (mut device, superblock) = image();
+        let root = unifs::read_inode(&mut device, &superblock, superblock.root_inode).unwrap();
+        // drop the entry of `b`, the second one
+        let start = root.blocks[0] as usize * BLOCK_SIZE + DIR_ENTRY_SIZE;
+        device.0[start..start + DIR_ENTRY_SIZE].fill(0);
+
+        assert_eq!(problems(&mut device), vec!["inode 3: In use, but in no directory".to_string()]);
+    }
+
+    #[test]
+    fn unknown_inode_kind_is_reported() {
+        let (mut device, superblock) = image();
+        let offset = (superblock.inode_table_start as usize) * BLOCK_SIZE + 2 * INODE_SIZE;
+        device.0[offset] = 7;
+
+        let problems = problems(&mut device);
+        assert!(problems.contains(&"inode 2: Unknown kind".to_string()));
+        assert!(problems.contains(&"/a: Inode 2 is free".to_string()));
+    }
+
+    #[test]
+    fn wrong_free_count_is_reported() {
+        let (mut device, _) = image();
+        let saved = unifs::read_superblock(&mut device).unwrap();
+        unifs::write_superblock(&mut device, &Superblock { free_blocks: 1, ..saved }).unwrap();
+
+        let problems = problems(&mut device);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("superblock: 1 free blocks recorded"));
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use unifs::block::{sectors_of, BlockDevice, BlockError, SECTOR_SIZE};
+
+/// A disk image file, the way QEMU attaches it with `format=raw`.
+pub struct Image {
+    file: File,
+    sectors: u64,
+}
+
+impl Image {
+    pub fn open(path: &Path, writable: bool) -> io::Result<Image> {
+        let file = OpenOptions::new().read(true).write(writable).open(path)?;
+        let sectors = file.metadata()?.len() / SECTOR_SIZE as u64;
+        Ok(Image { file, sectors })
+    }
+
+    /// Creates an image of `size` bytes filled with zeros, replacing the
+    /// file if it exists.
+    pub fn create(path: &Path, size: u64) -> io::Result<Image> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        file.set_len(size)?;
+        Ok(Image {
+            file,
+            sectors: size / SECTOR_SIZE as u64,
+        })
+    }
+
+    fn seek(&mut self, lba: u64) -> Result<(), BlockError> {
+        self.file
+            .seek(SeekFrom::Start(lba * SECTOR_SIZE as u64))
+            .map(|_| ())
+            .map_err(|_| BlockError::DeviceError)
+    }
+}
+
+impl BlockDevice for Image {
+    fn sector_count(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        self.seek(lba)?;
+        self.file.read_exact(buffer).map_err(|_| BlockError::DeviceError)
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        self.seek(lba)?;
+        self.file.write_all(buffer).map_err(|_| BlockError::DeviceError)
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        self.file.sync_data().map_err(|_| BlockError::DeviceError)
+    }
+}
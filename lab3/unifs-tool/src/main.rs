@@ -0,0 +1,241 @@
+//! Creates and inspects UniFS disk images on the host, sharing the format
+//! with the kernel through the `unifs` crate.
+
+mod check;
+mod image;
+mod tree;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use unifs::block::BlockError;
+use unifs::{DateTime, InodeKind, UnifsError};
+
+use image::Image;
+use tree::Node;
+
+const USAGE: &str = "\
+Usage: unifs-tool <command> <image> [arguments]
+
+  create <image> <size>          create and format an image, e.g. 16M
+  list <image> [path]            list a directory and everything below it
+  extract <image> <path> [dest]  copy a file or directory out of the image
+  add <image> <source> [path]    copy a file or directory into the image
+  check <image>                  check that the file system is consistent
+
+Paths inside the image start at its root directory, e.g. docs/readme.";
+
+#[derive(Debug)]
+pub enum Error {
+    Usage,
+    Io(io::Error),
+    Block(BlockError),
+    Unifs(UnifsError),
+    Message(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<BlockError> for Error {
+    fn from(error: BlockError) -> Error {
+        Error::Block(error)
+    }
+}
+
+impl From<UnifsError> for Error {
+    fn from(error: UnifsError) -> Error {
+        Error::Unifs(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage => f.write_str(USAGE),
+            Error::Io(error) => error.fmt(f),
+            Error::Block(error) => error.fmt(f),
+            Error::Unifs(error) => error.fmt(f),
+            Error::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+/// The current time in UTC, which is what the kernel reads from the real
+/// time clock of QEMU.
+fn now() -> DateTime {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // days since 1970-01-01 to a date, counting in eras of 400 years that
+    // start on March 1st so that leap days come last
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    DateTime {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (seconds / 3600) as u8,
+        minute: (seconds / 60 % 60) as u8,
+        second: (seconds % 60) as u8,
+    }
+}
+
+/// Size like `16M`, `512K` or a number of bytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, unit) = match size.char_indices().last()? {
+        (end, 'K' | 'k') => (&size[..end], 1 << 10),
+        (end, 'M' | 'm') => (&size[..end], 1 << 20),
+        (end, 'G' | 'g') => (&size[..end], 1 << 30),
+        _ => (size, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+fn create(image: &Path, size: &str) -> Result<(), Error> {
+    let size = parse_size(size).ok_or(Error::Usage)?;
+    let superblock = unifs::format(&mut Image::create(image, size)?, now())?;
+    println!("{}: {} blocks, {} inodes", image.display(), superblock.block_count, superblock.inode_count);
+    Ok(())
+}
+
+/// Opens `image` for changes, first finishing a sync a crash has cut off.
+fn open(image: &Path) -> Result<Image, Error> {
+    let mut image_file = Image::open(image, true)?;
+    let replayed = unifs::replay_journal(&mut image_file)?;
+    if replayed > 0 {
+        println!("{}: Finished the last sync, {} blocks from the journal", image.display(), replayed);
+    }
+    Ok(image_file)
+}
+
+fn find<'a>(root: &'a Node, path: &str) -> Result<&'a Node, Error> {
+    root.find(path).ok_or_else(|| Error::Message(format!("{}: No such file or directory", path)))
+}
+
+fn list(image: &Path, path: &str) -> Result<(), Error> {
+    let root = tree::load(&mut open(image)?)?;
+    print_node(find(&root, path)?, path.trim_end_matches('/'));
+    Ok(())
+}
+
+fn print_node(node: &Node, path: &str) {
+    match node.is_directory() {
+        true => println!("{:>9}  {}  {}/", "<DIR>", node.modified, path),
+        false => println!("{:>9}  {}  {}", node.data.len(), node.modified, path),
+    }
+
+    for child in &node.children {
+        print_node(child, &format!("{}/{}", path, child.name));
+    }
+}
+
+fn extract(image: &Path, path: &str, destination: &Path) -> Result<(), Error> {
+    let root = tree::load(&mut open(image)?)?;
+    extract_node(find(&root, path)?, destination)
+}
+
+fn extract_node(node: &Node, destination: &Path) -> Result<(), Error> {
+    if !node.is_directory() {
+        return Ok(fs::write(destination, &node.data)?);
+    }
+
+    fs::create_dir_all(destination)?;
+    for child in &node.children {
+        extract_node(child, &destination.join(&child.name))?;
+    }
+    Ok(())
+}
+
+fn add(image: &Path, source: &Path, path: &str) -> Result<(), Error> {
+    let now = now();
+    let mut image_file = open(image)?;
+    let mut root = tree::load(&mut image_file)?;
+
+    root.insert(path, read_source(source, now)?, now)?;
+    tree::save(&mut image_file, &root, now)
+}
+
+/// Reads a file or a directory of the host into a node.
+fn read_source(source: &Path, now: DateTime) -> Result<Node, Error> {
+    let name = source.file_name().and_then(|name| name.to_str()).unwrap_or("");
+
+    if !source.is_dir() {
+        let mut node = Node::new(name, InodeKind::File, now)?;
+        node.data = fs::read(source)?;
+        return Ok(node);
+    }
+
+    let mut node = Node::new(name, InodeKind::Directory, now)?;
+    let mut entries = fs::read_dir(source)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        node.children.push(read_source(&entry.path(), now)?);
+    }
+    Ok(node)
+}
+
+fn check(image: &Path) -> Result<ExitCode, Error> {
+    let mut image_file = Image::open(image, false)?;
+    let count = check::check(&mut image_file, &mut |problem| println!("{}", problem))?;
+
+    match count {
+        0 => {
+            println!("{}: No problems found", image.display());
+            Ok(ExitCode::SUCCESS)
+        }
+        count => {
+            println!("{}: {} problems found", image.display(), count);
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, Error> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let file_name = |path: &str| path.trim_end_matches('/').rsplit('/').next().unwrap_or("").to_string();
+
+    match args[..] {
+        ["create", image, size] => create(Path::new(image), size)?,
+        ["list", image] => list(Path::new(image), "/")?,
+        ["list", image, path] => list(Path::new(image), path)?,
+        ["extract", image, path] => extract(Path::new(image), path, Path::new(&file_name(path)))?,
+        ["extract", image, path, destination] => extract(Path::new(image), path, Path::new(destination))?,
+        ["add", image, source] => add(Path::new(image), Path::new(source), &file_name(source))?,
+        ["add", image, source, path] => add(Path::new(image), Path::new(source), path)?,
+        ["check", image] => return check(Path::new(image)),
+        _ => return Err(Error::Usage),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(code) => code,
+        Err(Error::Usage) => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+        Err(error) => {
+            eprintln!("unifs-tool: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
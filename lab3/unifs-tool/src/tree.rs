@@ -0,0 +1,240 @@
+use unifs::block::BlockDevice;
+use unifs::{
+    self, DateTime, DirEntry, Inode, InodeKind, Journal, Superblock, UnifsError, DIR_ENTRY_SIZE, MAX_DATA_SIZE,
+    MAX_NAME_LEN, ROOT_INODE,
+};
+
+use crate::Error;
+
+/// Deepest directory `load` follows, deeper ones can only come from a loop.
+const MAX_DEPTH: usize = 64;
+
+/// A directory or file of an image, read into memory as a whole.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub kind: InodeKind,
+    /// Contents of a file.
+    pub data: Vec<u8>,
+    /// Entries of a directory.
+    pub children: Vec<Node>,
+    pub created: DateTime,
+    pub modified: DateTime,
+}
+
+impl Node {
+    pub fn new(name: &str, kind: InodeKind, now: DateTime) -> Result<Node, Error> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN || name.contains('/') {
+            return Err(Error::Message(format!("{}: A name needs 1 to {} bytes and no /", name, MAX_NAME_LEN)));
+        }
+
+        Ok(Node {
+            name: name.to_string(),
+            kind,
+            data: Vec::new(),
+            children: Vec::new(),
+            created: now,
+            modified: now,
+        })
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.kind == InodeKind::Directory
+    }
+
+    /// How many inodes the node and everything below it take.
+    fn count_inodes(&self) -> usize {
+        1 + self.children.iter().map(Node::count_inodes).sum::<usize>()
+    }
+
+    pub fn find(&self, path: &str) -> Option<&Node> {
+        components(path).try_fold(self, |node, name| node.children.iter().find(|child| child.name == name))
+    }
+
+    /// Puts `node` at `path`, creating the directories leading there. A file
+    /// replaces a file of the same name, a directory is merged into one.
+    pub fn insert(&mut self, path: &str, mut node: Node, now: DateTime) -> Result<(), Error> {
+        let mut names = components(path).collect::<Vec<_>>();
+        let name = names.pop().ok_or_else(|| Error::Message("The root directory can not be replaced".into()))?;
+        node.name = Node::new(name, node.kind, now)?.name;
+
+        let mut directory = self;
+        for name in names {
+            let index = match directory.children.iter().position(|child| child.name == name) {
+                Some(index) => index,
+                None => {
+                    directory.children.push(Node::new(name, InodeKind::Directory, now)?);
+                    directory.modified = now;
+                    directory.children.len() - 1
+                }
+            };
+            directory = &mut directory.children[index];
+            if !directory.is_directory() {
+                return Err(Error::Message(format!("{}: Not a directory", name)));
+            }
+        }
+
+        match directory.children.iter_mut().find(|child| child.name == node.name) {
+            None => {
+                directory.children.push(node);
+                directory.modified = now;
+            }
+            Some(existing) if existing.is_directory() && node.is_directory() => {
+                for child in node.children {
+                    let name = child.name.clone();
+                    existing.insert(&name, child, now)?;
+                }
+            }
+            Some(existing) if !existing.is_directory() && !node.is_directory() => {
+                existing.data = node.data;
+                existing.modified = now;
+            }
+            Some(existing) => return Err(Error::Message(format!("{}: Exists with another kind", existing.name))),
+        }
+        Ok(())
+    }
+}
+
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// Reads the whole tree of the file system on `device`.
+pub fn load(device: &mut dyn BlockDevice) -> Result<Node, Error> {
+    let superblock = unifs::read_superblock(device)?;
+    let root = unifs::read_inode(device, &superblock, superblock.root_inode)?;
+    if root.kind != InodeKind::Directory {
+        return Err(UnifsError::Corrupted.into());
+    }
+
+    load_node(device, &superblock, "", &root, 0)
+}
+
+fn load_node(
+    device: &mut dyn BlockDevice,
+    superblock: &Superblock,
+    name: &str,
+    inode: &Inode,
+    depth: usize,
+) -> Result<Node, Error> {
+    if depth > MAX_DEPTH {
+        return Err(UnifsError::Corrupted.into());
+    }
+
+    let mut data = [0; MAX_DATA_SIZE];
+    let size = unifs::read_data(device, superblock, inode, &mut data)?;
+    let mut node = Node {
+        name: name.to_string(),
+        kind: inode.kind,
+        data: Vec::new(),
+        children: Vec::new(),
+        created: inode.created,
+        modified: inode.modified,
+    };
+
+    match inode.kind {
+        InodeKind::File => node.data = data[..size].to_vec(),
+        InodeKind::Directory => {
+            for bytes in data[..size].chunks_exact(DIR_ENTRY_SIZE) {
+                let entry = DirEntry::decode(bytes)?;
+                if entry.inode == 0 {
+                    continue;
+                }
+
+                let child = unifs::read_inode(device, superblock, entry.inode)?;
+                node.children.push(load_node(device, superblock, entry.name(), &child, depth + 1)?);
+            }
+        }
+        InodeKind::Free => return Err(UnifsError::Corrupted.into()),
+    }
+    Ok(node)
+}
+
+/// Writes `root` to `device` in one update of the journal, replacing what
+/// the file system held.
+pub fn save(device: &mut dyn BlockDevice, root: &Node, now: DateTime) -> Result<(), Error> {
+    unifs::replay_journal(device)?;
+
+    let mut superblock = unifs::read_superblock(device)?;
+    // inode 0 is never used
+    if root.count_inodes() + ROOT_INODE as usize > superblock.inode_count as usize {
+        return Err(UnifsError::NoSpace.into());
+    }
+
+    let mut inodes = vec![Inode::FREE; superblock.inode_count as usize];
+    let mut writer = Writer {
+        journal: Journal::new(device, &superblock),
+        superblock,
+        next_block: superblock.data_start,
+        next_inode: ROOT_INODE + 1,
+        inodes: &mut inodes,
+    };
+    writer.write(root, ROOT_INODE, ROOT_INODE)?;
+    let Writer {
+        mut journal,
+        next_block,
+        ..
+    } = writer;
+
+    let count_used = inodes.iter().filter(|inode| inode.kind != InodeKind::Free).count() as u32;
+    unifs::write_inode_table(&mut journal, &superblock, |number| inodes[number as usize])?;
+    unifs::write_bitmap(&mut journal, superblock.inode_bitmap_start, 1, |number| {
+        number == 0 || inodes.get(number as usize).is_some_and(|inode| inode.kind != InodeKind::Free)
+    })?;
+    unifs::write_bitmap(
+        &mut journal,
+        superblock.block_bitmap_start,
+        superblock.block_bitmap_blocks,
+        |block| block < next_block,
+    )?;
+
+    superblock.root_inode = ROOT_INODE;
+    superblock.free_blocks = superblock.block_count - next_block;
+    superblock.free_inodes = superblock.inode_count - count_used - 1;
+    superblock.synced = now;
+    unifs::write_superblock(&mut journal, &superblock)?;
+    journal.commit()?;
+    Ok(())
+}
+
+struct Writer<'a, 'b> {
+    journal: Journal<'a>,
+    superblock: Superblock,
+    next_block: u32,
+    next_inode: u32,
+    inodes: &'b mut [Inode],
+}
+
+impl Writer<'_, '_> {
+    /// Writes `node` as inode `number`, its children numbered behind the
+    /// ones already taken.
+    fn write(&mut self, node: &Node, number: u32, parent: u32) -> Result<(), Error> {
+        let mut data = Vec::new();
+
+        match node.kind {
+            InodeKind::Directory => {
+                for child in &node.children {
+                    let child_number = self.next_inode;
+                    self.next_inode += 1;
+                    self.write(child, child_number, number)?;
+
+                    let mut entry = [0; DIR_ENTRY_SIZE];
+                    DirEntry::new(child_number, child.kind, &child.name)?.encode(&mut entry);
+                    data.extend_from_slice(&entry);
+                }
+            }
+            _ => data.extend_from_slice(&node.data),
+        }
+
+        let mut inode = Inode {
+            kind: node.kind,
+            parent,
+            created: node.created,
+            modified: node.modified,
+            ..Inode::FREE
+        };
+        unifs::write_data(&mut self.journal, &self.superblock, &mut self.next_block, &data, &mut inode)?;
+        self.inodes[number as usize] = inode;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use core::fmt;
+
+/// Every block device reads and writes whole sectors of this size.
+pub const SECTOR_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// There is no such device.
+    NoDevice,
+    /// The sectors lie behind the end of the device.
+    OutOfRange,
+    /// The buffer is not a whole number of sectors.
+    BadBuffer,
+    /// The device reported an error.
+    DeviceError,
+    /// The device did not answer in time.
+    Timeout,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            BlockError::NoDevice => "No such disk",
+            BlockError::OutOfRange => "Sector is out of range",
+            BlockError::BadBuffer => "Buffer is not a whole number of sectors",
+            BlockError::DeviceError => "Disk reported an error",
+            BlockError::Timeout => "Disk does not respond",
+        };
+        f.write_str(message)
+    }
+}
+
+/// A device storing data in sectors of `SECTOR_SIZE` bytes, addressed by
+/// their number starting at 0.
+pub trait BlockDevice {
+    fn sector_count(&self) -> u64;
+
+    /// Reads as many sectors starting at `lba` as fit into `buffer`.
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>;
+
+    /// Writes `buffer` to the sectors starting at `lba`.
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>;
+
+    /// Makes sure everything written has reached the medium.
+    fn flush(&mut self) -> Result<(), BlockError>;
+}
+
+/// Number of sectors `buffer` covers, checking that they lie on the device.
+pub fn sectors_of(device: &dyn BlockDevice, lba: u64, buffer: &[u8]) -> Result<u64, BlockError> {
+    if !buffer.len().is_multiple_of(SECTOR_SIZE) {
+        return Err(BlockError::BadBuffer);
+    }
+    let count = (buffer.len() / SECTOR_SIZE) as u64;
+
+    match lba.checked_add(count) {
+        Some(end) if end <= device.sector_count() => Ok(count),
+        _ => Err(BlockError::OutOfRange),
+    }
+}
@@ -0,0 +1,793 @@
+//! UniFS, the format the shell keeps its directories and files in on a
+//! disk. Every block is one sector and all numbers are little endian.
+//!
+//! The kernel and `unifs-tool` on the host share this crate, which only
+//! needs `core`.
+//!
+//! ```text
+//! block 0                 superblock
+//! inode_bitmap_start      1 block, bit i set if inode i is in use
+//! block_bitmap_start      bit b set if block b is in use, metadata included
+//! inode_table_start       8 inodes of 64 bytes per block
+//! journal_start           2 descriptor blocks, then the logged blocks
+//! data_start              directory entries and file contents
+//! ```
+//!
+//! Superblock:
+//!
+//! ```text
+//!  0  magic "UNFS"          28  inode_table_start     48  free_inodes
+//!  4  version               32  inode_table_blocks    52  created (time)
+//!  8  block_count           36  data_start            60  synced (time)
+//! 12  inode_count           40  root_inode            68  journal_start
+//! 16  inode_bitmap_start    44  free_blocks           72  journal_blocks
+//! 20  block_bitmap_start
+//! 24  block_bitmap_blocks
+//! ```
+//!
+//! Version 1 has no journal. From version 2 on every block of an update is
+//! written to the journal first and the descriptor behind them, then the
+//! blocks are copied to their places and the descriptor is cleared. Mounting
+//! copies the blocks again while the descriptor is intact, so an update cut
+//! off by a crash is either lost completely or finished:
+//!
+//! ```text
+//!  0  magic "UNJL"     8  count of logged blocks
+//!  4  checksum        12  where each logged block belongs, 4 bytes each
+//! ```
+//!
+//! The checksum is FNV-1a over the logged blocks followed by the descriptor
+//! from byte 8 to the last block number.
+//!
+//! Inode, inode 0 is never used and the root directory is inode 1:
+//!
+//! ```text
+//!  0  kind: 0 free, 1 file, 2 directory     12  created (time)
+//!  4  parent, the directory holding it      20  modified (time)
+//!  8  size of the data in bytes             28  9 data blocks, 0 if unused
+//! ```
+//!
+//! A directory holds 16-byte entries: the inode (4 bytes), its kind (1),
+//! the length of the name (1) and the name (10). Files hold text, every line
+//! ending with `\n`. A time is the year (2 bytes), month, day, hour, minute
+//! and second followed by a zero byte.
+
+#![no_std]
+
+pub mod block;
+#[cfg(test)]
+mod tests;
+mod time;
+
+use core::fmt;
+
+use crate::block::{sectors_of, BlockDevice, BlockError, SECTOR_SIZE};
+
+pub use time::DateTime;
+
+pub const BLOCK_SIZE: usize = SECTOR_SIZE;
+pub const MAGIC: [u8; 4] = *b"UNFS";
+pub const VERSION: u32 = 2;
+/// Disks formatted before the journal was added.
+const VERSION_WITHOUT_JOURNAL: u32 = 1;
+
+pub const INODE_SIZE: usize = 64;
+pub const INODES_PER_BLOCK: usize = BLOCK_SIZE / INODE_SIZE;
+pub const DIRECT_BLOCKS: usize = 9;
+/// Most bytes of data one inode can have.
+pub const MAX_DATA_SIZE: usize = DIRECT_BLOCKS * BLOCK_SIZE;
+pub const ROOT_INODE: u32 = 1;
+/// The inode bitmap takes one block.
+pub const MAX_COUNT_INODES: u32 = (BLOCK_SIZE * 8) as u32;
+pub const DEFAULT_COUNT_INODES: u32 = 128;
+
+pub const DIR_ENTRY_SIZE: usize = 16;
+pub const MAX_NAME_LEN: usize = 10;
+
+const BITS_PER_BLOCK: u32 = (BLOCK_SIZE * 8) as u32;
+/// Smallest disk that leaves some room for data.
+const MIN_COUNT_BLOCKS: u32 = 512;
+/// Larger disks are only used up to here, which keeps the block bitmap
+/// small enough to be written in one update.
+const MAX_COUNT_BLOCKS: u32 = 16 * BITS_PER_BLOCK;
+
+const JOURNAL_MAGIC: [u8; 4] = *b"UNJL";
+const DESCRIPTOR_BLOCKS: u32 = 2;
+const DESCRIPTOR_SIZE: usize = DESCRIPTOR_BLOCKS as usize * BLOCK_SIZE;
+/// Most blocks one update can write.
+pub const MAX_JOURNAL_ENTRIES: usize = (DESCRIPTOR_SIZE - 12) / 4;
+const JOURNAL_BLOCKS: u32 = DESCRIPTOR_BLOCKS + MAX_JOURNAL_ENTRIES as u32;
+const CHECKSUM_START: u32 = 0x811C_9DC5;
+
+pub type Block = [u8; BLOCK_SIZE];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnifsError {
+    Block(BlockError),
+    /// The disk does not hold a UniFS file system.
+    NotFormatted,
+    UnsupportedVersion,
+    /// Something on the disk contradicts the rest.
+    Corrupted,
+    NoSpace,
+    TooSmall,
+    /// An update writes more blocks than the journal holds.
+    JournalFull,
+}
+
+impl From<BlockError> for UnifsError {
+    fn from(error: BlockError) -> UnifsError {
+        UnifsError::Block(error)
+    }
+}
+
+impl fmt::Display for UnifsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnifsError::Block(error) => error.fmt(f),
+            UnifsError::NotFormatted => f.write_str("Disk is not formatted"),
+            UnifsError::UnsupportedVersion => f.write_str("Unsupported file system version"),
+            UnifsError::Corrupted => f.write_str("File system is corrupted"),
+            UnifsError::NoSpace => f.write_str("Disk is full"),
+            UnifsError::TooSmall => f.write_str("Disk is too small"),
+            UnifsError::JournalFull => f.write_str("Update does not fit into the journal"),
+        }
+    }
+}
+
+fn get_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn get_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// FNV-1a of `bytes`, continuing from `hash`.
+fn checksum(hash: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(hash, |hash, &b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+fn get_time(bytes: &[u8], offset: usize) -> DateTime {
+    DateTime {
+        year: get_u16(bytes, offset),
+        month: bytes[offset + 2],
+        day: bytes[offset + 3],
+        hour: bytes[offset + 4],
+        minute: bytes[offset + 5],
+        second: bytes[offset + 6],
+    }
+}
+
+fn put_time(bytes: &mut [u8], offset: usize, time: DateTime) {
+    bytes[offset..offset + 2].copy_from_slice(&time.year.to_le_bytes());
+    bytes[offset + 2..offset + 8].copy_from_slice(&[time.month, time.day, time.hour, time.minute, time.second, 0]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superblock {
+    pub block_count: u32,
+    pub inode_count: u32,
+    pub inode_bitmap_start: u32,
+    pub block_bitmap_start: u32,
+    pub block_bitmap_blocks: u32,
+    pub inode_table_start: u32,
+    pub inode_table_blocks: u32,
+    /// 0 blocks on disks without a journal.
+    pub journal_start: u32,
+    pub journal_blocks: u32,
+    pub data_start: u32,
+    pub root_inode: u32,
+    pub free_blocks: u32,
+    pub free_inodes: u32,
+    pub created: DateTime,
+    pub synced: DateTime,
+}
+
+impl Superblock {
+    /// Places the regions of a file system of `block_count` blocks with
+    /// room for `inode_count` inodes behind each other.
+    pub fn new(block_count: u32, inode_count: u32, now: DateTime) -> Result<Superblock, UnifsError> {
+        let inode_count = inode_count.min(MAX_COUNT_INODES);
+        let inode_bitmap_start = 1;
+        let block_bitmap_start = inode_bitmap_start + 1;
+        let block_bitmap_blocks = block_count.div_ceil(BITS_PER_BLOCK);
+        let inode_table_start = block_bitmap_start + block_bitmap_blocks;
+        let inode_table_blocks = inode_count.div_ceil(INODES_PER_BLOCK as u32);
+        let journal_start = inode_table_start + inode_table_blocks;
+        let data_start = journal_start + JOURNAL_BLOCKS;
+
+        if block_count < MIN_COUNT_BLOCKS || data_start >= block_count {
+            return Err(UnifsError::TooSmall);
+        }
+
+        Ok(Superblock {
+            block_count,
+            inode_count,
+            inode_bitmap_start,
+            block_bitmap_start,
+            block_bitmap_blocks,
+            inode_table_start,
+            inode_table_blocks,
+            journal_start,
+            journal_blocks: JOURNAL_BLOCKS,
+            data_start,
+            root_inode: ROOT_INODE,
+            free_blocks: block_count - data_start,
+            // inode 0 and the root directory
+            free_inodes: inode_count - 2,
+            created: now,
+            synced: now,
+        })
+    }
+
+    pub fn decode(block: &Block) -> Result<Superblock, UnifsError> {
+        if block[0..4] != MAGIC {
+            return Err(UnifsError::NotFormatted);
+        }
+        let (journal_start, journal_blocks) = match get_u32(block, 4) {
+            VERSION => (get_u32(block, 68), get_u32(block, 72)),
+            VERSION_WITHOUT_JOURNAL => (0, 0),
+            _ => return Err(UnifsError::UnsupportedVersion),
+        };
+
+        let superblock = Superblock {
+            block_count: get_u32(block, 8),
+            inode_count: get_u32(block, 12),
+            inode_bitmap_start: get_u32(block, 16),
+            block_bitmap_start: get_u32(block, 20),
+            block_bitmap_blocks: get_u32(block, 24),
+            inode_table_start: get_u32(block, 28),
+            inode_table_blocks: get_u32(block, 32),
+            journal_start,
+            journal_blocks,
+            data_start: get_u32(block, 36),
+            root_inode: get_u32(block, 40),
+            free_blocks: get_u32(block, 44),
+            free_inodes: get_u32(block, 48),
+            created: get_time(block, 52),
+            synced: get_time(block, 60),
+        };
+
+        match superblock.is_consistent() {
+            true => Ok(superblock),
+            false => Err(UnifsError::Corrupted),
+        }
+    }
+
+    pub fn encode(&self, block: &mut Block) {
+        block.fill(0);
+        block[0..4].copy_from_slice(&MAGIC);
+        put_u32(block, 4, VERSION);
+        put_u32(block, 8, self.block_count);
+        put_u32(block, 12, self.inode_count);
+        put_u32(block, 16, self.inode_bitmap_start);
+        put_u32(block, 20, self.block_bitmap_start);
+        put_u32(block, 24, self.block_bitmap_blocks);
+        put_u32(block, 28, self.inode_table_start);
+        put_u32(block, 32, self.inode_table_blocks);
+        put_u32(block, 36, self.data_start);
+        put_u32(block, 40, self.root_inode);
+        put_u32(block, 44, self.free_blocks);
+        put_u32(block, 48, self.free_inodes);
+        put_time(block, 52, self.created);
+        put_time(block, 60, self.synced);
+        put_u32(block, 68, self.journal_start);
+        put_u32(block, 72, self.journal_blocks);
+    }
+
+    /// Whether the regions lie behind each other inside the disk.
+    fn is_consistent(&self) -> bool {
+        // in 64 bits nothing read from a damaged disk can overflow
+        let end = |start: u32, blocks: u32| start as u64 + blocks as u64;
+
+        self.inode_count <= MAX_COUNT_INODES
+            && self.root_inode != 0
+            && self.root_inode < self.inode_count
+            && self.inode_bitmap_start >= 1
+            && self.block_bitmap_start > self.inode_bitmap_start
+            && self.block_bitmap_blocks as u64 * BITS_PER_BLOCK as u64 >= self.block_count as u64
+            && self.inode_table_start as u64 >= end(self.block_bitmap_start, self.block_bitmap_blocks)
+            && self.inode_table_blocks as u64 * INODES_PER_BLOCK as u64 >= self.inode_count as u64
+            && self.data_start as u64 >= end(self.inode_table_start, self.inode_table_blocks)
+            && self.data_start < self.block_count
+            && (self.journal_blocks == 0
+                || self.journal_blocks > DESCRIPTOR_BLOCKS
+                    && self.journal_start as u64 >= end(self.inode_table_start, self.inode_table_blocks)
+                    && self.data_start as u64 >= end(self.journal_start, self.journal_blocks))
+    }
+
+    pub fn is_data_block(&self, block: u32) -> bool {
+        (self.data_start..self.block_count).contains(&block)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeKind {
+    Free,
+    File,
+    Directory,
+}
+
+impl InodeKind {
+    fn decode(kind: u8) -> Result<InodeKind, UnifsError> {
+        match kind {
+            0 => Ok(InodeKind::Free),
+            1 => Ok(InodeKind::File),
+            2 => Ok(InodeKind::Directory),
+            _ => Err(UnifsError::Corrupted),
+        }
+    }
+
+    fn encode(self) -> u8 {
+        match self {
+            InodeKind::Free => 0,
+            InodeKind::File => 1,
+            InodeKind::Directory => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inode {
+    pub kind: InodeKind,
+    pub parent: u32,
+    pub size: u32,
+    pub created: DateTime,
+    pub modified: DateTime,
+    pub blocks: [u32; DIRECT_BLOCKS],
+}
+
+impl Inode {
+    pub const FREE: Inode = Inode {
+        kind: InodeKind::Free,
+        parent: 0,
+        size: 0,
+        created: DateTime::UNKNOWN,
+        modified: DateTime::UNKNOWN,
+        blocks: [0; DIRECT_BLOCKS],
+    };
+
+    pub fn decode(bytes: &[u8]) -> Result<Inode, UnifsError> {
+        let mut blocks = [0; DIRECT_BLOCKS];
+        for (i, block) in blocks.iter_mut().enumerate() {
+            *block = get_u32(bytes, 28 + 4 * i);
+        }
+
+        Ok(Inode {
+            kind: InodeKind::decode(bytes[0])?,
+            parent: get_u32(bytes, 4),
+            size: get_u32(bytes, 8),
+            created: get_time(bytes, 12),
+            modified: get_time(bytes, 20),
+            blocks,
+        })
+    }
+
+    pub fn encode(&self, bytes: &mut [u8]) {
+        bytes[..INODE_SIZE].fill(0);
+        bytes[0] = self.kind.encode();
+        put_u32(bytes, 4, self.parent);
+        put_u32(bytes, 8, self.size);
+        put_time(bytes, 12, self.created);
+        put_time(bytes, 20, self.modified);
+        for (i, &block) in self.blocks.iter().enumerate() {
+            put_u32(bytes, 28 + 4 * i, block);
+        }
+    }
+
+    /// Number of blocks holding the data.
+    pub fn count_blocks(&self) -> usize {
+        (self.size as usize).div_ceil(BLOCK_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirEntry {
+    /// 0 marks an unused entry.
+    pub inode: u32,
+    pub kind: InodeKind,
+    name: [u8; MAX_NAME_LEN],
+    name_len: u8,
+}
+
+impl DirEntry {
+    pub fn new(inode: u32, kind: InodeKind, name: &str) -> Result<DirEntry, UnifsError> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(UnifsError::Corrupted);
+        }
+
+        let mut entry = DirEntry {
+            inode,
+            kind,
+            name: [0; MAX_NAME_LEN],
+            name_len: name.len() as u8,
+        };
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        Ok(entry)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<DirEntry, UnifsError> {
+        let name_len = bytes[5];
+        if name_len as usize > MAX_NAME_LEN {
+            return Err(UnifsError::Corrupted);
+        }
+
+        let mut name = [0; MAX_NAME_LEN];
+        name.copy_from_slice(&bytes[6..6 + MAX_NAME_LEN]);
+        Ok(DirEntry {
+            inode: get_u32(bytes, 0),
+            kind: InodeKind::decode(bytes[4])?,
+            name,
+            name_len,
+        })
+    }
+
+    pub fn encode(&self, bytes: &mut [u8]) {
+        put_u32(bytes, 0, self.inode);
+        bytes[4] = self.kind.encode();
+        bytes[5] = self.name_len;
+        bytes[6..6 + MAX_NAME_LEN].copy_from_slice(&self.name);
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("")
+    }
+}
+
+pub fn read_superblock(device: &mut dyn BlockDevice) -> Result<Superblock, UnifsError> {
+    let mut block = [0; BLOCK_SIZE];
+    device.read(0, &mut block)?;
+    let superblock = Superblock::decode(&block)?;
+
+    match superblock.block_count as u64 <= device.sector_count() {
+        true => Ok(superblock),
+        false => Err(UnifsError::Corrupted),
+    }
+}
+
+pub fn write_superblock(device: &mut dyn BlockDevice, superblock: &Superblock) -> Result<(), UnifsError> {
+    let mut block = [0; BLOCK_SIZE];
+    superblock.encode(&mut block);
+    device.write(0, &block)?;
+    Ok(())
+}
+
+pub fn read_inode(device: &mut dyn BlockDevice, superblock: &Superblock, number: u32) -> Result<Inode, UnifsError> {
+    if number == 0 || number >= superblock.inode_count {
+        return Err(UnifsError::Corrupted);
+    }
+
+    let mut block = [0; BLOCK_SIZE];
+    let table_block = superblock.inode_table_start + number / INODES_PER_BLOCK as u32;
+    device.read(table_block as u64, &mut block)?;
+
+    let offset = number as usize % INODES_PER_BLOCK * INODE_SIZE;
+    Inode::decode(&block[offset..offset + INODE_SIZE])
+}
+
+/// Reads block `index` of the data of `inode`, checking that it lies in the
+/// data region.
+pub fn read_data_block(
+    device: &mut dyn BlockDevice,
+    superblock: &Superblock,
+    inode: &Inode,
+    index: usize,
+    block: &mut Block,
+) -> Result<(), UnifsError> {
+    if inode.size as usize > MAX_DATA_SIZE {
+        return Err(UnifsError::Corrupted);
+    }
+
+    let number = inode.blocks[index];
+    if !superblock.is_data_block(number) {
+        return Err(UnifsError::Corrupted);
+    }
+    device.read(number as u64, block)?;
+    Ok(())
+}
+
+/// Reads the data of `inode` into `data`, returning its size.
+pub fn read_data(
+    device: &mut dyn BlockDevice,
+    superblock: &Superblock,
+    inode: &Inode,
+    data: &mut [u8; MAX_DATA_SIZE],
+) -> Result<usize, UnifsError> {
+    let mut block = [0; BLOCK_SIZE];
+
+    for index in 0..inode.count_blocks().min(DIRECT_BLOCKS) {
+        read_data_block(device, superblock, inode, index, &mut block)?;
+        data[index * BLOCK_SIZE..(index + 1) * BLOCK_SIZE].copy_from_slice(&block);
+    }
+    Ok((inode.size as usize).min(MAX_DATA_SIZE))
+}
+
+/// Writes `data` to the blocks from `next_block` on and records them in
+/// `inode`.
+pub fn write_data(
+    device: &mut dyn BlockDevice,
+    superblock: &Superblock,
+    next_block: &mut u32,
+    data: &[u8],
+    inode: &mut Inode,
+) -> Result<(), UnifsError> {
+    if data.len() > MAX_DATA_SIZE {
+        return Err(UnifsError::NoSpace);
+    }
+
+    inode.size = data.len() as u32;
+    inode.blocks = [0; DIRECT_BLOCKS];
+
+    for (index, chunk) in data.chunks(BLOCK_SIZE).enumerate() {
+        if *next_block >= superblock.block_count {
+            return Err(UnifsError::NoSpace);
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        block[..chunk.len()].copy_from_slice(chunk);
+        device.write(*next_block as u64, &block)?;
+
+        inode.blocks[index] = *next_block;
+        *next_block += 1;
+    }
+    Ok(())
+}
+
+/// Writes the inode table, `inode(i)` giving inode `i`.
+pub fn write_inode_table(
+    device: &mut dyn BlockDevice,
+    superblock: &Superblock,
+    inode: impl Fn(u32) -> Inode,
+) -> Result<(), UnifsError> {
+    let mut block = [0; BLOCK_SIZE];
+
+    for table_block in 0..superblock.inode_table_blocks {
+        for slot in 0..INODES_PER_BLOCK {
+            let number = table_block * INODES_PER_BLOCK as u32 + slot as u32;
+            let inode = match number < superblock.inode_count {
+                true => inode(number),
+                false => Inode::FREE,
+            };
+            inode.encode(&mut block[slot * INODE_SIZE..(slot + 1) * INODE_SIZE]);
+        }
+        device.write((superblock.inode_table_start + table_block) as u64, &block)?;
+    }
+    Ok(())
+}
+
+/// Writes `blocks` blocks of a bitmap starting at `start`, bit `i` being
+/// `is_used(i)`.
+pub fn write_bitmap(
+    device: &mut dyn BlockDevice,
+    start: u32,
+    blocks: u32,
+    is_used: impl Fn(u32) -> bool,
+) -> Result<(), UnifsError> {
+    let mut block = [0; BLOCK_SIZE];
+
+    for bitmap_block in 0..blocks {
+        block.fill(0);
+        for bit in 0..BITS_PER_BLOCK {
+            if is_used(bitmap_block * BITS_PER_BLOCK + bit) {
+                block[bit as usize / 8] |= 1 << (bit % 8);
+            }
+        }
+        device.write((start + bitmap_block) as u64, &block)?;
+    }
+    Ok(())
+}
+
+/// Creates an empty file system on `device`, which only holds the root
+/// directory.
+pub fn format(device: &mut dyn BlockDevice, now: DateTime) -> Result<Superblock, UnifsError> {
+    let block_count = device.sector_count().min(MAX_COUNT_BLOCKS as u64) as u32;
+    let superblock = Superblock::new(block_count, DEFAULT_COUNT_INODES, now)?;
+
+    let root = Inode {
+        kind: InodeKind::Directory,
+        parent: ROOT_INODE,
+        created: now,
+        modified: now,
+        ..Inode::FREE
+    };
+
+    write_bitmap(device, superblock.inode_bitmap_start, 1, |inode| inode <= ROOT_INODE)?;
+    write_bitmap(
+        device,
+        superblock.block_bitmap_start,
+        superblock.block_bitmap_blocks,
+        |block| block < superblock.data_start,
+    )?;
+    write_inode_table(device, &superblock, |number| match number {
+        ROOT_INODE => root,
+        _ => Inode::FREE,
+    })?;
+    // whatever the disk held there must not be taken for a descriptor
+    device.write(superblock.journal_start as u64, &[0; BLOCK_SIZE])?;
+    write_superblock(device, &superblock)?;
+    device.flush()?;
+
+    Ok(superblock)
+}
+
+/// Block device that logs the blocks written to it in the journal of a file
+/// system until `commit`, so that either all of them reach their places or
+/// none. Reads see the blocks as they were before. An update too large for
+/// the journal is refused by `commit` without anything being changed.
+pub struct Journal<'a> {
+    device: &'a mut dyn BlockDevice,
+    start: u32,
+    /// Whether the file system has a journal at all, without one blocks are
+    /// written to their places right away.
+    enabled: bool,
+    targets: [u32; MAX_JOURNAL_ENTRIES],
+    count: usize,
+    overflowed: bool,
+    checksum: u32,
+}
+
+impl<'a> Journal<'a> {
+    pub fn new(device: &'a mut dyn BlockDevice, superblock: &Superblock) -> Journal<'a> {
+        Journal {
+            device,
+            start: superblock.journal_start,
+            enabled: superblock.journal_blocks > 0,
+            targets: [0; MAX_JOURNAL_ENTRIES],
+            count: 0,
+            overflowed: false,
+            checksum: CHECKSUM_START,
+        }
+    }
+
+    /// Writes the descriptor, from then on the update survives a crash, and
+    /// copies the logged blocks to their places.
+    pub fn commit(&mut self) -> Result<(), UnifsError> {
+        if self.overflowed {
+            return Err(UnifsError::JournalFull);
+        }
+        if !self.enabled || self.count == 0 {
+            self.device.flush()?;
+            return Ok(());
+        }
+
+        let mut descriptor = [0; DESCRIPTOR_SIZE];
+        let end = 12 + 4 * self.count;
+        descriptor[0..4].copy_from_slice(&JOURNAL_MAGIC);
+        put_u32(&mut descriptor, 8, self.count as u32);
+        for (index, &target) in self.targets[..self.count].iter().enumerate() {
+            put_u32(&mut descriptor, 12 + 4 * index, target);
+        }
+        let hash = checksum(self.checksum, &descriptor[8..end]);
+        put_u32(&mut descriptor, 4, hash);
+
+        // the logged blocks have to be complete before the descriptor
+        self.device.flush()?;
+        self.device.write(self.start as u64, &descriptor)?;
+        self.device.flush()?;
+
+        checkpoint(self.device, self.start, &descriptor)?;
+        self.count = 0;
+        self.checksum = CHECKSUM_START;
+        Ok(())
+    }
+}
+
+impl BlockDevice for Journal<'_> {
+    fn sector_count(&self) -> u64 {
+        self.device.sector_count()
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        self.device.read(lba, buffer)
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        if !self.enabled {
+            return self.device.write(lba, buffer);
+        }
+
+        let count = sectors_of(&*self.device, lba, buffer)?;
+        if self.count + count as usize > MAX_JOURNAL_ENTRIES {
+            self.overflowed = true;
+            return Ok(());
+        }
+
+        for (index, block) in buffer.chunks(BLOCK_SIZE).enumerate() {
+            let logged = self.start + DESCRIPTOR_BLOCKS + self.count as u32;
+            self.device.write(logged as u64, block)?;
+            self.targets[self.count] = (lba + index as u64) as u32;
+            self.count += 1;
+            self.checksum = checksum(self.checksum, block);
+        }
+        Ok(())
+    }
+
+    /// The blocks reach the disk on `commit`.
+    fn flush(&mut self) -> Result<(), BlockError> {
+        Ok(())
+    }
+}
+
+/// Copies the blocks a committed `descriptor` lists to their places and
+/// clears the descriptor.
+fn checkpoint(device: &mut dyn BlockDevice, start: u32, descriptor: &[u8]) -> Result<usize, UnifsError> {
+    let count = get_u32(descriptor, 8) as usize;
+    let mut block = [0; BLOCK_SIZE];
+
+    for index in 0..count {
+        let logged = start + DESCRIPTOR_BLOCKS + index as u32;
+        device.read(logged as u64, &mut block)?;
+        device.write(get_u32(descriptor, 12 + 4 * index) as u64, &block)?;
+    }
+    device.flush()?;
+
+    device.write(start as u64, &[0; BLOCK_SIZE])?;
+    device.flush()?;
+    Ok(count)
+}
+
+/// Descriptor of an update that was committed, whose blocks may not all
+/// have reached their places yet.
+fn committed_descriptor(
+    device: &mut dyn BlockDevice,
+    superblock: &Superblock,
+) -> Result<Option<[u8; DESCRIPTOR_SIZE]>, UnifsError> {
+    if superblock.journal_blocks == 0 {
+        return Ok(None);
+    }
+
+    let mut descriptor = [0; DESCRIPTOR_SIZE];
+    device.read(superblock.journal_start as u64, &mut descriptor)?;
+    let count = get_u32(&descriptor, 8) as usize;
+    if descriptor[0..4] != JOURNAL_MAGIC || count > MAX_JOURNAL_ENTRIES {
+        return Ok(None);
+    }
+
+    let mut block = [0; BLOCK_SIZE];
+    let mut hash = CHECKSUM_START;
+    for index in 0..count {
+        let logged = superblock.journal_start + DESCRIPTOR_BLOCKS + index as u32;
+        device.read(logged as u64, &mut block)?;
+        hash = checksum(hash, &block);
+    }
+
+    // the crash came while the descriptor was written, nothing has changed
+    if checksum(hash, &descriptor[8..12 + 4 * count]) != get_u32(&descriptor, 4) {
+        return Ok(None);
+    }
+
+    let journal_end = superblock.journal_start + superblock.journal_blocks;
+    let is_outside_journal = |target: u32| target < superblock.journal_start || target >= journal_end;
+    if !(0..count)
+        .map(|index| get_u32(&descriptor, 12 + 4 * index))
+        .all(|target| target < superblock.block_count && is_outside_journal(target))
+    {
+        return Err(UnifsError::Corrupted);
+    }
+
+    Ok(Some(descriptor))
+}
+
+/// How many blocks of an update cut off by a crash `replay_journal` would
+/// copy, without changing anything.
+pub fn pending_journal(device: &mut dyn BlockDevice) -> Result<usize, UnifsError> {
+    let superblock = read_superblock(device)?;
+    let descriptor = committed_descriptor(device, &superblock)?;
+    Ok(descriptor.map_or(0, |descriptor| get_u32(&descriptor, 8) as usize))
+}
+
+/// Finishes an update a crash has cut off after its descriptor was written,
+/// returning how many blocks were copied to their places.
+pub fn replay_journal(device: &mut dyn BlockDevice) -> Result<usize, UnifsError> {
+    let superblock = read_superblock(device)?;
+
+    match committed_descriptor(device, &superblock)? {
+        Some(descriptor) => checkpoint(device, superblock.journal_start, &descriptor),
+        None => Ok(0),
+    }
+}
@@ -0,0 +1,296 @@
+extern crate std;
+
+use std::vec;
+use std::vec::Vec;
+
+use super::*;
+
+const NOW: DateTime = DateTime {
+    year: 2024,
+    month: 5,
+    day: 17,
+    hour: 12,
+    minute: 30,
+    second: 45,
+};
+
+/// A disk in memory. Once `writes_left` is used up further writes are
+/// dropped, the way a disk loses them when the power goes.
+struct Memory {
+    bytes: Vec<u8>,
+    writes_left: usize,
+}
+
+impl Memory {
+    fn new(sectors: usize) -> Memory {
+        Memory {
+            bytes: vec![0; sectors * SECTOR_SIZE],
+            writes_left: usize::MAX,
+        }
+    }
+
+    fn block(&self, number: u32) -> &[u8] {
+        let start = number as usize * BLOCK_SIZE;
+        &self.bytes[start..start + BLOCK_SIZE]
+    }
+
+    fn block_mut(&mut self, number: u32) -> &mut [u8] {
+        let start = number as usize * BLOCK_SIZE;
+        &mut self.bytes[start..start + BLOCK_SIZE]
+    }
+}
+
+impl BlockDevice for Memory {
+    fn sector_count(&self) -> u64 {
+        (self.bytes.len() / SECTOR_SIZE) as u64
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        let start = lba as usize * SECTOR_SIZE;
+        buffer.copy_from_slice(&self.bytes[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        if self.writes_left == 0 {
+            return Ok(());
+        }
+        self.writes_left -= 1;
+
+        let start = lba as usize * SECTOR_SIZE;
+        self.bytes[start..start + buffer.len()].copy_from_slice(buffer);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        Ok(())
+    }
+}
+
+fn formatted() -> (Memory, Superblock) {
+    let mut device = Memory::new(1024);
+    let superblock = format(&mut device, NOW).unwrap();
+    (device, superblock)
+}
+
+/// Blocks of the data region an update writes to.
+fn targets(superblock: &Superblock) -> [u32; 3] {
+    [superblock.data_start, superblock.data_start + 5, superblock.data_start + 9]
+}
+
+/// Logs an update of `targets` and commits it, `writes_left` writes
+/// reaching the disk from the start of the update on. The blocks are
+/// filled with `0x11`, `0x22` and so on.
+fn update(device: &mut Memory, superblock: &Superblock, targets: &[u32], writes_left: usize) -> Result<(), UnifsError> {
+    device.writes_left = writes_left;
+    let mut journal = Journal::new(device, superblock);
+    for (index, &target) in targets.iter().enumerate() {
+        journal.write(target as u64, &[0x11u8.wrapping_mul(index as u8 + 1); BLOCK_SIZE])?;
+    }
+    let result = journal.commit();
+    device.writes_left = usize::MAX;
+    result
+}
+
+#[test]
+fn superblock_round_trip() {
+    let superblock = Superblock::new(2048, 64, NOW).unwrap();
+    let mut block = [0; BLOCK_SIZE];
+    superblock.encode(&mut block);
+
+    assert_eq!(Superblock::decode(&block), Ok(superblock));
+}
+
+#[test]
+fn superblock_without_magic_is_not_formatted() {
+    let mut block = [0; BLOCK_SIZE];
+    Superblock::new(2048, 64, NOW).unwrap().encode(&mut block);
+    block[0] = b'X';
+
+    assert_eq!(Superblock::decode(&block), Err(UnifsError::NotFormatted));
+}
+
+#[test]
+fn superblock_with_overlapping_regions_is_corrupted() {
+    let mut superblock = Superblock::new(2048, 64, NOW).unwrap();
+    superblock.data_start = superblock.inode_table_start;
+    let mut block = [0; BLOCK_SIZE];
+    superblock.encode(&mut block);
+
+    assert_eq!(Superblock::decode(&block), Err(UnifsError::Corrupted));
+}
+
+#[test]
+fn inode_round_trip() {
+    let inode = Inode {
+        kind: InodeKind::File,
+        parent: ROOT_INODE,
+        size: 700,
+        created: NOW,
+        modified: DateTime { second: 0, ..NOW },
+        blocks: [40, 41, 0, 0, 0, 0, 0, 0, 0],
+    };
+    let mut bytes = [0; INODE_SIZE];
+    inode.encode(&mut bytes);
+
+    assert_eq!(Inode::decode(&bytes), Ok(inode));
+    assert_eq!(inode.count_blocks(), 2);
+}
+
+#[test]
+fn dir_entry_round_trip() {
+    let entry = DirEntry::new(7, InodeKind::Directory, "scripts").unwrap();
+    let mut bytes = [0; DIR_ENTRY_SIZE];
+    entry.encode(&mut bytes);
+
+    let decoded = DirEntry::decode(&bytes).unwrap();
+    assert_eq!(decoded, entry);
+    assert_eq!(decoded.name(), "scripts");
+}
+
+#[test]
+fn dir_entry_name_must_fit() {
+    assert_eq!(DirEntry::new(7, InodeKind::File, ""), Err(UnifsError::Corrupted));
+    assert_eq!(DirEntry::new(7, InodeKind::File, "elevenbytes"), Err(UnifsError::Corrupted));
+}
+
+#[test]
+fn format_leaves_an_empty_root_directory() {
+    let (mut device, superblock) = formatted();
+
+    assert_eq!(read_superblock(&mut device), Ok(superblock));
+    let root = read_inode(&mut device, &superblock, ROOT_INODE).unwrap();
+    assert_eq!(root.kind, InodeKind::Directory);
+    assert_eq!(root.parent, ROOT_INODE);
+    assert_eq!(root.size, 0);
+    assert_eq!(pending_journal(&mut device), Ok(0));
+}
+
+#[test]
+fn data_written_is_read_back() {
+    let (mut device, superblock) = formatted();
+    let text: Vec<u8> = (0..1300).map(|i| b'a' + (i % 26) as u8).collect();
+
+    let mut inode = Inode {
+        kind: InodeKind::File,
+        ..Inode::FREE
+    };
+    let mut next_block = superblock.data_start;
+    write_data(&mut device, &superblock, &mut next_block, &text, &mut inode).unwrap();
+    assert_eq!(next_block, superblock.data_start + 3);
+
+    let mut data = [0; MAX_DATA_SIZE];
+    let size = read_data(&mut device, &superblock, &inode, &mut data).unwrap();
+    assert_eq!(&data[..size], &text[..]);
+}
+
+#[test]
+fn data_larger_than_an_inode_holds_is_refused() {
+    let (mut device, superblock) = formatted();
+    let mut inode = Inode::FREE;
+    let mut next_block = superblock.data_start;
+
+    let result = write_data(&mut device, &superblock, &mut next_block, &[0; MAX_DATA_SIZE + 1], &mut inode);
+    assert_eq!(result, Err(UnifsError::NoSpace));
+}
+
+#[test]
+fn commit_puts_the_blocks_in_their_places() {
+    let (mut device, superblock) = formatted();
+    let targets = targets(&superblock);
+
+    update(&mut device, &superblock, &targets, usize::MAX).unwrap();
+
+    assert_eq!(device.block(targets[0]), &[0x11; BLOCK_SIZE]);
+    assert_eq!(device.block(targets[2]), &[0x33; BLOCK_SIZE]);
+    assert_eq!(pending_journal(&mut device), Ok(0));
+}
+
+#[test]
+fn update_too_large_for_the_journal_changes_nothing() {
+    let (mut device, superblock) = formatted();
+    let targets: Vec<u32> = (0..MAX_JOURNAL_ENTRIES as u32 + 1).map(|i| superblock.data_start + i).collect();
+
+    assert_eq!(update(&mut device, &superblock, &targets, usize::MAX), Err(UnifsError::JournalFull));
+    assert_eq!(device.block(targets[0]), &[0; BLOCK_SIZE]);
+}
+
+#[test]
+fn committed_update_is_replayed() {
+    let (mut device, superblock) = formatted();
+    let targets = targets(&superblock);
+
+    // the logged blocks and the descriptor, then the power goes
+    update(&mut device, &superblock, &targets, targets.len() + 1).unwrap();
+    assert_eq!(device.block(targets[0]), &[0; BLOCK_SIZE]);
+    assert_eq!(pending_journal(&mut device), Ok(targets.len()));
+
+    assert_eq!(replay_journal(&mut device), Ok(targets.len()));
+    assert_eq!(device.block(targets[0]), &[0x11; BLOCK_SIZE]);
+    assert_eq!(device.block(targets[1]), &[0x22; BLOCK_SIZE]);
+    assert_eq!(device.block(targets[2]), &[0x33; BLOCK_SIZE]);
+    assert_eq!(pending_journal(&mut device), Ok(0));
+}
+
+#[test]
+fn update_cut_off_before_its_descriptor_is_lost() {
+    let (mut device, superblock) = formatted();
+    let targets = targets(&superblock);
+
+    update(&mut device, &superblock, &targets, targets.len()).unwrap();
+
+    assert_eq!(replay_journal(&mut device), Ok(0));
+    assert_eq!(device.block(targets[0]), &[0; BLOCK_SIZE]);
+}
+
+#[test]
+fn torn_descriptor_is_ignored() {
+    let (mut device, superblock) = formatted();
+    let targets = targets(&superblock);
+    update(&mut device, &superblock, &targets, targets.len() + 1).unwrap();
+
+    // only the first half of the descriptor reached the disk
+    let second_half = superblock.journal_start + 1;
+    device.block_mut(second_half).fill(0);
+    let descriptor = device.block_mut(superblock.journal_start);
+    descriptor[12 + 4 * 2..].fill(0);
+
+    assert_eq!(replay_journal(&mut device), Ok(0));
+    assert_eq!(device.block(targets[2]), &[0; BLOCK_SIZE]);
+}
+
+#[test]
+fn logged_block_not_matching_the_checksum_is_ignored() {
+    let (mut device, superblock) = formatted();
+    let targets = targets(&superblock);
+    update(&mut device, &superblock, &targets, targets.len() + 1).unwrap();
+
+    device.block_mut(superblock.journal_start + DESCRIPTOR_BLOCKS + 1)[100] ^= 0xFF;
+
+    assert_eq!(pending_journal(&mut device), Ok(0));
+    assert_eq!(replay_journal(&mut device), Ok(0));
+    assert_eq!(device.block(targets[1]), &[0; BLOCK_SIZE]);
+}
+
+#[test]
+fn descriptor_pointing_into_the_journal_is_corrupted() {
+    let (mut device, superblock) = formatted();
+    let targets = [superblock.data_start, superblock.journal_start + DESCRIPTOR_BLOCKS];
+
+    // written directly, the journal would log its own blocks otherwise
+    let mut descriptor = [0; DESCRIPTOR_SIZE];
+    descriptor[0..4].copy_from_slice(&JOURNAL_MAGIC);
+    put_u32(&mut descriptor, 8, targets.len() as u32);
+    let mut hash = CHECKSUM_START;
+    for (index, &target) in targets.iter().enumerate() {
+        put_u32(&mut descriptor, 12 + 4 * index, target);
+        hash = checksum(hash, device.block(superblock.journal_start + DESCRIPTOR_BLOCKS + index as u32));
+    }
+    let hash = checksum(hash, &descriptor[8..12 + 4 * targets.len()]);
+    put_u32(&mut descriptor, 4, hash);
+    device.write(superblock.journal_start as u64, &descriptor).unwrap();
+
+    assert_eq!(replay_journal(&mut device), Err(UnifsError::Corrupted));
+}
@@ -0,0 +1,83 @@
+//! Packs the files in `initrd/` into a USTAR archive, which the kernel
+//! embeds and unpacks into the file tree of the shell at boot.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const BLOCK_SIZE: usize = 512;
+const MAX_NAME_LEN: usize = 100;
+
+fn main() -> io::Result<()> {
+    let source = Path::new("initrd");
+    println!("cargo:rerun-if-changed=initrd");
+
+    let mut archive = Vec::new();
+    if source.is_dir() {
+        add_directory(&mut archive, source, "")?;
+    }
+    // two zero blocks end the archive
+    archive.resize(archive.len() + 2 * BLOCK_SIZE, 0);
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+    fs::write(out_dir.join("initrd.tar"), archive)
+}
+
+/// Adds everything in `directory` with names starting with `prefix`, in
+/// alphabetical order so that every build gives the same archive.
+fn add_directory(archive: &mut Vec<u8>, directory: &Path, prefix: &str) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not UTF-8", name)))?;
+        let path = format!("{}{}", prefix, name);
+
+        if entry.file_type()?.is_dir() {
+            let path = format!("{}/", path);
+            add_entry(archive, &path, b'5', &[])?;
+            add_directory(archive, &entry.path(), &path)?;
+        } else {
+            add_entry(archive, &path, b'0', &fs::read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+fn add_entry(archive: &mut Vec<u8>, path: &str, kind: u8, data: &[u8]) -> io::Result<()> {
+    if path.len() > MAX_NAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is too long", path)));
+    }
+
+    let mut header = [0u8; BLOCK_SIZE];
+    header[..path.len()].copy_from_slice(path.as_bytes());
+    put_octal(&mut header[100..108], if kind == b'5' { 0o755 } else { 0o644 });
+    put_octal(&mut header[108..116], 0);
+    put_octal(&mut header[116..124], 0);
+    put_octal(&mut header[124..136], data.len() as u64);
+    put_octal(&mut header[136..148], 0);
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // the checksum counts its own field as spaces and ends with NUL, space
+    header[148..156].fill(b' ');
+    let checksum = header.iter().map(|&b| b as u64).sum();
+    put_octal(&mut header[148..155], checksum);
+
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(data);
+    archive.resize((archive.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE, 0);
+    Ok(())
+}
+
+/// Writes `value` as octal digits filling `field`, followed by a NUL.
+fn put_octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    field[..width].copy_from_slice(format!("{:0width$o}", value, width = width).as_bytes());
+    field[width] = 0;
+}
@@ -0,0 +1,329 @@
+use x86_64::instructions::port::Port;
+
+use crate::block::{sectors_of, BlockDevice, BlockError, SECTOR_SIZE};
+
+const PRIMARY_IO_BASE: u16 = 0x1F0;
+const PRIMARY_CONTROL: u16 = 0x3F6;
+const SECONDARY_IO_BASE: u16 = 0x170;
+const SECONDARY_CONTROL: u16 = 0x376;
+
+// registers relative to the I/O base
+const REGISTER_DATA: u16 = 0;
+const REGISTER_SECTOR_COUNT: u16 = 2;
+const REGISTER_LBA_LOW: u16 = 3;
+const REGISTER_LBA_MID: u16 = 4;
+const REGISTER_LBA_HIGH: u16 = 5;
+const REGISTER_DRIVE: u16 = 6;
+/// Status when read, command when written.
+const REGISTER_STATUS: u16 = 7;
+
+const STATUS_ERROR: u8 = 0x01;
+const STATUS_DATA_REQUEST: u8 = 0x08;
+const STATUS_DEVICE_FAULT: u8 = 0x20;
+const STATUS_BUSY: u8 = 0x80;
+/// What the status of a bus without any drive reads as.
+const STATUS_FLOATING: u8 = 0xFF;
+
+/// Written to the control register, the driver polls instead of taking
+/// interrupts.
+const CONTROL_NO_INTERRUPTS: u8 = 0x02;
+
+const DRIVE_IDENTIFY: u8 = 0xA0;
+const DRIVE_LBA: u8 = 0x40;
+const DRIVE_LBA28: u8 = 0xE0;
+const DRIVE_SLAVE: u8 = 0x10;
+
+const COMMAND_READ_SECTORS: u8 = 0x20;
+const COMMAND_READ_SECTORS_EXT: u8 = 0x24;
+const COMMAND_WRITE_SECTORS: u8 = 0x30;
+const COMMAND_WRITE_SECTORS_EXT: u8 = 0x34;
+const COMMAND_CACHE_FLUSH: u8 = 0xE7;
+const COMMAND_CACHE_FLUSH_EXT: u8 = 0xEA;
+const COMMAND_IDENTIFY: u8 = 0xEC;
+
+// words of the IDENTIFY data
+const IDENTIFY_MODEL: usize = 27;
+const IDENTIFY_SECTORS_LBA28: usize = 60;
+const IDENTIFY_COMMAND_SETS: usize = 83;
+const IDENTIFY_SECTORS_LBA48: usize = 100;
+const COMMAND_SET_LBA48: u16 = 1 << 10;
+
+/// Sectors below this number can be reached with 28-bit commands.
+const LBA28_LIMIT: u64 = 1 << 28;
+/// A sector count of 0 in a command stands for 256 sectors, with 48-bit
+/// commands more would be possible but this keeps both the same.
+const MAX_SECTORS_PER_COMMAND: usize = 256;
+/// How often the status is read before giving up on a drive.
+const TIMEOUT_POLLS: usize = 1_000_000;
+
+const SIZE_MODEL: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    Primary,
+    Secondary,
+}
+
+impl Bus {
+    fn io_base(self) -> u16 {
+        match self {
+            Bus::Primary => PRIMARY_IO_BASE,
+            Bus::Secondary => SECONDARY_IO_BASE,
+        }
+    }
+
+    fn control(self) -> u16 {
+        match self {
+            Bus::Primary => PRIMARY_CONTROL,
+            Bus::Secondary => SECONDARY_CONTROL,
+        }
+    }
+
+    fn read(self, register: u16) -> u8 {
+        let mut port: Port<u8> = Port::new(self.io_base() + register);
+        unsafe { port.read() }
+    }
+
+    fn write(self, register: u16, value: u8) {
+        let mut port: Port<u8> = Port::new(self.io_base() + register);
+        unsafe { port.write(value) }
+    }
+
+    fn data(self) -> Port<u16> {
+        Port::new(self.io_base() + REGISTER_DATA)
+    }
+
+    /// Waits the 400ns a drive needs to show its status after being
+    /// selected, reading the alternate status takes about 100ns.
+    fn delay(self) {
+        let mut alternate_status: Port<u8> = Port::new(self.control());
+        for _ in 0..4 {
+            unsafe {
+                alternate_status.read();
+            }
+        }
+    }
+
+    fn select(self, drive: u8) {
+        self.write(REGISTER_DRIVE, drive);
+        self.delay();
+    }
+
+    fn disable_interrupts(self) {
+        let mut control: Port<u8> = Port::new(self.control());
+        unsafe { control.write(CONTROL_NO_INTERRUPTS) }
+    }
+
+    fn wait_not_busy(self) -> Result<u8, BlockError> {
+        for _ in 0..TIMEOUT_POLLS {
+            let status = self.read(REGISTER_STATUS);
+            if status & STATUS_BUSY == 0 {
+                return match status & (STATUS_ERROR | STATUS_DEVICE_FAULT) {
+                    0 => Ok(status),
+                    _ => Err(BlockError::DeviceError),
+                };
+            }
+        }
+        Err(BlockError::Timeout)
+    }
+
+    /// Waits until the drive is ready to hand over or take a sector.
+    fn wait_data(self) -> Result<(), BlockError> {
+        for _ in 0..TIMEOUT_POLLS {
+            let status = self.read(REGISTER_STATUS);
+            if status & STATUS_BUSY != 0 {
+                continue;
+            }
+            if status & (STATUS_ERROR | STATUS_DEVICE_FAULT) != 0 {
+                return Err(BlockError::DeviceError);
+            }
+            if status & STATUS_DATA_REQUEST != 0 {
+                return Ok(());
+            }
+        }
+        Err(BlockError::Timeout)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drive {
+    Master,
+    Slave,
+}
+
+impl Drive {
+    fn bit(self) -> u8 {
+        match self {
+            Drive::Master => 0,
+            Drive::Slave => DRIVE_SLAVE,
+        }
+    }
+}
+
+/// An ATA hard disk driven with programmed I/O.
+#[derive(Debug, Clone, Copy)]
+pub struct AtaDisk {
+    pub bus: Bus,
+    pub drive: Drive,
+    sectors: u64,
+    /// Whether the disk knows 48-bit commands, needed past 128 GiB.
+    pub lba48: bool,
+    model: [u8; SIZE_MODEL],
+}
+
+/// IDENTIFY strings hold two characters per word, the first one in the
+/// high byte, and are padded with blanks.
+fn identify_string(words: &[u16], text: &mut [u8]) {
+    for (pair, word) in text.chunks_mut(2).zip(words) {
+        pair.copy_from_slice(&word.to_be_bytes());
+    }
+}
+
+impl AtaDisk {
+    /// Asks the drive for its IDENTIFY data, returning `None` if there is
+    /// no drive or it is not an ATA disk, e.g. a CD-ROM drive.
+    pub fn identify(bus: Bus, drive: Drive) -> Option<AtaDisk> {
+        if bus.read(REGISTER_STATUS) == STATUS_FLOATING {
+            return None;
+        }
+
+        bus.disable_interrupts();
+        bus.select(DRIVE_IDENTIFY | drive.bit());
+        for register in [REGISTER_SECTOR_COUNT, REGISTER_LBA_LOW, REGISTER_LBA_MID, REGISTER_LBA_HIGH] {
+            bus.write(register, 0);
+        }
+        bus.write(REGISTER_STATUS, COMMAND_IDENTIFY);
+
+        if bus.read(REGISTER_STATUS) == 0 {
+            return None;
+        }
+        bus.wait_not_busy().ok()?;
+
+        // ATAPI and SATA devices put their signature here and abort
+        if bus.read(REGISTER_LBA_MID) != 0 || bus.read(REGISTER_LBA_HIGH) != 0 {
+            return None;
+        }
+        bus.wait_data().ok()?;
+
+        let mut words = [0u16; SECTOR_SIZE / 2];
+        let mut data = bus.data();
+        for word in words.iter_mut() {
+            *word = unsafe { data.read() };
+        }
+
+        let lba48 = words[IDENTIFY_COMMAND_SETS] & COMMAND_SET_LBA48 != 0;
+        let sectors = match lba48 {
+            true => words[IDENTIFY_SECTORS_LBA48..IDENTIFY_SECTORS_LBA48 + 4]
+                .iter()
+                .rev()
+                .fold(0, |sectors, &word| sectors << 16 | word as u64),
+            false => (words[IDENTIFY_SECTORS_LBA28 + 1] as u64) << 16 | words[IDENTIFY_SECTORS_LBA28] as u64,
+        };
+
+        let mut disk = AtaDisk {
+            bus,
+            drive,
+            sectors,
+            lba48,
+            model: [0; SIZE_MODEL],
+        };
+        identify_string(&words[IDENTIFY_MODEL..], &mut disk.model);
+        Some(disk)
+    }
+
+    pub fn model(&self) -> &str {
+        core::str::from_utf8(&self.model).unwrap_or("").trim()
+    }
+
+    /// Sends a read or write command for `count` sectors starting at `lba`,
+    /// with a 48-bit address only where a 28-bit one does not do.
+    fn start(&self, lba: u64, count: usize, command28: u8, command48: u8) -> Result<(), BlockError> {
+        let bus = self.bus;
+        bus.wait_not_busy()?;
+
+        if lba + count as u64 <= LBA28_LIMIT {
+            bus.select(DRIVE_LBA28 | self.drive.bit() | (lba >> 24) as u8 & 0x0F);
+            // a count of 256 is written as 0
+            bus.write(REGISTER_SECTOR_COUNT, count as u8);
+            bus.write(REGISTER_LBA_LOW, lba as u8);
+            bus.write(REGISTER_LBA_MID, (lba >> 8) as u8);
+            bus.write(REGISTER_LBA_HIGH, (lba >> 16) as u8);
+            bus.write(REGISTER_STATUS, command28);
+            return Ok(());
+        }
+
+        if !self.lba48 {
+            return Err(BlockError::OutOfRange);
+        }
+
+        // the registers take the high bytes first, then the low ones
+        bus.select(DRIVE_LBA | self.drive.bit());
+        bus.write(REGISTER_SECTOR_COUNT, (count >> 8) as u8);
+        bus.write(REGISTER_LBA_LOW, (lba >> 24) as u8);
+        bus.write(REGISTER_LBA_MID, (lba >> 32) as u8);
+        bus.write(REGISTER_LBA_HIGH, (lba >> 40) as u8);
+        bus.write(REGISTER_SECTOR_COUNT, count as u8);
+        bus.write(REGISTER_LBA_LOW, lba as u8);
+        bus.write(REGISTER_LBA_MID, (lba >> 8) as u8);
+        bus.write(REGISTER_LBA_HIGH, (lba >> 16) as u8);
+        bus.write(REGISTER_STATUS, command48);
+        Ok(())
+    }
+}
+
+impl BlockDevice for AtaDisk {
+    fn sector_count(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        let mut data = self.bus.data();
+
+        for (i, chunk) in buffer.chunks_mut(MAX_SECTORS_PER_COMMAND * SECTOR_SIZE).enumerate() {
+            let lba = lba + (i * MAX_SECTORS_PER_COMMAND) as u64;
+            self.start(lba, chunk.len() / SECTOR_SIZE, COMMAND_READ_SECTORS, COMMAND_READ_SECTORS_EXT)?;
+
+            for sector in chunk.chunks_mut(SECTOR_SIZE) {
+                self.bus.wait_data()?;
+                for pair in sector.chunks_mut(2) {
+                    let word = unsafe { data.read() };
+                    pair.copy_from_slice(&word.to_le_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        let mut data = self.bus.data();
+
+        for (i, chunk) in buffer.chunks(MAX_SECTORS_PER_COMMAND * SECTOR_SIZE).enumerate() {
+            let lba = lba + (i * MAX_SECTORS_PER_COMMAND) as u64;
+            self.start(lba, chunk.len() / SECTOR_SIZE, COMMAND_WRITE_SECTORS, COMMAND_WRITE_SECTORS_EXT)?;
+
+            for sector in chunk.chunks(SECTOR_SIZE) {
+                self.bus.wait_data()?;
+                for pair in sector.chunks(2) {
+                    unsafe { data.write(u16::from_le_bytes([pair[0], pair[1]])) }
+                }
+            }
+            self.bus.wait_not_busy()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        let command = match self.lba48 {
+            true => COMMAND_CACHE_FLUSH_EXT,
+            false => COMMAND_CACHE_FLUSH,
+        };
+
+        self.bus.wait_not_busy()?;
+        self.bus.select(DRIVE_LBA28 | self.drive.bit());
+        self.bus.write(REGISTER_STATUS, command);
+        self.bus.wait_not_busy()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+//! Block devices are defined next to UniFS, which reads and writes them in
+//! the kernel as well as in `unifs-tool`.
+
+pub use unifs::block::{sectors_of, BlockDevice, BlockError, SECTOR_SIZE};
@@ -0,0 +1,209 @@
+//! Sectors of a block device kept in memory, so that file systems do not
+//! go to the disk for every sector they look at.
+//!
+//! Writes only change the cached sector, which is written back when it is
+//! evicted, on `flush` or by `write_back` from the timer. The least recently
+//! used sector makes room for a new one. A sector not in the cache is read
+//! together with the ones behind it, file systems mostly read on from there.
+
+use crate::block::{sectors_of, BlockDevice, BlockError, SECTOR_SIZE};
+
+/// Sectors one cache holds.
+pub const CACHE_SECTORS: usize = 32;
+/// Sectors read with a missing one, itself included.
+pub const READ_AHEAD: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    lba: u64,
+    valid: bool,
+    /// Changed since it was read or last written back.
+    dirty: bool,
+    /// Value of the clock when the sector was used last.
+    used: u64,
+    data: [u8; SECTOR_SIZE],
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        lba: 0,
+        valid: false,
+        dirty: false,
+        used: 0,
+        data: [0; SECTOR_SIZE],
+    };
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Sectors read or written that were in the cache.
+    pub hits: u64,
+    /// Sectors read that had to come from the device.
+    pub misses: u64,
+    /// Sectors read before anyone asked for them.
+    pub read_ahead: u64,
+    /// Sectors written to the device.
+    pub written_back: u64,
+}
+
+pub struct BlockCache<D> {
+    device: D,
+    slots: [Slot; CACHE_SECTORS],
+    /// Counts every use of a sector, the least recently used one has the
+    /// smallest value.
+    clock: u64,
+    pub stats: CacheStats,
+}
+
+impl<D: BlockDevice> BlockCache<D> {
+    pub fn new(device: D) -> BlockCache<D> {
+        BlockCache {
+            device,
+            slots: [Slot::EMPTY; CACHE_SECTORS],
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The device behind the cache. Reading or writing it directly would
+    /// bypass the cache.
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    pub fn count_cached(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.valid).count()
+    }
+
+    pub fn count_dirty(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.dirty).count()
+    }
+
+    fn find(&self, lba: u64) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.valid && slot.lba == lba)
+    }
+
+    fn touch(&mut self, index: usize) {
+        self.clock += 1;
+        self.slots[index].used = self.clock;
+    }
+
+    fn write_slot(&mut self, index: usize) -> Result<(), BlockError> {
+        let slot = &mut self.slots[index];
+        self.device.write(slot.lba, &slot.data)?;
+        slot.dirty = false;
+        self.stats.written_back += 1;
+        Ok(())
+    }
+
+    /// Slot for a sector not in the cache, an empty one or else the least
+    /// recently used one, written back first when it is dirty.
+    fn take_slot(&mut self) -> Result<usize, BlockError> {
+        let index = match self.slots.iter().position(|slot| !slot.valid) {
+            Some(index) => index,
+            None => (0..CACHE_SECTORS).min_by_key(|&index| self.slots[index].used).unwrap_or(0),
+        };
+
+        if self.slots[index].dirty {
+            self.write_slot(index)?;
+        }
+        self.slots[index].valid = false;
+        Ok(index)
+    }
+
+    /// Reads the missing sector `lba` and the ones behind it that are not
+    /// cached yet, returning the slot of `lba`.
+    fn load(&mut self, lba: u64) -> Result<usize, BlockError> {
+        let count = (self.device.sector_count() - lba).min(READ_AHEAD as u64) as usize;
+        let mut buffer = [0; READ_AHEAD * SECTOR_SIZE];
+        self.device.read(lba, &mut buffer[..count * SECTOR_SIZE])?;
+        self.stats.misses += 1;
+
+        // `lba` itself comes last, so it is the most recently used
+        let mut index = 0;
+        for offset in (0..count).rev() {
+            let sector_lba = lba + offset as u64;
+            // a cached sector may have been changed since
+            if offset > 0 && self.find(sector_lba).is_some() {
+                continue;
+            }
+
+            index = self.take_slot()?;
+            let slot = &mut self.slots[index];
+            slot.lba = sector_lba;
+            slot.valid = true;
+            slot.data.copy_from_slice(&buffer[offset * SECTOR_SIZE..(offset + 1) * SECTOR_SIZE]);
+            self.touch(index);
+
+            if offset > 0 {
+                self.stats.read_ahead += 1;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Writes all dirty sectors to the device, which may still hold them in
+    /// its own cache.
+    pub fn write_back(&mut self) -> Result<(), BlockError> {
+        for index in 0..CACHE_SECTORS {
+            if self.slots[index].dirty {
+                self.write_slot(index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> BlockDevice for BlockCache<D> {
+    fn sector_count(&self) -> u64 {
+        self.device.sector_count()
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+
+        for (offset, sector) in buffer.chunks_mut(SECTOR_SIZE).enumerate() {
+            let lba = lba + offset as u64;
+            let index = match self.find(lba) {
+                Some(index) => {
+                    self.stats.hits += 1;
+                    index
+                }
+                None => self.load(lba)?,
+            };
+
+            sector.copy_from_slice(&self.slots[index].data);
+            self.touch(index);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+
+        for (offset, sector) in buffer.chunks(SECTOR_SIZE).enumerate() {
+            let lba = lba + offset as u64;
+            // the whole sector is replaced, nothing needs to be read
+            let index = match self.find(lba) {
+                Some(index) => {
+                    self.stats.hits += 1;
+                    index
+                }
+                None => self.take_slot()?,
+            };
+
+            let slot = &mut self.slots[index];
+            slot.lba = lba;
+            slot.valid = true;
+            slot.dirty = true;
+            slot.data.copy_from_slice(sector);
+            self.touch(index);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        self.write_back()?;
+        self.device.flush()
+    }
+}
@@ -0,0 +1,161 @@
+//! Every disk the kernel found, whatever drives it, each behind a cache.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+use crate::ata::{AtaDisk, Bus, Drive};
+use crate::block::{BlockDevice, BlockError, SECTOR_SIZE};
+use crate::cache::BlockCache;
+use crate::interrupts;
+use crate::pci::{self, PciDevice, ProbeError};
+use crate::println;
+use crate::virtio::{self, VirtioBlk};
+
+/// ATA disks come first, in the order primary master, primary slave,
+/// secondary master, secondary slave, then the virtio disks.
+const MAX_COUNT_ATA_DISKS: usize = 4;
+pub const MAX_COUNT_DISKS: usize = MAX_COUNT_ATA_DISKS + virtio::MAX_COUNT_DEVICES;
+/// Timer ticks between two write-backs of the caches, about 5 seconds at
+/// the 18.2 Hz the timer runs at.
+const WRITE_BACK_TICKS: u32 = 91;
+
+const NO_DISK: Option<BlockCache<Disk>> = None;
+/// Disks found by `init`, at the index their name stands for.
+pub static DISKS: Mutex<[Option<BlockCache<Disk>>; MAX_COUNT_DISKS]> = Mutex::new([NO_DISK; MAX_COUNT_DISKS]);
+static TICKS: AtomicU32 = AtomicU32::new(0);
+/// Timer tick the caches were last written back on.
+static LAST_WRITE_BACK: AtomicU32 = AtomicU32::new(0);
+
+pub enum Disk {
+    Ata(AtaDisk),
+    Virtio(VirtioBlk),
+}
+
+impl Disk {
+    pub fn model(&self) -> &str {
+        match self {
+            Disk::Ata(disk) => disk.model(),
+            Disk::Virtio(_) => "virtio-blk",
+        }
+    }
+}
+
+impl BlockDevice for Disk {
+    fn sector_count(&self) -> u64 {
+        match self {
+            Disk::Ata(disk) => disk.sector_count(),
+            Disk::Virtio(disk) => disk.sector_count(),
+        }
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        match self {
+            Disk::Ata(disk) => disk.read(lba, buffer),
+            Disk::Virtio(disk) => disk.read(lba, buffer),
+        }
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        match self {
+            Disk::Ata(disk) => disk.write(lba, buffer),
+            Disk::Virtio(disk) => disk.write(lba, buffer),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        match self {
+            Disk::Ata(disk) => disk.flush(),
+            Disk::Virtio(disk) => disk.flush(),
+        }
+    }
+}
+
+impl fmt::Display for Disk {
+    /// Size the way disks are sold, in powers of ten.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sectors = self.sector_count();
+        let bytes = sectors * SECTOR_SIZE as u64;
+        let (size, unit) = match bytes {
+            0..=999_999 => (bytes / 1000, "kB"),
+            1_000_000..=999_999_999 => (bytes / 1_000_000, "MB"),
+            _ => (bytes / 1_000_000_000, "GB"),
+        };
+        write!(f, "{} {}, {} sectors", size, unit, sectors)
+    }
+}
+
+/// Name of the disk at `index` in `DISKS`, the same as Linux gives it.
+pub fn disk_name(index: usize) -> &'static str {
+    ["hda", "hdb", "hdc", "hdd", "vda", "vdb", "vdc", "vdd"][index]
+}
+
+/// Takes the virtio disks `pci::init` finds, in the slots behind the ATA
+/// disks.
+struct VirtioDriver;
+
+static VIRTIO_DRIVER: VirtioDriver = VirtioDriver;
+
+impl pci::Driver for VirtioDriver {
+    fn name(&self) -> &'static str {
+        "virtio-blk"
+    }
+
+    fn ids(&self) -> &'static [(u16, u16)] {
+        &[(virtio::VENDOR_ID, virtio::DEVICE_ID_BLOCK)]
+    }
+
+    fn probe(&self, device: &PciDevice) -> Result<(), ProbeError> {
+        let mut disks = DISKS.lock();
+        let slot = (0..virtio::MAX_COUNT_DEVICES)
+            .find(|&slot| disks[MAX_COUNT_ATA_DISKS + slot].is_none())
+            .ok_or(ProbeError::TooManyDevices)?;
+
+        let disk = VirtioBlk::new(device, slot)?;
+        // without its interrupt the disk is only looked at again on the
+        // next timer tick while the kernel sleeps
+        if !interrupts::enable_pci_interrupt(disk.irq) {
+            println!("virtio-blk {}: IRQ {} is not supported", device.address, disk.irq);
+        }
+        disks[MAX_COUNT_ATA_DISKS + slot] = Some(BlockCache::new(Disk::Virtio(disk)));
+        Ok(())
+    }
+}
+
+/// Looks for disks on both IDE buses and registers the driver for virtio
+/// disks, which `pci::init` hands them to.
+pub fn init() {
+    let mut disks = DISKS.lock();
+
+    for (index, bus) in [Bus::Primary, Bus::Secondary].into_iter().enumerate() {
+        for (slot, drive) in [Drive::Master, Drive::Slave].into_iter().enumerate() {
+            disks[2 * index + slot] = AtaDisk::identify(bus, drive).map(|disk| BlockCache::new(Disk::Ata(disk)));
+        }
+    }
+
+    pci::register_driver(&VIRTIO_DRIVER);
+}
+
+/// Timer ticks since the interrupts were turned on.
+pub fn ticks() -> u32 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+pub fn on_timer_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Called from the idle loop of the kernel, now and then writes back what
+/// the caches hold. A disk that fails keeps its sectors dirty for the next
+/// turn.
+pub fn write_back() {
+    let ticks = ticks();
+    if ticks.wrapping_sub(LAST_WRITE_BACK.load(Ordering::Relaxed)) < WRITE_BACK_TICKS {
+        return;
+    }
+    LAST_WRITE_BACK.store(ticks, Ordering::Relaxed);
+
+    for disk in DISKS.lock().iter_mut().flatten().filter(|disk| disk.count_dirty() > 0) {
+        let _ = disk.flush();
+    }
+}
@@ -0,0 +1,1061 @@
+//! FAT12, FAT16 and FAT32 file systems as `mkfs.fat` makes them, either
+//! filling the whole disk or in its first partition, with long file names.
+
+use core::fmt;
+
+use crate::block::{BlockDevice, BlockError, SECTOR_SIZE};
+use crate::rtc::DateTime;
+
+pub const MAX_NAME_LEN: usize = 255;
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ENTRIES_PER_SECTOR: usize = SECTOR_SIZE / DIR_ENTRY_SIZE;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+/// Marks the entries holding parts of a long name.
+const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+/// First byte of an entry that was deleted.
+const ENTRY_FREE: u8 = 0xE5;
+/// First byte of the entry behind the last one in use.
+const ENTRY_END: u8 = 0x00;
+/// Set in the sequence number of the long name entry holding the end of
+/// the name, which comes first.
+const LFN_LAST: u8 = 0x40;
+const LFN_SEQUENCE: u8 = 0x1F;
+const CHARS_PER_LFN: usize = 13;
+/// Where the characters of a long name entry are stored.
+const LFN_CHAR_OFFSETS: [usize; CHARS_PER_LFN] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const MAX_LFN_ENTRIES: usize = (MAX_NAME_LEN + CHARS_PER_LFN - 1) / CHARS_PER_LFN;
+/// Set by Windows in byte 12 of a short entry whose base name or extension
+/// is all lowercase.
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXTENSION: u8 = 0x10;
+
+/// Below this many clusters a volume is FAT12, below the next FAT16.
+const MAX_CLUSTERS_FAT12: u32 = 4085;
+const MAX_CLUSTERS_FAT16: u32 = 65525;
+const FAT32_MASK: u32 = 0x0FFF_FFFF;
+
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+/// Partition types of FAT volumes in a master boot record.
+const PARTITION_TYPES: [u8; 7] = [0x01, 0x04, 0x06, 0x0B, 0x0C, 0x0E, 0x0F];
+const FS_INFO_SIGNATURE_START: u32 = 0x4161_5252;
+const FS_INFO_SIGNATURE_MIDDLE: u32 = 0x6141_7272;
+const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatError {
+    Block(BlockError),
+    /// The disk holds no FAT file system.
+    NotFat,
+    NotFound,
+    AlreadyExists,
+    NotDirectory,
+    IsDirectory,
+    DirectoryNotEmpty,
+    NoSpace,
+    InvalidName,
+    Corrupted,
+}
+
+impl From<BlockError> for FatError {
+    fn from(error: BlockError) -> FatError {
+        FatError::Block(error)
+    }
+}
+
+impl fmt::Display for FatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FatError::Block(error) => error.fmt(f),
+            FatError::NotFat => f.write_str("Disk holds no FAT file system"),
+            FatError::NotFound => f.write_str("No such file or directory"),
+            FatError::AlreadyExists => f.write_str("File or directory already exists"),
+            FatError::NotDirectory => f.write_str("Not a directory"),
+            FatError::IsDirectory => f.write_str("Is a directory"),
+            FatError::DirectoryNotEmpty => f.write_str("Directory is not empty"),
+            FatError::NoSpace => f.write_str("Disk is full"),
+            FatError::InvalidName => f.write_str("Invalid name"),
+            FatError::Corrupted => f.write_str("File system is corrupted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatKind {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl fmt::Display for FatKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FatKind::Fat12 => "FAT12",
+            FatKind::Fat16 => "FAT16",
+            FatKind::Fat32 => "FAT32",
+        })
+    }
+}
+
+/// A directory: the fixed root directory of FAT12 and FAT16 or one stored
+/// in clusters like files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    Root,
+    Cluster(u32),
+}
+
+/// A name of up to `MAX_NAME_LEN` bytes of UTF-8.
+#[derive(Clone, Copy)]
+pub struct Name {
+    bytes: [u8; MAX_NAME_LEN],
+    len: usize,
+}
+
+impl Name {
+    const fn new() -> Name {
+        Name {
+            bytes: [0; MAX_NAME_LEN],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        let mut encoded = [0; 4];
+        let encoded = c.encode_utf8(&mut encoded).as_bytes();
+
+        if self.len + encoded.len() <= MAX_NAME_LEN {
+            self.bytes[self.len..self.len + encoded.len()].copy_from_slice(encoded);
+            self.len += encoded.len();
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+/// A file or directory as listed in its directory.
+#[derive(Clone, Copy)]
+pub struct FatEntry {
+    pub name: Name,
+    short_name: [u8; 11],
+    pub attributes: u8,
+    pub cluster: u32,
+    pub size: u32,
+    pub modified: DateTime,
+    /// Position of the short entry in its directory.
+    slot: usize,
+    /// Entries taken by the long name and the short entry.
+    count_slots: usize,
+}
+
+impl FatEntry {
+    pub fn is_directory(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    fn is_dot(&self) -> bool {
+        self.short_name[0] == b'.'
+    }
+}
+
+fn get_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn get_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn put_u16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn decode_time(date: u16, time: u16) -> DateTime {
+    DateTime {
+        year: 1980 + (date >> 9),
+        month: (date >> 5 & 0x0F) as u8,
+        day: (date & 0x1F) as u8,
+        hour: (time >> 11) as u8,
+        minute: (time >> 5 & 0x3F) as u8,
+        second: ((time & 0x1F) * 2) as u8,
+    }
+}
+
+/// Date and time fields of a directory entry.
+fn encode_time(time: DateTime) -> (u16, u16) {
+    let date = (time.year.saturating_sub(1980) << 9) | (time.month as u16) << 5 | time.day as u16;
+    let time = (time.hour as u16) << 11 | (time.minute as u16) << 5 | time.second as u16 / 2;
+    (date, time)
+}
+
+/// Checksum of a short name, which the long name entries belonging to it
+/// repeat.
+fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &b| ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(b))
+}
+
+/// `NAME    EXT` as `name.ext`, in lowercase where byte 12 asks for it.
+fn short_name_to_name(short_name: &[u8; 11], case: u8) -> Name {
+    let mut name = Name::new();
+    let base = &short_name[..8];
+    let extension = &short_name[8..];
+
+    let mut push = |part: &[u8], lower: bool| {
+        for &b in part.iter().take_while(|&&b| b != b' ') {
+            let c = if lower { b.to_ascii_lowercase() } else { b };
+            name.push(if c.is_ascii() { c as char } else { '?' });
+        }
+    };
+
+    push(base, case & NT_LOWER_BASE != 0);
+    if extension[0] != b' ' {
+        push(b".", false);
+        push(extension, case & NT_LOWER_EXTENSION != 0);
+    }
+    name
+}
+
+fn is_short_name_char(b: u8) -> bool {
+    b.is_ascii_uppercase() || b.is_ascii_digit() || b"!#$%&'()-@^_`{}~".contains(&b)
+}
+
+/// The short entry for `name` if it is a valid 8.3 name in uppercase and
+/// needs no long name.
+fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = match name.split_once('.') {
+        Some((base, extension)) => (base, extension),
+        None => (name, ""),
+    };
+
+    let valid = |part: &str, max: usize| part.len() <= max && part.bytes().all(is_short_name_char);
+    if base.is_empty() || !valid(base, 8) || !valid(extension, 3) || name.ends_with('.') {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    Some(short_name)
+}
+
+/// Short name made from a long one with `~number` in it, e.g. `LONGNA~1TXT`.
+fn numbered_short_name(name: &str, number: u32) -> [u8; 11] {
+    let (base, extension) = match name.rsplit_once('.') {
+        Some((base, extension)) if !base.is_empty() => (base, extension),
+        _ => (name, ""),
+    };
+    let clean = |b: u8| match b.to_ascii_uppercase() {
+        b if is_short_name_char(b) => b,
+        _ => b'_',
+    };
+
+    let mut tail = [0u8; 8];
+    let mut tail_len = 0;
+    let mut rest = number;
+    loop {
+        tail[tail_len] = b'0' + (rest % 10) as u8;
+        tail_len += 1;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+    tail[tail_len] = b'~';
+    tail_len += 1;
+    tail[..tail_len].reverse();
+
+    let mut short_name = [b' '; 11];
+    let base_bytes = base.bytes().filter(|&b| b != b' ' && b != b'.').map(clean);
+    let mut len = 0;
+    for b in base_bytes.take(8 - tail_len) {
+        short_name[len] = b;
+        len += 1;
+    }
+    short_name[len..len + tail_len].copy_from_slice(&tail[..tail_len]);
+
+    for (i, b) in extension.bytes().filter(|&b| b != b' ').map(clean).take(3).enumerate() {
+        short_name[8 + i] = b;
+    }
+    short_name
+}
+
+/// Checks that `name` may be used for a new entry and turns it into the
+/// UCS-2 characters of a long name.
+fn long_name_units(name: &str, units: &mut [u16; MAX_NAME_LEN]) -> Result<usize, FatError> {
+    if name.is_empty() || name == "." || name == ".." || name.ends_with(' ') {
+        return Err(FatError::InvalidName);
+    }
+
+    let mut len = 0;
+    for c in name.chars() {
+        if (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c) || (c as u32) > 0xFFFF || len == MAX_NAME_LEN {
+            return Err(FatError::InvalidName);
+        }
+        units[len] = c as u16;
+        len += 1;
+    }
+    Ok(len)
+}
+
+/// A mounted FAT volume on `device`.
+pub struct Volume<'a> {
+    device: &'a mut dyn BlockDevice,
+    pub kind: FatKind,
+    /// First sector of the volume on the device.
+    start: u64,
+    pub sectors_per_cluster: u32,
+    fat_start: u64,
+    fat_sectors: u64,
+    count_fats: u32,
+    /// The fixed root directory of FAT12 and FAT16.
+    root_start: u64,
+    root_sectors: u64,
+    data_start: u64,
+    pub cluster_count: u32,
+    root_cluster: u32,
+    fs_info: Option<u64>,
+    /// The sector of the FAT last read, FAT entries are read one by one.
+    fat_cache: [u8; SECTOR_SIZE],
+    cached_fat_sector: Option<u64>,
+    /// Where to start looking for a free cluster.
+    next_free: u32,
+}
+
+/// Whether `sector` looks like a boot sector with a BIOS parameter block.
+fn is_boot_sector(sector: &[u8; SECTOR_SIZE]) -> bool {
+    let sectors_per_cluster = sector[13];
+    sector[SECTOR_SIZE - 2..] == BOOT_SIGNATURE
+        && get_u16(sector, 11) as usize == SECTOR_SIZE
+        && sectors_per_cluster.is_power_of_two()
+        && get_u16(sector, 14) >= 1
+        && sector[16] >= 1
+}
+
+impl<'a> Volume<'a> {
+    pub fn open(device: &'a mut dyn BlockDevice) -> Result<Volume<'a>, FatError> {
+        let mut sector = [0; SECTOR_SIZE];
+        device.read(0, &mut sector)?;
+
+        let mut start = 0;
+        if !is_boot_sector(&sector) {
+            // a partitioned disk, use the first partition
+            let partition = &sector[446..462];
+            if sector[SECTOR_SIZE - 2..] != BOOT_SIGNATURE || !PARTITION_TYPES.contains(&partition[4]) {
+                return Err(FatError::NotFat);
+            }
+            start = get_u32(partition, 8) as u64;
+            device.read(start, &mut sector)?;
+            if !is_boot_sector(&sector) {
+                return Err(FatError::NotFat);
+            }
+        }
+
+        let sectors_per_cluster = sector[13] as u32;
+        let reserved = get_u16(&sector, 14) as u64;
+        let count_fats = sector[16] as u32;
+        let root_entries = get_u16(&sector, 17) as u64;
+        let total_sectors = match get_u16(&sector, 19) {
+            0 => get_u32(&sector, 32) as u64,
+            count => count as u64,
+        };
+        let fat_sectors = match get_u16(&sector, 22) {
+            0 => get_u32(&sector, 36) as u64,
+            count => count as u64,
+        };
+
+        let root_sectors = (root_entries * DIR_ENTRY_SIZE as u64 + SECTOR_SIZE as u64 - 1) / SECTOR_SIZE as u64;
+        let root_start = reserved + count_fats as u64 * fat_sectors;
+        let data_start = root_start + root_sectors;
+        if fat_sectors == 0 || data_start >= total_sectors || start + total_sectors > device.sector_count() {
+            return Err(FatError::NotFat);
+        }
+
+        let cluster_count = ((total_sectors - data_start) / sectors_per_cluster as u64) as u32;
+        let kind = if cluster_count < MAX_CLUSTERS_FAT12 {
+            FatKind::Fat12
+        } else if cluster_count < MAX_CLUSTERS_FAT16 {
+            FatKind::Fat16
+        } else {
+            FatKind::Fat32
+        };
+
+        let (root_cluster, fs_info) = match kind {
+            FatKind::Fat32 => (get_u32(&sector, 44) & FAT32_MASK, Some(get_u16(&sector, 48) as u64)),
+            _ => (0, None),
+        };
+
+        Ok(Volume {
+            device,
+            kind,
+            start,
+            sectors_per_cluster,
+            fat_start: reserved,
+            fat_sectors,
+            count_fats,
+            root_start,
+            root_sectors,
+            data_start,
+            cluster_count,
+            root_cluster,
+            fs_info: fs_info.filter(|&sector| sector != 0 && sector < reserved),
+            fat_cache: [0; SECTOR_SIZE],
+            cached_fat_sector: None,
+            next_free: 2,
+        })
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster as usize * SECTOR_SIZE
+    }
+
+    fn read_sector(&mut self, sector: u64, buffer: &mut [u8; SECTOR_SIZE]) -> Result<(), FatError> {
+        self.device.read(self.start + sector, buffer)?;
+        Ok(())
+    }
+
+    fn write_sector(&mut self, sector: u64, buffer: &[u8; SECTOR_SIZE]) -> Result<(), FatError> {
+        self.device.write(self.start + sector, buffer)?;
+        Ok(())
+    }
+
+    fn root(&self) -> Dir {
+        match self.kind {
+            FatKind::Fat32 => Dir::Cluster(self.root_cluster),
+            _ => Dir::Root,
+        }
+    }
+
+    /// Directory starting at `cluster`, `..` entries use 0 for the root.
+    fn dir_at(&self, cluster: u32) -> Dir {
+        match cluster {
+            0 => self.root(),
+            cluster => Dir::Cluster(cluster),
+        }
+    }
+
+    fn cluster_sector(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - 2) as u64 * self.sectors_per_cluster as u64
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_count + 2).contains(&cluster)
+    }
+
+    /// Offset in the FAT of the entry of `cluster`, and how many bytes it
+    /// takes up.
+    fn fat_offset(&self, cluster: u32) -> (u64, usize) {
+        let cluster = cluster as u64;
+        match self.kind {
+            FatKind::Fat12 => (cluster + cluster / 2, 2),
+            FatKind::Fat16 => (cluster * 2, 2),
+            FatKind::Fat32 => (cluster * 4, 4),
+        }
+    }
+
+    fn load_fat_sector(&mut self, sector: u64) -> Result<(), FatError> {
+        if self.cached_fat_sector != Some(sector) {
+            let mut buffer = [0; SECTOR_SIZE];
+            self.read_sector(self.fat_start + sector, &mut buffer)?;
+            self.fat_cache = buffer;
+            self.cached_fat_sector = Some(sector);
+        }
+        Ok(())
+    }
+
+    /// Writes the cached sector of the FAT to every copy of the FAT.
+    fn store_fat_sector(&mut self) -> Result<(), FatError> {
+        if let Some(sector) = self.cached_fat_sector {
+            let buffer = self.fat_cache;
+            for copy in 0..self.count_fats as u64 {
+                self.write_sector(self.fat_start + copy * self.fat_sectors + sector, &buffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fat_entry(&mut self, cluster: u32) -> Result<u32, FatError> {
+        let (offset, len) = self.fat_offset(cluster);
+        let mut bytes = [0; 4];
+
+        for (i, byte) in bytes[..len].iter_mut().enumerate() {
+            let offset = offset + i as u64;
+            self.load_fat_sector(offset / SECTOR_SIZE as u64)?;
+            *byte = self.fat_cache[offset as usize % SECTOR_SIZE];
+        }
+
+        let value = u32::from_le_bytes(bytes);
+        Ok(match self.kind {
+            FatKind::Fat12 if cluster % 2 == 1 => value >> 4,
+            FatKind::Fat12 => value & 0x0FFF,
+            FatKind::Fat16 => value,
+            FatKind::Fat32 => value & FAT32_MASK,
+        })
+    }
+
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), FatError> {
+        let (offset, len) = self.fat_offset(cluster);
+        // the bits of the entry, the others belong to a neighbour or are
+        // reserved
+        let (value, mask) = match self.kind {
+            FatKind::Fat12 if cluster % 2 == 1 => (value << 4, 0xFFF0),
+            FatKind::Fat12 => (value & 0x0FFF, 0x0FFF),
+            FatKind::Fat16 => (value, 0xFFFF),
+            FatKind::Fat32 => (value & FAT32_MASK, FAT32_MASK),
+        };
+
+        self.invalidate_fs_info()?;
+        let bytes = value.to_le_bytes().into_iter().zip(u32::to_le_bytes(mask));
+        for (i, (new, mask)) in bytes.take(len).enumerate() {
+            let offset = offset + i as u64;
+            let sector = offset / SECTOR_SIZE as u64;
+
+            if self.cached_fat_sector != Some(sector) {
+                self.store_fat_sector()?;
+                self.load_fat_sector(sector)?;
+            }
+            let byte = &mut self.fat_cache[offset as usize % SECTOR_SIZE];
+            *byte = *byte & !mask | new & mask;
+        }
+        self.store_fat_sector()
+    }
+
+    fn end_of_chain(&self) -> u32 {
+        match self.kind {
+            FatKind::Fat12 => 0x0FFF,
+            FatKind::Fat16 => 0xFFFF,
+            FatKind::Fat32 => FAT32_MASK,
+        }
+    }
+
+    /// The cluster after `cluster` in its chain.
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, FatError> {
+        let next = self.fat_entry(cluster)?;
+        if next >= self.end_of_chain() - 7 {
+            return Ok(None);
+        }
+        match self.is_valid_cluster(next) {
+            true => Ok(Some(next)),
+            false => Err(FatError::Corrupted),
+        }
+    }
+
+    /// FAT32 keeps the number of free clusters in its FS information sector,
+    /// which is marked unknown before the FAT changes.
+    fn invalidate_fs_info(&mut self) -> Result<(), FatError> {
+        let sector = match self.fs_info.take() {
+            Some(sector) => sector,
+            None => return Ok(()),
+        };
+
+        let mut buffer = [0; SECTOR_SIZE];
+        self.read_sector(sector, &mut buffer)?;
+        if get_u32(&buffer, 0) == FS_INFO_SIGNATURE_START && get_u32(&buffer, 484) == FS_INFO_SIGNATURE_MIDDLE {
+            put_u32(&mut buffer, 488, FS_INFO_UNKNOWN);
+            put_u32(&mut buffer, 492, FS_INFO_UNKNOWN);
+            self.write_sector(sector, &buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Takes a free cluster and ends a chain with it, filling it with zeros
+    /// if it is for a directory.
+    fn allocate_cluster(&mut self, zeroed: bool) -> Result<u32, FatError> {
+        let first = self.next_free;
+        let mut cluster = first;
+
+        loop {
+            if self.fat_entry(cluster)? == 0 {
+                break;
+            }
+            cluster = if cluster + 1 >= self.cluster_count + 2 { 2 } else { cluster + 1 };
+            if cluster == first {
+                return Err(FatError::NoSpace);
+            }
+        }
+
+        self.set_fat_entry(cluster, self.end_of_chain())?;
+        self.next_free = cluster;
+
+        if zeroed {
+            let zeros = [0; SECTOR_SIZE];
+            for sector in 0..self.sectors_per_cluster as u64 {
+                self.write_sector(self.cluster_sector(cluster) + sector, &zeros)?;
+            }
+        }
+        Ok(cluster)
+    }
+
+    fn free_chain(&mut self, first: u32) -> Result<(), FatError> {
+        let mut cluster = Some(first).filter(|&cluster| self.is_valid_cluster(cluster));
+
+        while let Some(current) = cluster {
+            cluster = self.next_cluster(current)?;
+            self.set_fat_entry(current, 0)?;
+        }
+        Ok(())
+    }
+
+    /// The `index`th sector of `dir`, `None` past its end.
+    fn dir_sector(&mut self, dir: Dir, index: u64) -> Result<Option<u64>, FatError> {
+        let first = match dir {
+            Dir::Root => return Ok(Some(self.root_start + index).filter(|_| index < self.root_sectors)),
+            Dir::Cluster(first) => first,
+        };
+
+        let mut cluster = first;
+        for _ in 0..index / self.sectors_per_cluster as u64 {
+            cluster = match self.next_cluster(cluster)? {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(self.cluster_sector(cluster) + index % self.sectors_per_cluster as u64))
+    }
+
+    /// Reads entry `slot` of `dir` into `entry`, returning `false` past the
+    /// end of the directory.
+    fn read_slot(&mut self, dir: Dir, slot: usize, entry: &mut [u8; DIR_ENTRY_SIZE]) -> Result<bool, FatError> {
+        let sector = match self.dir_sector(dir, (slot / ENTRIES_PER_SECTOR) as u64)? {
+            Some(sector) => sector,
+            None => return Ok(false),
+        };
+
+        let mut buffer = [0; SECTOR_SIZE];
+        self.read_sector(sector, &mut buffer)?;
+        let offset = slot % ENTRIES_PER_SECTOR * DIR_ENTRY_SIZE;
+        entry.copy_from_slice(&buffer[offset..offset + DIR_ENTRY_SIZE]);
+        Ok(true)
+    }
+
+    fn write_slot(&mut self, dir: Dir, slot: usize, entry: &[u8; DIR_ENTRY_SIZE]) -> Result<(), FatError> {
+        let sector = self
+            .dir_sector(dir, (slot / ENTRIES_PER_SECTOR) as u64)?
+            .ok_or(FatError::Corrupted)?;
+
+        let mut buffer = [0; SECTOR_SIZE];
+        self.read_sector(sector, &mut buffer)?;
+        let offset = slot % ENTRIES_PER_SECTOR * DIR_ENTRY_SIZE;
+        buffer[offset..offset + DIR_ENTRY_SIZE].copy_from_slice(entry);
+        self.write_sector(sector, &buffer)
+    }
+
+    /// Goes through the entries of `dir` until `f` returns `true` and
+    /// returns that entry. Long names are put together from their parts.
+    fn scan(&mut self, dir: Dir, f: &mut dyn FnMut(&FatEntry) -> bool) -> Result<Option<FatEntry>, FatError> {
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        let mut units = [0u16; MAX_LFN_ENTRIES * CHARS_PER_LFN];
+        // sequence number expected next, checksum and first slot of the
+        // long name being read
+        let mut long_name: Option<(u8, u8, usize)> = None;
+        let mut slot = 0;
+
+        while self.read_slot(dir, slot, &mut raw)? {
+            match raw[0] {
+                ENTRY_END => break,
+                ENTRY_FREE => long_name = None,
+                _ if raw[11] & ATTR_LONG_NAME == ATTR_LONG_NAME => {
+                    let sequence = raw[0] & LFN_SEQUENCE;
+                    let expected = match (raw[0] & LFN_LAST != 0, long_name) {
+                        (true, _) => {
+                            units.fill(0xFFFF);
+                            long_name = Some((sequence, raw[13], slot));
+                            sequence
+                        }
+                        (false, Some((expected, sum, _))) if raw[13] == sum => expected,
+                        _ => 0,
+                    };
+
+                    if sequence == 0 || sequence as usize > MAX_LFN_ENTRIES || sequence != expected {
+                        long_name = None;
+                    } else {
+                        let start = (sequence as usize - 1) * CHARS_PER_LFN;
+                        for (i, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                            units[start + i] = get_u16(&raw, offset);
+                        }
+                        long_name = long_name.map(|(_, sum, first)| (sequence - 1, sum, first));
+                    }
+                }
+                _ if raw[11] & ATTR_VOLUME_ID != 0 => long_name = None,
+                _ => {
+                    let mut short_name = [0; 11];
+                    short_name.copy_from_slice(&raw[..11]);
+                    let entry = self.entry_from_raw(&raw, short_name, &units, long_name, slot);
+                    long_name = None;
+
+                    if f(&entry) {
+                        return Ok(Some(entry));
+                    }
+                }
+            }
+            slot += 1;
+        }
+        Ok(None)
+    }
+
+    fn entry_from_raw(
+        &self,
+        raw: &[u8; DIR_ENTRY_SIZE],
+        short_name: [u8; 11],
+        units: &[u16],
+        long_name: Option<(u8, u8, usize)>,
+        slot: usize,
+    ) -> FatEntry {
+        let (name, first_slot) = match long_name {
+            // every part was found and they belong to this entry
+            Some((0, sum, first)) if sum == checksum(&short_name) => {
+                let mut name = Name::new();
+                let units = units.iter().copied().take_while(|&unit| unit != 0 && unit != 0xFFFF);
+                for c in char::decode_utf16(units) {
+                    name.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                (name, first)
+            }
+            _ => (short_name_to_name(&short_name, raw[12]), slot),
+        };
+
+        let cluster_high = match self.kind {
+            FatKind::Fat32 => (get_u16(raw, 20) as u32) << 16,
+            _ => 0,
+        };
+
+        FatEntry {
+            name,
+            short_name,
+            attributes: raw[11],
+            cluster: cluster_high | get_u16(raw, 26) as u32,
+            size: get_u32(raw, 28),
+            modified: decode_time(get_u16(raw, 24), get_u16(raw, 22)),
+            slot,
+            count_slots: slot - first_slot + 1,
+        }
+    }
+
+    fn find(&mut self, dir: Dir, name: &str) -> Result<FatEntry, FatError> {
+        self.scan(dir, &mut |entry| entry.name.as_str().eq_ignore_ascii_case(name))?
+            .ok_or(FatError::NotFound)
+    }
+
+    /// Directory `path` leads to, relative to the root directory.
+    fn resolve_dir(&mut self, path: &str) -> Result<Dir, FatError> {
+        let mut dir = self.root();
+
+        for component in path.split('/').filter(|component| !component.is_empty() && *component != ".") {
+            let entry = match (component, dir) {
+                ("..", Dir::Root) => continue,
+                ("..", Dir::Cluster(cluster)) if cluster == self.root_cluster => continue,
+                _ => self.find(dir, component)?,
+            };
+            if !entry.is_directory() {
+                return Err(FatError::NotDirectory);
+            }
+            dir = self.dir_at(entry.cluster);
+        }
+        Ok(dir)
+    }
+
+    /// Splits `path` into the directory its last component lives in and
+    /// that name.
+    fn resolve_parent<'p>(&mut self, path: &'p str) -> Result<(Dir, &'p str), FatError> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(FatError::InvalidName);
+        }
+        Ok((self.resolve_dir(parent)?, name))
+    }
+
+    /// Calls `f` for every entry of the directory at `path`, except `.`,
+    /// `..` and the volume label.
+    pub fn list(&mut self, path: &str, f: &mut dyn FnMut(&FatEntry)) -> Result<(), FatError> {
+        let dir = self.resolve_dir(path)?;
+        self.scan(dir, &mut |entry| {
+            if !entry.is_dot() {
+                f(entry);
+            }
+            false
+        })?;
+        Ok(())
+    }
+
+    /// Calls `f` with the content of the file at `path`, a sector at a time.
+    pub fn read_file(&mut self, path: &str, f: &mut dyn FnMut(&[u8])) -> Result<(), FatError> {
+        let (dir, name) = self.resolve_parent(path)?;
+        let entry = self.find(dir, name)?;
+        if entry.is_directory() {
+            return Err(FatError::IsDirectory);
+        }
+
+        let mut left = entry.size as usize;
+        let mut cluster = Some(entry.cluster).filter(|_| left > 0);
+        let mut buffer = [0; SECTOR_SIZE];
+
+        while let Some(current) = cluster {
+            if !self.is_valid_cluster(current) {
+                return Err(FatError::Corrupted);
+            }
+            for sector in 0..self.sectors_per_cluster as u64 {
+                if left == 0 {
+                    return Ok(());
+                }
+                self.read_sector(self.cluster_sector(current) + sector, &mut buffer)?;
+                let len = left.min(SECTOR_SIZE);
+                f(&buffer[..len]);
+                left -= len;
+            }
+            cluster = self.next_cluster(current)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` to clusters of their own and returns the first one, 0
+    /// for no data.
+    fn write_chain(&mut self, data: &[u8]) -> Result<u32, FatError> {
+        let mut first = 0;
+        let mut previous = None;
+
+        for chunk in data.chunks(self.cluster_size()) {
+            let cluster = match self.allocate_cluster(false) {
+                Ok(cluster) => cluster,
+                Err(error) => {
+                    self.free_chain(first)?;
+                    return Err(error);
+                }
+            };
+            match previous {
+                Some(previous) => self.set_fat_entry(previous, cluster)?,
+                None => first = cluster,
+            }
+            previous = Some(cluster);
+
+            for (i, part) in chunk.chunks(SECTOR_SIZE).enumerate() {
+                let mut buffer = [0; SECTOR_SIZE];
+                buffer[..part.len()].copy_from_slice(part);
+                self.write_sector(self.cluster_sector(cluster) + i as u64, &buffer)?;
+            }
+        }
+        Ok(first)
+    }
+
+    /// Fills the fields of a short entry.
+    fn short_entry(
+        &self,
+        short_name: &[u8; 11],
+        attributes: u8,
+        cluster: u32,
+        size: u32,
+        now: DateTime,
+    ) -> [u8; DIR_ENTRY_SIZE] {
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        let (date, time) = encode_time(now);
+
+        raw[..11].copy_from_slice(short_name);
+        raw[11] = attributes;
+        put_u16(&mut raw, 14, time);
+        put_u16(&mut raw, 16, date);
+        put_u16(&mut raw, 18, date);
+        if self.kind == FatKind::Fat32 {
+            put_u16(&mut raw, 20, (cluster >> 16) as u16);
+        }
+        put_u16(&mut raw, 22, time);
+        put_u16(&mut raw, 24, date);
+        put_u16(&mut raw, 26, cluster as u16);
+        put_u32(&mut raw, 28, size);
+        raw
+    }
+
+    /// First of `count` free entries in a row in `dir`, which grows if it
+    /// has none.
+    fn free_slots(&mut self, dir: Dir, count: usize) -> Result<usize, FatError> {
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        let mut run = 0;
+        let mut slot = 0;
+
+        loop {
+            if !self.read_slot(dir, slot, &mut raw)? {
+                let last = match dir {
+                    Dir::Root => return Err(FatError::NoSpace),
+                    Dir::Cluster(first) => {
+                        let mut last = first;
+                        while let Some(next) = self.next_cluster(last)? {
+                            last = next;
+                        }
+                        last
+                    }
+                };
+                let cluster = self.allocate_cluster(true)?;
+                self.set_fat_entry(last, cluster)?;
+                continue;
+            }
+
+            run = match raw[0] {
+                ENTRY_END | ENTRY_FREE => run + 1,
+                _ => 0,
+            };
+            if run == count {
+                return Ok(slot + 1 - count);
+            }
+            slot += 1;
+        }
+    }
+
+    /// Adds an entry called `name` to `dir`, with a long name if the name
+    /// is no valid short one.
+    fn add_entry(
+        &mut self,
+        dir: Dir,
+        name: &str,
+        attributes: u8,
+        cluster: u32,
+        size: u32,
+        now: DateTime,
+    ) -> Result<(), FatError> {
+        let mut units = [0u16; MAX_NAME_LEN];
+        let len = long_name_units(name, &mut units)?;
+
+        if self.scan(dir, &mut |entry| entry.name.as_str().eq_ignore_ascii_case(name))?.is_some() {
+            return Err(FatError::AlreadyExists);
+        }
+
+        let (short_name, count_lfn) = match exact_short_name(name) {
+            Some(short_name) => (short_name, 0),
+            None => {
+                let mut number = 1;
+                let short_name = loop {
+                    let candidate = numbered_short_name(name, number);
+                    if self.scan(dir, &mut |entry| entry.short_name == candidate)?.is_none() {
+                        break candidate;
+                    }
+                    number += 1;
+                };
+                (short_name, (len + CHARS_PER_LFN - 1) / CHARS_PER_LFN)
+            }
+        };
+
+        let first = self.free_slots(dir, count_lfn + 1)?;
+        let sum = checksum(&short_name);
+
+        for i in 0..count_lfn {
+            // the part holding the end of the name comes first
+            let sequence = (count_lfn - i) as u8;
+            let mut raw = [0; DIR_ENTRY_SIZE];
+            raw[0] = if i == 0 { sequence | LFN_LAST } else { sequence };
+            raw[11] = ATTR_LONG_NAME;
+            raw[13] = sum;
+
+            for (j, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                let index = (sequence as usize - 1) * CHARS_PER_LFN + j;
+                let unit = match index.cmp(&len) {
+                    core::cmp::Ordering::Less => units[index],
+                    core::cmp::Ordering::Equal => 0,
+                    core::cmp::Ordering::Greater => 0xFFFF,
+                };
+                put_u16(&mut raw, offset, unit);
+            }
+            self.write_slot(dir, first + i, &raw)?;
+        }
+
+        let raw = self.short_entry(&short_name, attributes, cluster, size, now);
+        self.write_slot(dir, first + count_lfn, &raw)
+    }
+
+    /// Creates the file at `path` or replaces its content with `data`.
+    pub fn write_file(&mut self, path: &str, data: &[u8], now: DateTime) -> Result<(), FatError> {
+        let (dir, name) = self.resolve_parent(path)?;
+        let existing = match self.find(dir, name) {
+            Ok(entry) if entry.is_directory() => return Err(FatError::IsDirectory),
+            Ok(entry) => Some(entry),
+            Err(FatError::NotFound) => None,
+            Err(error) => return Err(error),
+        };
+
+        if let Some(entry) = existing {
+            self.free_chain(entry.cluster)?;
+        }
+        let cluster = self.write_chain(data)?;
+
+        match existing {
+            Some(entry) => {
+                let attributes = entry.attributes | ATTR_ARCHIVE;
+                let mut raw = self.short_entry(&entry.short_name, attributes, cluster, data.len() as u32, now);
+
+                // keep the case of the short name and the time of creation
+                let mut old = [0; DIR_ENTRY_SIZE];
+                self.read_slot(dir, entry.slot, &mut old)?;
+                raw[12..18].copy_from_slice(&old[12..18]);
+                self.write_slot(dir, entry.slot, &raw)
+            }
+            None => self.add_entry(dir, name, ATTR_ARCHIVE, cluster, data.len() as u32, now),
+        }
+    }
+
+    pub fn create_dir(&mut self, path: &str, now: DateTime) -> Result<(), FatError> {
+        let (dir, name) = self.resolve_parent(path)?;
+        if self.find(dir, name).is_ok() {
+            return Err(FatError::AlreadyExists);
+        }
+
+        let cluster = self.allocate_cluster(true)?;
+        let parent_cluster = match dir {
+            Dir::Cluster(cluster) if cluster != self.root_cluster => cluster,
+            _ => 0,
+        };
+
+        let dot = self.short_entry(b".          ", ATTR_DIRECTORY, cluster, 0, now);
+        let dot_dot = self.short_entry(b"..         ", ATTR_DIRECTORY, parent_cluster, 0, now);
+        self.write_slot(Dir::Cluster(cluster), 0, &dot)?;
+        self.write_slot(Dir::Cluster(cluster), 1, &dot_dot)?;
+
+        if let Err(error) = self.add_entry(dir, name, ATTR_DIRECTORY, cluster, 0, now) {
+            self.free_chain(cluster)?;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Deletes a file or an empty directory.
+    pub fn remove(&mut self, path: &str) -> Result<(), FatError> {
+        let (dir, name) = self.resolve_parent(path)?;
+        let entry = self.find(dir, name)?;
+
+        if entry.is_directory() {
+            let inner = self.dir_at(entry.cluster);
+            if self.scan(inner, &mut |entry| !entry.is_dot())?.is_some() {
+                return Err(FatError::DirectoryNotEmpty);
+            }
+        }
+
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        for slot in entry.slot + 1 - entry.count_slots..=entry.slot {
+            self.read_slot(dir, slot, &mut raw)?;
+            raw[0] = ENTRY_FREE;
+            self.write_slot(dir, slot, &raw)?;
+        }
+        self.free_chain(entry.cluster)
+    }
+
+    /// Number of free clusters, counted in the FAT.
+    pub fn free_clusters(&mut self) -> Result<u32, FatError> {
+        let mut count = 0;
+        for cluster in 2..self.cluster_count + 2 {
+            if self.fat_entry(cluster)? == 0 {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
@@ -9,6 +9,13 @@ const PIC_1_OFFSET: u8 = 32;
 const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 const TIMER_INTERRUPT: u8 = PIC_1_OFFSET;
 const KEYBOARD_INTERRUPT: u8 = PIC_1_OFFSET + 1;
+/// Line the second PIC is chained to on the first.
+const CASCADE_IRQ: u8 = 2;
+const PIC_1_DATA: u16 = 0x21;
+const PIC_2_DATA: u16 = 0xA1;
+/// The lines the BIOS of QEMU hands out to PCI devices, each has a handler
+/// in the IDT.
+const PCI_IRQS: [u8; 4] = [5, 9, 10, 11];
 
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
@@ -16,6 +23,10 @@ lazy_static! {
         idt.double_fault.set_handler_fn(double_fault_handler);
         idt[TIMER_INTERRUPT as usize].set_handler_fn(timer_interrupt_handler);
         idt[KEYBOARD_INTERRUPT as usize].set_handler_fn(keyboard_interrupt_handler);
+        idt[(PIC_1_OFFSET + 5) as usize].set_handler_fn(pci_interrupt_handler::<5>);
+        idt[(PIC_1_OFFSET + 9) as usize].set_handler_fn(pci_interrupt_handler::<9>);
+        idt[(PIC_1_OFFSET + 10) as usize].set_handler_fn(pci_interrupt_handler::<10>);
+        idt[(PIC_1_OFFSET + 11) as usize].set_handler_fn(pci_interrupt_handler::<11>);
         idt
     };
 }
@@ -35,7 +46,8 @@ lazy_static! {
         {
             let mut ch = CustomHandlers{
                 timer_interrupt_handler: || {},
-                keyboard_interrupt_handler: |dk| {}
+                keyboard_interrupt_handler: |dk| {},
+                pci_interrupt_handler: |_irq| {}
             };
             ch
         }
@@ -60,10 +72,39 @@ pub fn set_timer_interrupt_handler(handler: fn()) {
     CUSTOM_HANDLERS.lock().timer_interrupt_handler  = handler;
 }
 
+/// The handler gets the line of the PIC the interrupt came in on.
+pub fn set_pci_interrupt_handler(handler: fn(u8)) {
+    CUSTOM_HANDLERS.lock().pci_interrupt_handler = handler;
+}
+
+/// Lets the PICs pass on interrupts from the line `irq`. The lines of the
+/// second PIC also need the line it is chained to. Lines without a handler
+/// stay masked and return `false`.
+pub fn enable_pci_interrupt(irq: u8) -> bool {
+    if !PCI_IRQS.contains(&irq) {
+        return false;
+    }
+    let (mut primary, mut secondary): (Port<u8>, Port<u8>) = (Port::new(PIC_1_DATA), Port::new(PIC_2_DATA));
+
+    unsafe {
+        if irq < 8 {
+            let mask = primary.read();
+            primary.write(mask & !(1 << irq));
+        } else {
+            let mask = primary.read();
+            primary.write(mask & !(1 << CASCADE_IRQ));
+            let mask = secondary.read();
+            secondary.write(mask & !(1 << (irq - 8)));
+        }
+    }
+    true
+}
+
 
 struct CustomHandlers {
     timer_interrupt_handler: fn(),
     keyboard_interrupt_handler: fn(DecodedKey),
+    pci_interrupt_handler: fn(u8),
 }
 
 extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame, _error_code: u64) -> ! {
@@ -100,3 +141,10 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
         PICS.lock().notify_end_of_interrupt(KEYBOARD_INTERRUPT);
     }
 }
+
+extern "x86-interrupt" fn pci_interrupt_handler<const IRQ: u8>(_stack_frame: InterruptStackFrame) {
+    (CUSTOM_HANDLERS.lock().pci_interrupt_handler)(IRQ);
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(PIC_1_OFFSET + IRQ);
+    }
+}
@@ -6,12 +6,25 @@
 use core::fmt::Write;
 use core::panic::PanicInfo;
 use core::ptr::write;
+use bootloader::{entry_point, BootInfo};
 use pc_keyboard::DecodedKey;
 use crate::vga_buf::SCREEN;
 
 mod vga_buf;
 mod interrupts;
+mod ata;
+mod block;
+mod cache;
+mod disks;
+mod fat;
+mod memory;
+mod pci;
+mod rtc;
+mod serial;
 mod shell;
+mod tar;
+mod virtio;
+mod xmodem;
 
 /// This function is called on panic.
 #[panic_handler]
@@ -23,18 +36,44 @@ fn panic(_info: &PanicInfo) -> ! {
 }
 
 fn my_keyboard_handler(key: DecodedKey) {
-    shell::handle_keyboard_interrupt(key);
+    shell::queue_key(key);
 }
 
 fn my_timer_handler() {
+    disks::on_timer_tick();
 }
 
-#[no_mangle]
-pub extern "C" fn _start() -> ! {
-    shell::init_shell();
+fn my_pci_interrupt_handler(irq: u8) {
+    virtio::on_interrupt(irq);
+}
+
+entry_point!(kernel_main);
+
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    memory::init(boot_info);
+    serial::init();
+    disks::init();
+    pci::init();
     interrupts::set_keyboard_interrupt_handler(my_keyboard_handler);
     interrupts::set_timer_interrupt_handler(my_timer_handler);
+    interrupts::set_pci_interrupt_handler(my_pci_interrupt_handler);
     interrupts::init();
+    shell::init_shell();
 
-    loop {}
+    // keys are handled here rather than in their interrupt, so that
+    // commands run with interrupts on
+    loop {
+        disks::write_back();
+
+        // an interrupt arriving between the check and `hlt` would only be
+        // noticed on the next one, so they are only allowed again together
+        x86_64::instructions::interrupts::disable();
+        match shell::take_key() {
+            Some(key) => {
+                x86_64::instructions::interrupts::enable();
+                shell::handle_key(key);
+            }
+            None => x86_64::instructions::interrupts::enable_and_hlt(),
+        }
+    }
 }
@@ -0,0 +1,74 @@
+//! Physical memory as the bootloader hands it over. All of it is mapped at
+//! `physical_memory_offset`, so the kernel reaches any frame without
+//! changing the page tables.
+//!
+//! Devices that read and write memory on their own, such as virtio disks,
+//! need physical addresses of contiguous memory. Their frames are taken
+//! from the usable regions of the memory map and never given back.
+
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use bootloader::BootInfo;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::{OffsetPageTable, PageTable, Translate};
+use x86_64::{PhysAddr, VirtAddr};
+
+pub const PAGE_SIZE: u64 = 4096;
+/// Memory below 1 MiB holds what the BIOS left behind and is never handed
+/// out, which also keeps frame 0 from being mistaken for no frame.
+const FIRST_FRAME: u64 = 0x10_0000;
+
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+static FRAMES: Mutex<Option<Frames>> = Mutex::new(None);
+
+struct Frames {
+    memory_map: &'static MemoryMap,
+    /// Frames below this address have been handed out or skipped.
+    next: u64,
+}
+
+pub fn init(boot_info: &'static BootInfo) {
+    PHYSICAL_MEMORY_OFFSET.store(boot_info.physical_memory_offset, Ordering::Relaxed);
+    *FRAMES.lock() = Some(Frames {
+        memory_map: &boot_info.memory_map,
+        next: FIRST_FRAME,
+    });
+}
+
+/// Where the kernel sees the physical address `address`.
+pub fn phys_to_virt(address: PhysAddr) -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) + address.as_u64())
+}
+
+/// Whether the page holding `address` is mapped in the page table in use,
+/// so that reading it does not fault.
+pub fn is_mapped(address: VirtAddr) -> bool {
+    let offset = VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed));
+    let (level_4_frame, _) = Cr3::read();
+    let level_4_table = phys_to_virt(level_4_frame.start_address()).as_mut_ptr::<PageTable>();
+    let page_table = unsafe { OffsetPageTable::new(&mut *level_4_table, offset) };
+    page_table.translate_addr(address).is_some()
+}
+
+/// Takes `count` frames following each other in physical memory, filled
+/// with zeros. Returns `None` when no usable region has room for them.
+pub fn allocate_frames(count: u64) -> Option<PhysAddr> {
+    let mut frames = FRAMES.lock();
+    let frames = frames.as_mut()?;
+
+    let usable = frames.memory_map.iter().filter(|region| region.region_type == MemoryRegionType::Usable);
+    for region in usable {
+        let start = region.range.start_addr().max(frames.next);
+        let end = start + count * PAGE_SIZE;
+        if end > region.range.end_addr() {
+            continue;
+        }
+
+        frames.next = end;
+        let address = PhysAddr::new(start);
+        unsafe { core::ptr::write_bytes(phys_to_virt(address).as_mut_ptr::<u8>(), 0, (count * PAGE_SIZE) as usize) }
+        return Some(address);
+    }
+    None
+}
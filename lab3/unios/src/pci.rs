@@ -0,0 +1,510 @@
+//! PCI configuration space, reached through the ports `0xCF8` and `0xCFC`
+//! (configuration mechanism 1).
+//!
+//! `init` walks every bus once at boot, reads the header of each function
+//! it finds and sizes its base address registers. Drivers registered with
+//! `register_driver` before that are handed the devices whose vendor and
+//! device ids they list; the first driver that takes a device keeps it.
+
+use core::fmt;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+use crate::println;
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+const CONFIG_ENABLE: u32 = 1 << 31;
+
+// offsets in the configuration space of every function
+pub const REGISTER_VENDOR_ID: u8 = 0x00;
+pub const REGISTER_DEVICE_ID: u8 = 0x02;
+pub const REGISTER_COMMAND: u8 = 0x04;
+pub const REGISTER_STATUS: u8 = 0x06;
+pub const REGISTER_REVISION: u8 = 0x08;
+pub const REGISTER_PROG_IF: u8 = 0x09;
+pub const REGISTER_SUBCLASS: u8 = 0x0A;
+pub const REGISTER_CLASS: u8 = 0x0B;
+pub const REGISTER_HEADER_TYPE: u8 = 0x0E;
+pub const REGISTER_BAR0: u8 = 0x10;
+pub const REGISTER_CAPABILITIES: u8 = 0x34;
+pub const REGISTER_INTERRUPT_LINE: u8 = 0x3C;
+pub const REGISTER_INTERRUPT_PIN: u8 = 0x3D;
+
+pub const COMMAND_IO_SPACE: u16 = 1 << 0;
+pub const COMMAND_MEMORY_SPACE: u16 = 1 << 1;
+pub const COMMAND_BUS_MASTER: u16 = 1 << 2;
+pub const COMMAND_INTERRUPT_DISABLE: u16 = 1 << 10;
+const STATUS_CAPABILITIES: u16 = 1 << 4;
+
+/// What reading a function that does not exist returns.
+const VENDOR_NONE: u16 = 0xFFFF;
+const HEADER_MULTI_FUNCTION: u8 = 0x80;
+const HEADER_KIND: u8 = 0x7F;
+const HEADER_GENERAL: u8 = 0x00;
+const HEADER_PCI_BRIDGE: u8 = 0x01;
+
+const BAR_IO: u32 = 1;
+const BAR_MEMORY_64: u32 = 2 << 1;
+const BAR_MEMORY_KIND: u32 = 3 << 1;
+const BAR_PREFETCHABLE: u32 = 1 << 3;
+const MAX_COUNT_BARS: usize = 6;
+
+const COUNT_BUSES: u16 = 256;
+const COUNT_DEVICES: u8 = 32;
+const COUNT_FUNCTIONS: u8 = 8;
+/// Capabilities followed at most, a longer list can only be a loop.
+const MAX_COUNT_CAPABILITIES: usize = 48;
+
+pub const MAX_COUNT_PCI_DEVICES: usize = 32;
+const MAX_COUNT_DRIVERS: usize = 8;
+
+/// Devices found by `init`, each with the name of the driver that took it.
+pub static DEVICES: Mutex<DeviceTable> = Mutex::new(DeviceTable {
+    devices: [None; MAX_COUNT_PCI_DEVICES],
+    count: 0,
+});
+static DRIVERS: Mutex<[Option<&'static dyn Driver>; MAX_COUNT_DRIVERS]> = Mutex::new([None; MAX_COUNT_DRIVERS]);
+
+/// A function of a device on the PCI bus, shown as `bus:device.function`
+/// in hex like `lspci` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciAddress {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl PciAddress {
+    fn select(self, offset: u8) -> Port<u32> {
+        let address = CONFIG_ENABLE
+            | (self.bus as u32) << 16
+            | (self.device as u32) << 11
+            | (self.function as u32) << 8
+            | (offset & 0xFC) as u32;
+
+        let mut port: Port<u32> = Port::new(CONFIG_ADDRESS);
+        unsafe { port.write(address) }
+        Port::new(CONFIG_DATA)
+    }
+
+    /// Reads the 32-bit register holding `offset`.
+    pub fn read(self, offset: u8) -> u32 {
+        unsafe { self.select(offset).read() }
+    }
+
+    pub fn read_u16(self, offset: u8) -> u16 {
+        (self.read(offset) >> ((offset & 2) * 8)) as u16
+    }
+
+    pub fn read_u8(self, offset: u8) -> u8 {
+        (self.read(offset) >> ((offset & 3) * 8)) as u8
+    }
+
+    pub fn write(self, offset: u8, value: u32) {
+        unsafe { self.select(offset).write(value) }
+    }
+
+    /// Writes the 16 bits at `offset`, keeping the other half of the
+    /// register.
+    pub fn write_u16(self, offset: u8, value: u16) {
+        let shift = (offset & 2) * 8;
+        let register = self.read(offset) & !(0xFFFF << shift) | (value as u32) << shift;
+        self.write(offset, register);
+    }
+
+    pub fn vendor_id(self) -> u16 {
+        self.read_u16(REGISTER_VENDOR_ID)
+    }
+
+    pub fn device_id(self) -> u16 {
+        self.read_u16(REGISTER_DEVICE_ID)
+    }
+
+    pub fn exists(self) -> bool {
+        self.vendor_id() != VENDOR_NONE
+    }
+
+    /// Lets the device answer in I/O space and read and write memory on its
+    /// own, with its interrupt line enabled.
+    pub fn enable_io_and_bus_master(self) {
+        let command = self.read_u16(REGISTER_COMMAND);
+        let command = command & !COMMAND_INTERRUPT_DISABLE | COMMAND_IO_SPACE | COMMAND_BUS_MASTER;
+        self.write_u16(REGISTER_COMMAND, command);
+    }
+
+    /// The list of capabilities the device has, empty if it has none.
+    pub fn capabilities(self) -> Capabilities {
+        let next = match self.read_u16(REGISTER_STATUS) & STATUS_CAPABILITIES {
+            0 => 0,
+            _ => self.read_u8(REGISTER_CAPABILITIES) & 0xFC,
+        };
+        Capabilities {
+            address: self,
+            next,
+            count: 0,
+        }
+    }
+
+    /// Reads the base address registers, learning their sizes by writing
+    /// all ones and reading back which bits stick. The device stops
+    /// decoding addresses meanwhile, so this is only done at boot.
+    fn read_bars(self, count: usize) -> [Bar; MAX_COUNT_BARS] {
+        let mut bars = [Bar::None; MAX_COUNT_BARS];
+        let command = self.read_u16(REGISTER_COMMAND);
+        self.write_u16(REGISTER_COMMAND, command & !(COMMAND_IO_SPACE | COMMAND_MEMORY_SPACE));
+
+        let mut index = 0;
+        while index < count {
+            let offset = REGISTER_BAR0 + 4 * index as u8;
+            let low = self.read(offset);
+            let low_mask = self.size_mask(offset, low);
+
+            if low & BAR_IO != 0 {
+                let mask = low_mask & 0xFFFC;
+                if mask != 0 {
+                    bars[index] = Bar::Io {
+                        port: (low & 0xFFFC) as u16,
+                        size: (!mask & 0xFFFF) + 1,
+                    };
+                }
+                index += 1;
+                continue;
+            }
+
+            // the upper half of a 64-bit address takes the next register
+            let is_64bit = low & BAR_MEMORY_KIND == BAR_MEMORY_64 && index + 1 < count;
+            let (high, high_mask) = match is_64bit {
+                true => {
+                    let high = self.read(offset + 4);
+                    (high, self.size_mask(offset + 4, high))
+                }
+                false => (0, 0xFFFF_FFFF),
+            };
+            let mask = (high_mask as u64) << 32 | (low_mask & !0xF) as u64;
+            // registers the device does not implement read back as 0
+            if low_mask != 0 {
+                bars[index] = Bar::Memory {
+                    address: (high as u64) << 32 | (low & !0xF) as u64,
+                    size: !mask + 1,
+                    is_64bit,
+                    prefetchable: low & BAR_PREFETCHABLE != 0,
+                };
+            }
+            index += if is_64bit { 2 } else { 1 };
+        }
+
+        self.write_u16(REGISTER_COMMAND, command);
+        bars
+    }
+
+    /// Writes all ones to the register at `offset`, reads back the bits the
+    /// device keeps and puts back `value`.
+    fn size_mask(self, offset: u8, value: u32) -> u32 {
+        self.write(offset, 0xFFFF_FFFF);
+        let mask = self.read(offset);
+        self.write(offset, value);
+        mask
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}.{:x}", self.bus, self.device, self.function)
+    }
+}
+
+/// An entry in the capability list of a device, `offset` being where it
+/// starts in the configuration space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    pub id: u8,
+    pub offset: u8,
+}
+
+impl Capability {
+    pub fn name(&self) -> &'static str {
+        match self.id {
+            0x01 => "Power Management",
+            0x05 => "MSI",
+            0x09 => "Vendor Specific",
+            0x0D => "Bridge subsystem",
+            0x10 => "Express",
+            0x11 => "MSI-X",
+            0x12 => "SATA",
+            0x13 => "Advanced Features",
+            _ => "Unknown",
+        }
+    }
+}
+
+pub struct Capabilities {
+    address: PciAddress,
+    next: u8,
+    count: usize,
+}
+
+impl Iterator for Capabilities {
+    type Item = Capability;
+
+    fn next(&mut self) -> Option<Capability> {
+        // the list lives behind the 64 bytes of the header
+        if self.next < 0x40 || self.count == MAX_COUNT_CAPABILITIES {
+            return None;
+        }
+
+        let capability = Capability {
+            id: self.address.read_u8(self.next),
+            offset: self.next,
+        };
+        self.next = self.address.read_u8(self.next + 1) & 0xFC;
+        self.count += 1;
+        Some(capability)
+    }
+}
+
+/// A base address register, telling where the device answers in I/O or
+/// memory space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bar {
+    /// Not implemented, or the upper half of a 64-bit address.
+    None,
+    Io {
+        port: u16,
+        size: u32,
+    },
+    Memory {
+        address: u64,
+        size: u64,
+        is_64bit: bool,
+        prefetchable: bool,
+    },
+}
+
+/// Size in bytes, shown with the largest unit that divides it like `lspci`
+/// does.
+pub struct Size(pub u64);
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            size if size >= 1 << 30 && size % (1 << 30) == 0 => write!(f, "{}G", size >> 30),
+            size if size >= 1 << 20 && size % (1 << 20) == 0 => write!(f, "{}M", size >> 20),
+            size if size >= 1 << 10 && size % (1 << 10) == 0 => write!(f, "{}K", size >> 10),
+            size => write!(f, "{}", size),
+        }
+    }
+}
+
+impl fmt::Display for Bar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Bar::None => f.write_str("unused"),
+            Bar::Io { port, size } => write!(f, "I/O ports at {:04x} [size={}]", port, Size(size as u64)),
+            Bar::Memory {
+                address,
+                size,
+                is_64bit,
+                prefetchable,
+            } => write!(
+                f,
+                "Memory at {:08x} ({}-bit, {}) [size={}]",
+                address,
+                if is_64bit { 64 } else { 32 },
+                if prefetchable { "prefetchable" } else { "non-prefetchable" },
+                Size(size)
+            ),
+        }
+    }
+}
+
+/// What the header of a function told at boot.
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub address: PciAddress,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    /// Line of the PICs the BIOS routed the interrupt to.
+    pub interrupt_line: u8,
+    /// 1 to 4 for INTA# to INTD#, 0 if the function has no interrupt.
+    pub interrupt_pin: u8,
+    pub bars: [Bar; MAX_COUNT_BARS],
+}
+
+impl PciDevice {
+    fn read(address: PciAddress) -> PciDevice {
+        let header_type = address.read_u8(REGISTER_HEADER_TYPE) & HEADER_KIND;
+        let count_bars = match header_type {
+            HEADER_GENERAL => 6,
+            HEADER_PCI_BRIDGE => 2,
+            _ => 0,
+        };
+
+        PciDevice {
+            address,
+            vendor_id: address.vendor_id(),
+            device_id: address.device_id(),
+            class: address.read_u8(REGISTER_CLASS),
+            subclass: address.read_u8(REGISTER_SUBCLASS),
+            prog_if: address.read_u8(REGISTER_PROG_IF),
+            revision: address.read_u8(REGISTER_REVISION),
+            interrupt_line: address.read_u8(REGISTER_INTERRUPT_LINE),
+            interrupt_pin: address.read_u8(REGISTER_INTERRUPT_PIN),
+            bars: address.read_bars(count_bars),
+        }
+    }
+
+    /// What the class and subclass stand for, as far as QEMU has them.
+    pub fn class_name(&self) -> &'static str {
+        match (self.class, self.subclass) {
+            (0x00, _) => "Non-VGA unclassified device",
+            (0x01, 0x00) => "SCSI storage controller",
+            (0x01, 0x01) => "IDE interface",
+            (0x01, 0x06) => "SATA controller",
+            (0x01, 0x08) => "Non-Volatile memory controller",
+            (0x01, _) => "Mass storage controller",
+            (0x02, 0x00) => "Ethernet controller",
+            (0x02, _) => "Network controller",
+            (0x03, 0x00) => "VGA compatible controller",
+            (0x03, _) => "Display controller",
+            (0x04, 0x01) => "Multimedia audio controller",
+            (0x04, 0x03) => "Audio device",
+            (0x04, _) => "Multimedia controller",
+            (0x05, _) => "Memory controller",
+            (0x06, 0x00) => "Host bridge",
+            (0x06, 0x01) => "ISA bridge",
+            (0x06, 0x04) => "PCI bridge",
+            (0x06, _) => "Bridge",
+            (0x07, _) => "Communication controller",
+            (0x08, _) => "System peripheral",
+            (0x09, _) => "Input device controller",
+            (0x0C, 0x03) => "USB controller",
+            (0x0C, 0x05) => "SMBus",
+            (0x0C, _) => "Serial bus controller",
+            (0xFF, _) => "Unassigned class",
+            _ => "Unknown class",
+        }
+    }
+
+    pub fn vendor_name(&self) -> Option<&'static str> {
+        match self.vendor_id {
+            0x1AF4 | 0x1B36 => Some("Red Hat, Inc."),
+            0x1234 => Some("QEMU"),
+            0x8086 => Some("Intel Corporation"),
+            0x10EC => Some("Realtek Semiconductor Co., Ltd."),
+            0x1022 => Some("Advanced Micro Devices, Inc."),
+            0x15AD => Some("VMware"),
+            _ => None,
+        }
+    }
+}
+
+/// Why a driver did not take a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeError {
+    /// The device lacks what the driver needs, e.g. a kind of BAR.
+    Unsupported,
+    /// There is no memory left for the device.
+    NoMemory,
+    /// The driver already drives as many devices as it can.
+    TooManyDevices,
+    /// The device did not come up.
+    DeviceError,
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ProbeError::Unsupported => "Device is not supported",
+            ProbeError::NoMemory => "Out of memory",
+            ProbeError::TooManyDevices => "Too many devices",
+            ProbeError::DeviceError => "Device reported an error",
+        };
+        f.write_str(message)
+    }
+}
+
+/// A driver for the PCI devices with the ids it lists.
+pub trait Driver: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Vendor and device ids of the devices the driver takes.
+    fn ids(&self) -> &'static [(u16, u16)];
+
+    /// Sets up a device with one of the ids.
+    fn probe(&self, device: &PciDevice) -> Result<(), ProbeError>;
+}
+
+pub struct DeviceTable {
+    devices: [Option<(PciDevice, Option<&'static str>)>; MAX_COUNT_PCI_DEVICES],
+    count: usize,
+}
+
+impl DeviceTable {
+    /// Every device with the name of the driver that took it.
+    pub fn iter(&self) -> impl Iterator<Item = &(PciDevice, Option<&'static str>)> {
+        self.devices[..self.count].iter().flatten()
+    }
+}
+
+/// Makes `driver` take the devices with its ids that `init` finds. Returns
+/// `false` if there are too many drivers.
+pub fn register_driver(driver: &'static dyn Driver) -> bool {
+    let mut drivers = DRIVERS.lock();
+
+    match drivers.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(driver);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Every function present on every bus, in the order of their addresses.
+/// Only devices with several functions are asked for more than function 0.
+pub fn functions() -> impl Iterator<Item = PciAddress> {
+    (0..COUNT_BUSES)
+        .flat_map(|bus| (0..COUNT_DEVICES).map(move |device| (bus as u8, device)))
+        .filter(|&(bus, device)| PciAddress { bus, device, function: 0 }.exists())
+        .flat_map(|(bus, device)| {
+            let header_type = PciAddress { bus, device, function: 0 }.read_u8(REGISTER_HEADER_TYPE);
+            let count = match header_type & HEADER_MULTI_FUNCTION {
+                0 => 1,
+                _ => COUNT_FUNCTIONS,
+            };
+            (0..count).map(move |function| PciAddress { bus, device, function })
+        })
+        .filter(|address| address.exists())
+}
+
+/// Finds every device and hands it to the first registered driver that
+/// lists its ids.
+pub fn init() {
+    let mut table = DEVICES.lock();
+    let drivers = DRIVERS.lock();
+
+    for address in functions().take(MAX_COUNT_PCI_DEVICES) {
+        let device = PciDevice::read(address);
+        let mut bound = None;
+
+        let id = (device.vendor_id, device.device_id);
+        for driver in drivers.iter().flatten().filter(|driver| driver.ids().contains(&id)) {
+            match driver.probe(&device) {
+                Ok(()) => {
+                    bound = Some(driver.name());
+                    break;
+                }
+                Err(error) => println!("[Error] {} {}: {}", driver.name(), address, error),
+            }
+        }
+
+        let index = table.count;
+        table.devices[index] = Some((device, bound));
+        table.count += 1;
+    }
+}
@@ -0,0 +1,99 @@
+use x86_64::instructions::port::Port;
+
+pub use unifs::DateTime;
+
+const CMOS_ADDRESS_PORT: u16 = 0x70;
+const CMOS_DATA_PORT: u16 = 0x71;
+
+const REGISTER_SECONDS: u8 = 0x00;
+const REGISTER_MINUTES: u8 = 0x02;
+const REGISTER_HOURS: u8 = 0x04;
+const REGISTER_DAY: u8 = 0x07;
+const REGISTER_MONTH: u8 = 0x08;
+const REGISTER_YEAR: u8 = 0x09;
+const REGISTER_STATUS_A: u8 = 0x0A;
+const REGISTER_STATUS_B: u8 = 0x0B;
+
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+const MODE_24_HOURS: u8 = 0x02;
+const MODE_BINARY: u8 = 0x04;
+const HOUR_PM: u8 = 0x80;
+
+fn read_register(register: u8) -> u8 {
+    let mut address: Port<u8> = Port::new(CMOS_ADDRESS_PORT);
+    let mut data: Port<u8> = Port::new(CMOS_DATA_PORT);
+
+    unsafe {
+        address.write(register);
+        data.read()
+    }
+}
+
+fn read_raw() -> [u8; 6] {
+    while read_register(REGISTER_STATUS_A) & UPDATE_IN_PROGRESS != 0 {}
+
+    [
+        read_register(REGISTER_SECONDS),
+        read_register(REGISTER_MINUTES),
+        read_register(REGISTER_HOURS),
+        read_register(REGISTER_DAY),
+        read_register(REGISTER_MONTH),
+        read_register(REGISTER_YEAR),
+    ]
+}
+
+/// The seconds register as the clock keeps it, which is enough to notice a
+/// second going by where the timer interrupt does not come through.
+pub fn second() -> u8 {
+    while read_register(REGISTER_STATUS_A) & UPDATE_IN_PROGRESS != 0 {}
+    read_register(REGISTER_SECONDS)
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Reads the current date and time from the CMOS real time clock.
+pub fn now() -> DateTime {
+    // the clock may tick between two registers, so read until two reads agree
+    let mut raw = read_raw();
+    loop {
+        let again = read_raw();
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+
+    let status = read_register(REGISTER_STATUS_B);
+    let [mut second, mut minute, mut hour, mut day, mut month, mut year] = raw;
+    let pm = hour & HOUR_PM != 0;
+    hour &= !HOUR_PM;
+
+    if status & MODE_BINARY == 0 {
+        second = from_bcd(second);
+        minute = from_bcd(minute);
+        hour = from_bcd(hour);
+        day = from_bcd(day);
+        month = from_bcd(month);
+        year = from_bcd(year);
+    }
+
+    if status & MODE_24_HOURS == 0 {
+        hour = match (hour, pm) {
+            (12, false) => 0,
+            (12, true) => 12,
+            (hour, true) => hour + 12,
+            (hour, false) => hour,
+        };
+    }
+
+    DateTime {
+        year: 2000 + year as u16,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    }
+}
@@ -0,0 +1,110 @@
+//! The first serial port, COM1, at 115200 baud with 8 data bits, no parity
+//! and one stop bit. The port is polled and its interrupt stays off, a
+//! transfer keeps the shell busy until it is over anyway.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::port::Port;
+
+use crate::rtc;
+
+const COM1: u16 = 0x3F8;
+
+// registers relative to the I/O base
+const REGISTER_DATA: u16 = 0;
+const REGISTER_INTERRUPT_ENABLE: u16 = 1;
+/// Low and high byte of the divisor while `LINE_DIVISOR_LATCH` is set.
+const REGISTER_DIVISOR_LOW: u16 = 0;
+const REGISTER_DIVISOR_HIGH: u16 = 1;
+const REGISTER_FIFO_CONTROL: u16 = 2;
+const REGISTER_LINE_CONTROL: u16 = 3;
+const REGISTER_MODEM_CONTROL: u16 = 4;
+const REGISTER_LINE_STATUS: u16 = 5;
+
+const LINE_8N1: u8 = 0x03;
+const LINE_DIVISOR_LATCH: u8 = 0x80;
+/// Enables and clears both FIFOs, with an interrupt level of 14 bytes.
+const FIFO_ENABLE: u8 = 0xC7;
+/// Data terminal ready and request to send, without OUT2 which would let
+/// the interrupt through.
+const MODEM_READY: u8 = 0x03;
+/// 115200 baud divided by this gives the speed.
+const DIVISOR: u16 = 1;
+
+const STATUS_DATA_READY: u8 = 0x01;
+const STATUS_TRANSMIT_EMPTY: u8 = 0x20;
+/// What the line status of a port that does not exist reads as.
+const STATUS_FLOATING: u8 = 0xFF;
+
+static PRESENT: AtomicBool = AtomicBool::new(false);
+
+fn read(register: u16) -> u8 {
+    let mut port: Port<u8> = Port::new(COM1 + register);
+    unsafe { port.read() }
+}
+
+fn write(register: u16, value: u8) {
+    let mut port: Port<u8> = Port::new(COM1 + register);
+    unsafe { port.write(value) }
+}
+
+/// Sets up COM1 if there is one.
+pub fn init() {
+    if read(REGISTER_LINE_STATUS) == STATUS_FLOATING {
+        return;
+    }
+
+    write(REGISTER_INTERRUPT_ENABLE, 0);
+    write(REGISTER_LINE_CONTROL, LINE_DIVISOR_LATCH);
+    write(REGISTER_DIVISOR_LOW, DIVISOR as u8);
+    write(REGISTER_DIVISOR_HIGH, (DIVISOR >> 8) as u8);
+    write(REGISTER_LINE_CONTROL, LINE_8N1);
+    write(REGISTER_FIFO_CONTROL, FIFO_ENABLE);
+    write(REGISTER_MODEM_CONTROL, MODEM_READY);
+    PRESENT.store(true, Ordering::Relaxed);
+}
+
+pub fn is_present() -> bool {
+    PRESENT.load(Ordering::Relaxed)
+}
+
+pub fn write_byte(byte: u8) {
+    while read(REGISTER_LINE_STATUS) & STATUS_TRANSMIT_EMPTY == 0 {
+        core::hint::spin_loop();
+    }
+    write(REGISTER_DATA, byte);
+}
+
+pub fn write_bytes(bytes: &[u8]) {
+    for &byte in bytes {
+        write_byte(byte);
+    }
+}
+
+/// Waits for the next byte, giving up when none came for `seconds` to
+/// `seconds + 1` seconds, the clock only telling whole seconds.
+pub fn read_byte(seconds: u32) -> Option<u8> {
+    let mut second = rtc::second();
+    let mut passed = 0;
+
+    loop {
+        if read(REGISTER_LINE_STATUS) & STATUS_DATA_READY != 0 {
+            return Some(read(REGISTER_DATA));
+        }
+
+        let now = rtc::second();
+        if now != second {
+            second = now;
+            passed += 1;
+            if passed > seconds {
+                return None;
+            }
+        }
+    }
+}
+
+/// Drops whatever has arrived and is still waiting to be read.
+pub fn drain() {
+    while read(REGISTER_LINE_STATUS) & STATUS_DATA_READY != 0 {
+        read(REGISTER_DATA);
+    }
+}
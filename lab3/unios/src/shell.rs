@@ -1,718 +1,536 @@
+use core::fmt::Write;
+
+use crate::rtc;
 use crate::vga_buf::SCREEN;
 use crate::{print, println};
 use lazy_static::lazy_static;
-use pc_keyboard::DecodedKey;
-
+use pc_keyboard::{DecodedKey, KeyCode};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+mod alias;
+mod builtins;
+mod fileops;
+pub mod command;
+mod disk;
+mod fatfs;
+mod fs;
+mod fsck;
+mod glob;
+mod hexdump;
+mod initrd;
+mod ls;
+mod mount;
+mod pager;
+mod parser;
+mod pci;
+mod script;
+mod search;
+mod text;
+mod transfer;
+mod tree;
+mod vars;
+
+pub use command::{Argv, Command, Io, RegisterError, ShellError};
+use command::{Buffer, CommandRegistry, Console, EXIT_SUCCESS};
+use alias::{Aliases, Expanding};
+use fileops::Removal;
+use pager::{Pager, PagerKey};
+use fs::{FileSystem, DELETED_INDEX_FILE, ROOT_DIRECTORY};
+use parser::{CommandList, ParseError, Pipeline, Separator};
+use script::Script;
+use vars::Variables;
+
+/// Default value of `$PS1`.
 const FORMATING_STRING: &str = " $ ";
-const FORMATING_STRING_LENGTH: u32 = 3;
-const MAX_COUNT_CHILDREN_DIRECTORIES: usize = 20;
-const MAX_COUNT_DIRECTORIES: usize = 100;
-const MAX_COUNT_FILES_IN_FOLDER: usize = 5;
-const MAX_COUNT_FILES: usize = 10;
-const DELETED_INDEX_DIRECTORY: usize = MAX_COUNT_DIRECTORIES + 1;
-const DELETED_INDEX_FILE: usize = MAX_COUNT_FILES + 1;
-const MAX_SIZE_DIRECTORY_NAME: usize = 10;
-const COMMAND_SIZE: usize = 10;
-const ARGV_SIZE: usize = 70;
-
-const BUF_HEIGHT: u32 = 25;
-const BUF_WIDTH: u32 = 80;
-const BUF_SIZE: usize = (BUF_HEIGHT * BUF_WIDTH) as usize;
+const COMMAND_LINE_SIZE: usize = 80;
+const MAX_SIZE_EXPANDED_LINE: usize = 160;
+/// Commands after an `rm` asking for confirmation: the rest of an alias and
+/// of the line it was typed in.
+const MAX_SIZE_DEFERRED_LINE: usize = MAX_SIZE_EXPANDED_LINE + COMMAND_LINE_SIZE;
+const MAX_SIZE_PATH: usize = 256;
+/// How much output one stage of a pipeline can hand to the next.
+const PIPE_SIZE: usize = 2048;
+/// Script run when the shell starts. A disk mounted at boot may bring its
+/// own, otherwise the one built into the kernel from `initrd/.unisrc` is
+/// there.
+const STARTUP_SCRIPT: &str = "/root/.unisrc";
 
 lazy_static! {
     static ref SH: spin::Mutex<Shell> = spin::Mutex::new({
         let mut sh = Shell::new();
+        register_commands(&mut sh.commands, &builtins::COMMANDS);
+        register_commands(&mut sh.commands, &fileops::COMMANDS);
+        register_commands(&mut sh.commands, &ls::COMMANDS);
+        register_commands(&mut sh.commands, &tree::COMMANDS);
+        register_commands(&mut sh.commands, &search::COMMANDS);
+        register_commands(&mut sh.commands, &text::COMMANDS);
+        register_commands(&mut sh.commands, &hexdump::COMMANDS);
+        register_commands(&mut sh.commands, &pager::COMMANDS);
+        register_commands(&mut sh.commands, &disk::COMMANDS);
+        register_commands(&mut sh.commands, &pci::COMMANDS);
+        register_commands(&mut sh.commands, &transfer::COMMANDS);
+        register_commands(&mut sh.commands, &mount::COMMANDS);
+        register_commands(&mut sh.commands, &fatfs::COMMANDS);
+        register_commands(&mut sh.commands, &fsck::COMMANDS);
+        register_commands(&mut sh.commands, &script::COMMANDS);
+        register_commands(&mut sh.commands, &vars::COMMANDS);
+        register_commands(&mut sh.commands, &alias::COMMANDS);
         sh
     });
 }
 
-pub fn handle_keyboard_interrupt(key: DecodedKey) {
-    match key {
-        DecodedKey::Unicode(c) => SH.lock().on_key_pressed(c as u8),
-        DecodedKey::RawKey(rk) => {}
+/// Keys pressed and not yet handled, more are dropped.
+const MAX_COUNT_KEYS: usize = 32;
+
+struct KeyQueue {
+    keys: [DecodedKey; MAX_COUNT_KEYS],
+    first: usize,
+    len: usize,
+}
+
+static KEYS: Mutex<KeyQueue> = Mutex::new(KeyQueue {
+    keys: [DecodedKey::Unicode('\0'); MAX_COUNT_KEYS],
+    first: 0,
+    len: 0,
+});
+
+/// Called on the keyboard interrupt. The key is handled later from the
+/// idle loop of the kernel, so that commands run with interrupts on and
+/// disks can tell with theirs when a request is done.
+pub fn queue_key(key: DecodedKey) {
+    let mut queue = KEYS.lock();
+    if queue.len < MAX_COUNT_KEYS {
+        let index = (queue.first + queue.len) % MAX_COUNT_KEYS;
+        queue.keys[index] = key;
+        queue.len += 1;
     }
 }
 
-pub fn init_shell() {
-    good_formatting();
+/// The oldest key not yet handled.
+pub fn take_key() -> Option<DecodedKey> {
+    // the keyboard interrupt would wait for the lock forever
+    without_interrupts(|| {
+        let mut queue = KEYS.lock();
+        if queue.len == 0 {
+            return None;
+        }
+        let key = queue.keys[queue.first];
+        queue.first = (queue.first + 1) % MAX_COUNT_KEYS;
+        queue.len -= 1;
+        Some(key)
+    })
 }
 
-#[derive(Debug, Clone, Copy)]
-struct File {
-    index: usize,
-    name: [u8; MAX_SIZE_DIRECTORY_NAME],
-    count_lines: usize,
-    folder_index: usize,
-    content: [u8; BUF_SIZE],
+pub fn handle_key(key: DecodedKey) {
+    match key {
+        DecodedKey::Unicode(c) => SH.lock().on_key_pressed(c as u8),
+        DecodedKey::RawKey(key) => SH.lock().on_raw_key(key),
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Directory {
-    index: usize,
-    name: [u8; MAX_SIZE_DIRECTORY_NAME],
-    parent_index: usize,
-    child_count: usize,
-    child_indexes: [usize; MAX_COUNT_CHILDREN_DIRECTORIES],
-    files_indexes: [usize; MAX_COUNT_FILES_IN_FOLDER],
+pub fn init_shell() {
+    let mut sh = SH.lock();
+    sh.mount_at_boot();
+    sh.unpack_initrd();
+    // the file tree is empty at first, the script comes from the disk or
+    // the initrd
+    sh.run_startup_script();
+    sh.print_prompt();
 }
 
-struct DirectoryList {
-    directories: [Directory; MAX_COUNT_DIRECTORIES],
+/// Makes `command` available in the shell, e.g. for commands provided by
+/// drivers or games living outside of this module.
+pub fn register_command(command: &'static dyn Command) -> Result<(), RegisterError> {
+    SH.lock().commands.register(command)
 }
 
-struct FileList {
-    files: [File; MAX_COUNT_FILES],
+fn register_commands(registry: &mut CommandRegistry, commands: &[&'static dyn Command]) {
+    for &command in commands {
+        registry
+            .register(command)
+            .expect("shell commands must have unique names");
+    }
 }
 
-struct Shell {
-    buf: [u8; 80],
+pub struct Shell {
+    buf: [u8; COMMAND_LINE_SIZE],
     buf_len: usize,
-    directory_list: DirectoryList,
-    files_list: FileList,
+    fs: FileSystem,
     current_directory: usize,
     is_editing_file: bool,
     current_editing_file: usize,
+    commands: CommandRegistry,
+    /// Exit status of the last command, available as `$?`.
+    last_status: u8,
+    vars: Variables,
+    aliases: Aliases,
+    /// How many scripts are running inside each other.
+    script_depth: usize,
+    /// Width of the prompt printed last, which backspace must not erase.
+    prompt_length: u32,
+    /// Set while `rm` waits for the user to confirm it.
+    pending_removal: Option<Removal>,
+    /// Commands that run once `rm` has its answer.
+    deferred: Option<(Separator, Buffer<MAX_SIZE_DEFERRED_LINE>)>,
+    /// Set while `more` shows its text.
+    pager: Option<Pager>,
+    /// Disk `sync` writes the directories and files to.
+    mounted: Option<usize>,
+    /// Disk the `fat` commands work on.
+    fat_disk: Option<usize>,
 }
 
-pub fn parameters_splitter(arr: [u8; 80], buf_len: usize) -> ([u8; COMMAND_SIZE], [u8; ARGV_SIZE]) {
-    let mut cmd: [u8; COMMAND_SIZE] = [b'\0'; COMMAND_SIZE];
-    let mut argument: [u8; ARGV_SIZE] = [b'\0'; ARGV_SIZE];
-
-    let mut i = 0;
-
-    while arr[i] != b' ' && i < COMMAND_SIZE {
-        cmd[i] = arr[i];
-        i += 1;
-    }
-
-    if i == buf_len {
-        return (cmd, argument);
-    }
-
-    i += 1;
-
-    let mut j = 0;
-    while i < buf_len {
-        argument[j] = arr[i];
-        i += 1;
-        j += 1;
-    }
-
-    return (cmd, argument);
-}
-
-pub fn compare_str_with_arr(str_for_compare: &str, arr: [u8; COMMAND_SIZE]) -> bool {
-    let mut are_the_same = true;
-
-    let mut i = 0;
-    for symbol in str_for_compare.bytes() {
-        if symbol != arr[i] {
-            are_the_same = false;
-        }
-        i += 1;
+fn print_error(cmd: &str, error: ShellError) {
+    match error {
+        ShellError::CommandNotFound => println!("[Error] Command \"{}\" not found!", cmd),
+        ShellError::Failed => {}
+        error => println!("[Error] {}: {}", cmd, error),
     }
-    return are_the_same;
-}
-
-fn good_formatting() {
-    print!("{}", FORMATING_STRING);
-}
-
-fn print_error_command_not_found(cmd: [u8; COMMAND_SIZE]) {
-    println!();
-    print!(
-        "Command \"{}\" not found!",
-        core::str::from_utf8(&cmd).unwrap().trim_matches('\0')
-    );
 }
 
 impl Shell {
-    fn execute_command(&mut self, argv: ([u8; COMMAND_SIZE], [u8; ARGV_SIZE])) {
-        if compare_str_with_arr("echo", argv.0) {
-            self.echo_command(argv.1);
-        } else if compare_str_with_arr("curdir", argv.0) {
-            self.current_directory_command(self.directory_list.directories[self.current_directory]);
-        } else if compare_str_with_arr("mkdir", argv.0) {
-            self.create_folder_command(argv.1);
-        } else if compare_str_with_arr("clear", argv.0) {
-            self.clear_command();
-        } else if compare_str_with_arr("cd", argv.0) {
-            self.change_directory_command(argv.1);
-        } else if compare_str_with_arr("dirtree", argv.0) {
-            self.directory_tree_command(self.directory_list.directories[self.current_directory], 0);
-        } else if compare_str_with_arr("deldir", argv.0) {
-            self.delete_directory_command(argv.1);
-        } else if compare_str_with_arr("ls", argv.0) {
-            self.list_files_command();
-        } else if compare_str_with_arr("mkfile", argv.0) {
-            self.create_file_command(argv.1);
-        } else if compare_str_with_arr("delfile", argv.0) {
-            self.delete_file_command(argv.1);
-        } else if compare_str_with_arr("readfile", argv.0) {
-            self.print_file_content_command(argv.1);
-        } else if compare_str_with_arr("editfile", argv.0) {
-            self.edit_file_command(argv.1);
-        } else {
-            print_error_command_not_found(argv.0);
-        }
+    /// Whether the next keys belong to the editor, a question or the pager
+    /// rather than to the command line. Commands after the editor or the
+    /// pager are dropped, they would only run behind the back of the user,
+    /// the ones after a question of `rm` run once it is answered.
+    fn waits_for_keys(&self) -> bool {
+        self.is_editing_file || self.pending_removal.is_some() || self.pager.is_some()
     }
 
-    fn echo_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        println!();
-        for symbol in argv {
-            print!("{}", symbol as char);
-        }
-    }
-
-    fn list_files_command(&mut self) {
-        println!();
-        for i in 0..MAX_COUNT_FILES_IN_FOLDER {
-            if self.directory_list.directories[self.current_directory].files_indexes[i]
-                != DELETED_INDEX_FILE
-            {
-                print!(
-                    "{}    ",
-                    core::str::from_utf8(
-                        &self.files_list.files[self.directory_list.directories
-                            [self.current_directory]
-                            .files_indexes[i]]
-                            .name
-                            .clone()
-                    )
-                    .unwrap()
-                    .trim_matches('\0')
-                );
-            }
-        }
-    }
-
-    fn get_file_index_by_name(&mut self, argv: [u8; ARGV_SIZE]) -> usize {
-        let mut current_file_index = DELETED_INDEX_FILE;
-        let mut is_same = true;
-
-        for i in 0..MAX_COUNT_FILES_IN_FOLDER {
-            current_file_index =
-                self.directory_list.directories[self.current_directory].files_indexes[i];
-
-            if current_file_index == DELETED_INDEX_FILE {
-                continue;
-            }
-
-            is_same = true;
-            for j in 0..MAX_SIZE_DIRECTORY_NAME {
-                if argv[j] == b'\0' {
-                    break;
-                }
-
-                if argv[j] != self.files_list.files[current_file_index].name[j] {
-                    is_same = false;
-                    break;
-                }
-            }
-
-            if is_same {
-                return current_file_index;
-            }
-        }
-
-        return DELETED_INDEX_FILE;
+    /// Runs every command of `line`, honouring `;`, `&&` and `||`.
+    fn execute_line(&mut self, line: &[u8]) {
+        self.execute_commands(CommandList::new(line));
     }
 
-    fn get_folder_index_by_name(&mut self, argv: [u8; ARGV_SIZE]) -> usize {
-
-        for i in 0..MAX_COUNT_CHILDREN_DIRECTORIES {
-            let mut is_same = true;
-            let dir_index = self.directory_list.directories[self.current_directory].child_indexes[i];
+    fn execute_commands(&mut self, mut commands: CommandList) {
+        while !self.waits_for_keys() {
+            let (separator, command) = match commands.next() {
+                Some(next) => next,
+                None => return,
+            };
 
-            if dir_index == DELETED_INDEX_DIRECTORY
-            {
-                continue;
+            if separator.should_run(self.last_status) {
+                self.execute_command(command);
             }
-
-            for i in 0..ARGV_SIZE {
-                if argv[i] == b'\0' {
-                    break;
-                }
-
-                if self.directory_list.directories[dir_index].name[i] != argv[i] {
-                    is_same = false;
-                    break;
-                }
-            }
-
-            if is_same {
-                // return index of child_indeexes array of current folder
-                return i;
-            } 
-        }
-
-        return DELETED_INDEX_DIRECTORY;
-    }
-
-    fn print_file_content_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        self.clear_command();
-
-        let current_file_index = self.get_file_index_by_name(argv);
-
-        if current_file_index == DELETED_INDEX_FILE {
-            print!(
-                "\n[Error] File \"{}\" does not exist!",
-                core::str::from_utf8(&argv.clone())
-                    .unwrap()
-                    .trim_matches('\0')
-            );
-            return;
         }
 
-        for j in 0..(BUF_WIDTH * (self.files_list.files[current_file_index].count_lines) as u32) {
-            print!(
-                "{}",
-                self.files_list.files[current_file_index].content[j as usize] as char
-            );
+        if self.pending_removal.is_some() {
+            let (separator, rest) = commands.rest();
+            self.defer(separator, rest);
         }
     }
 
-    fn edit_file_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        let current_file_index = self.get_file_index_by_name(argv);
-
-        if current_file_index == DELETED_INDEX_FILE {
-            print!(
-                "\n[Error] File \"{}\" does not exist!",
-                core::str::from_utf8(&argv.clone())
-                    .unwrap()
-                    .trim_matches('\0')
-            );
+    /// Keeps `commands` to run after the answer to `rm`, e.g. `echo done`
+    /// of `rm -r a && echo done`. Inside an alias the rest of the alias is
+    /// kept first, then the rest of the line it was used in.
+    fn defer(&mut self, separator: Separator, commands: &[u8]) {
+        if commands.is_empty() {
             return;
         }
 
-        self.is_editing_file = true;
-        self.current_editing_file = current_file_index;
-        self.files_list.files[self.current_editing_file].count_lines = 0;
-
-        self.clear_command();
-    }
-
-    fn delete_file_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        self.clear_command();
-
-        let current_file_index = self.get_file_index_by_name(argv);
-
-        if current_file_index == DELETED_INDEX_FILE {
-            print!(
-                "\n[Error] File \"{}\" does not exist!",
-                core::str::from_utf8(&argv.clone())
-                    .unwrap()
-                    .trim_matches('\0')
-            );
-            return;
-        }
-
-        self.files_list.files[current_file_index] = File {
-            index: DELETED_INDEX_FILE,
-            name: [b'\0'; MAX_SIZE_DIRECTORY_NAME],
-            count_lines: 0,
-            folder_index: DELETED_INDEX_DIRECTORY,
-            content: [b' '; BUF_SIZE],
+        let (first, mut line) = match self.deferred.take() {
+            Some((first, line)) => (first, line),
+            None => (separator, Buffer::new()),
         };
+        let symbol = if line.is_empty() { "" } else { separator.symbol() };
 
-        for i in 0..MAX_COUNT_FILES_IN_FOLDER {
-            if self.directory_list.directories[self.current_directory].child_indexes[i]
-                == current_file_index
-            {
-                self.directory_list.directories[self.current_directory].child_indexes[i] =
-                    DELETED_INDEX_FILE;
+        if line.len() + symbol.len() + commands.len() > MAX_SIZE_DEFERRED_LINE {
+            println!("[Error] sh: The commands after rm do not fit into the line and are skipped");
+        } else {
+            let _ = line.write_str(symbol);
+            for &byte in commands {
+                line.push(byte);
             }
         }
+        self.deferred = Some((first, line));
     }
 
-    fn create_file_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        let mut name_size = 0;
-        let mut name = [b'\0'; MAX_SIZE_DIRECTORY_NAME];
-
-        for i in 0..ARGV_SIZE {
-            if argv[i] == b'\0' {
-                break;
-            }
-            name[i] = argv[i];
-            name_size += 1;
+    /// Runs the commands kept by `defer`, once `rm` has set `$?`.
+    fn run_deferred(&mut self) {
+        if let Some((separator, line)) = self.deferred.take() {
+            self.execute_commands(CommandList::after(separator, line.as_bytes()));
         }
+    }
 
-        if name_size > MAX_SIZE_DIRECTORY_NAME {
-            print!("\n[Error] The maximum size of the file name is 10 characters");
+    fn execute_command(&mut self, line: &[u8]) {
+        let mut used = Expanding::default();
+        if let Some(expanded) = self.expand_aliases(line, &mut used) {
+            let outer = self.aliases.begin_expansion(&used);
+            self.execute_line(expanded.as_bytes());
+            self.aliases.end_expansion(outer);
             return;
         }
 
-        let mut file_index = DELETED_INDEX_FILE;
+        let mut stages = Pipeline::new(line);
+        let first = stages.next().unwrap_or(line);
 
-        for i in 0..MAX_COUNT_FILES {
-            if self.files_list.files[i].index == DELETED_INDEX_FILE {
-                file_index = i;
-                break;
-            }
-        }
-
-        if file_index == DELETED_INDEX_FILE {
-            print!("\n[Error] There is not a free space!");
+        if stages.is_empty() {
+            let mut console = Console;
+            self.run_command(first, &mut Io::console(&mut console));
             return;
         }
 
-        for i in 0..MAX_COUNT_FILES_IN_FOLDER {
-            if self.files_list.files[i].index == DELETED_INDEX_FILE {
-                file_index = i;
-                break;
-            }
-        }
-
-        let file = File {
-            index: file_index,
-            name: name,
-            count_lines: 0,
-            folder_index: self.current_directory,
-            content: [b' '; BUF_SIZE],
-        };
-
-        self.is_editing_file = true;
-        self.current_editing_file = file_index;
-
-        SCREEN.lock().clear();
-
-        self.files_list.files[file_index] = file;
-
-        let mut index_for_folder = DELETED_INDEX_FILE;
-        for i in 0..MAX_COUNT_FILES_IN_FOLDER {
-            if self.directory_list.directories[self.current_directory].files_indexes[i]
-                == DELETED_INDEX_FILE
-            {
-                index_for_folder = i;
-            }
-        }
-
-        self.directory_list.directories[self.current_directory].files_indexes[index_for_folder] =
-            file_index;
-    }
-
-    fn check_correct_dir_name(&mut self, dir_name: [u8; ARGV_SIZE]) -> bool {
-        for i in 0..MAX_SIZE_DIRECTORY_NAME {
-            if dir_name[i] != b'\0' {
-                return true;
-            }
+        if Pipeline::new(line).any(|stage| stage.iter().all(|&b| b == b' ')) {
+            let error = ShellError::Parse(ParseError::MissingCommand);
+            print_error("sh", error);
+            self.last_status = error.status();
+            return;
         }
 
-        return false;
+        self.execute_pipeline(Pipeline::new(line));
     }
 
-    fn delete_directory_command(&mut self, dir_name: [u8; ARGV_SIZE]) {
-        let mut name_size = 0;
+    /// Runs the stages of a pipeline one after another, every stage getting
+    /// what the one before it wrote as its input. Output that does not fit
+    /// into `PIPE_SIZE` bytes is dropped.
+    fn execute_pipeline(&mut self, mut stages: Pipeline) {
+        let mut input = Buffer::<PIPE_SIZE>::new();
+        let mut output = Buffer::<PIPE_SIZE>::new();
+        let mut has_input = false;
 
-        for i in 0..ARGV_SIZE {
-            if dir_name[i] == b'\0' {
+        while let Some(stage) = stages.next() {
+            if self.waits_for_keys() {
                 break;
             }
-            name_size += 1;
-        }
 
-        if name_size == 0
-        {
-            print!("\n[Error] Specify a folder name!");
-            return;
-        }
-
-        if name_size > MAX_SIZE_DIRECTORY_NAME {
-            print!("\n[Error] The maximum size of the directory name is 10 characters");
-            return;
-        }
-
-        let cur_dir = self.directory_list.directories[self.current_directory];
-
-        let index_dir_to_delete = self.get_folder_index_by_name(dir_name);
-
-        if index_dir_to_delete == DELETED_INDEX_DIRECTORY {
-            print!(
-                "\n[Error] Directory \"{}\" not found!",
-                core::str::from_utf8(&dir_name).unwrap().trim_matches('\0')
-            );
-            return;
-        }
+            let mut console = Console;
+            let mut io = match stages.is_empty() {
+                true => Io::console(&mut console),
+                false => Io::new(&mut output),
+            };
+            if has_input {
+                io.input = Some(input.as_bytes());
+            }
 
-        let dir_to_delete = self.directory_list.directories[self.current_directory].child_indexes[index_dir_to_delete];
+            self.run_command(stage, &mut io);
 
-        if self.directory_list.directories[dir_to_delete].child_count > 0 {
-            print!("[Error] Count of childrens must be 0");
-            return;
+            core::mem::swap(&mut input, &mut output);
+            output.clear();
+            has_input = true;
         }
-
-        self.directory_list.directories[self.current_directory].child_count -= 1;
-
-        self.directory_list.directories[dir_to_delete] = Directory {
-            index: DELETED_INDEX_DIRECTORY,
-            name: [b' '; MAX_SIZE_DIRECTORY_NAME],
-            parent_index: DELETED_INDEX_DIRECTORY,
-            child_count: DELETED_INDEX_DIRECTORY,
-            child_indexes: [DELETED_INDEX_DIRECTORY; MAX_COUNT_CHILDREN_DIRECTORIES],
-            files_indexes: [DELETED_INDEX_FILE; MAX_COUNT_FILES_IN_FOLDER],
-        };
-
-        self.directory_list.directories[cur_dir.index].child_indexes[index_dir_to_delete] = DELETED_INDEX_DIRECTORY;
-
-        print!(
-            "\n[Ok] Directory \"{}\" deleted succsessfully!",
-            core::str::from_utf8(&dir_name.clone())
-                .unwrap()
-                .trim_matches('\0')
-        );
     }
 
-    fn change_directory_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        if argv[0] == b'.' {
-            self.current_directory =
-                self.directory_list.directories[self.current_directory].parent_index;
-            return;
-        }
-
-        let cur_dir = self.directory_list.directories[self.current_directory];
-
-        for dir_index in cur_dir.child_indexes {
-            let mut is_same = true;
-
-            if dir_index == DELETED_INDEX_DIRECTORY
-            {
-                continue;
+    /// Runs a single command with `io` and records its exit status.
+    fn run_command(&mut self, line: &[u8], io: &mut Io) {
+        let argv = match self.parse_args(line) {
+            Ok(argv) => argv,
+            Err(error) => {
+                print_error("sh", error);
+                self.last_status = error.status();
+                return;
             }
+        };
 
-            for i in 0..ARGV_SIZE {
-                
-                
-                if argv[i] == b'\0' {
-                    break;
-                }
+        let name = match argv.get(0) {
+            Some(name) => name,
+            None => return,
+        };
 
-                if i == MAX_SIZE_DIRECTORY_NAME {
-                    print!("[Error] The maximum size of the directory name is 10 characters");
-                    return;
-                }
+        let command = self.commands.find(name);
 
-                if self.directory_list.directories[dir_index].name[i] != argv[i] {
-                    is_same = false;
-                    break;
-                }
+        let result = match (vars::parse_assignment(name), command) {
+            (Some((var_name, value)), _) if argv.len() == 1 => {
+                self.vars.set(var_name, value).map_err(ShellError::from)
             }
+            (_, Some(command)) => command.run(self, &argv, io),
+            (_, None) => Err(ShellError::CommandNotFound),
+        };
 
-            if is_same {
-                self.current_directory = self.directory_list.directories[dir_index].index;
-                return;
+        self.last_status = match result {
+            Ok(()) => EXIT_SUCCESS,
+            Err(error) => {
+                print_error(name, error);
+                if let (ShellError::Usage, Some(command)) = (error, command) {
+                    println!("Usage: {}", command.usage());
+                }
+                error.status()
             }
-        }
-
-        print!(
-            "\nFolder \"{}\" is not exist!",
-            core::str::from_utf8(&argv.clone())
-                .unwrap()
-                .trim_matches('\0')
-        )
+        };
     }
 
-    fn clear_command(&mut self) {
-        SCREEN.lock().clear();
-    }
+    /// `line` with the first word of every command of the pipeline replaced
+    /// by the alias of that name, if there is any. The aliases used are
+    /// marked in `used`.
+    fn expand_aliases(&self, line: &[u8], used: &mut Expanding) -> Option<Buffer<MAX_SIZE_EXPANDED_LINE>> {
+        let mut expanded = Buffer::new();
+        let mut is_expanded = false;
 
-    fn directory_tree_command(&mut self, current_directory: Directory, tab_count: usize) {
-        println!();
-        for i in 0..MAX_COUNT_CHILDREN_DIRECTORIES {
-            if current_directory.child_indexes[i] == DELETED_INDEX_DIRECTORY
-            {
-                continue;
+        for (index, stage) in Pipeline::new(line).enumerate() {
+            if index > 0 {
+                expanded.push(b'|');
             }
 
-            let child_directory =
-                self.directory_list.directories[current_directory.child_indexes[i]];
-
-            for tc in 0..tab_count {
-                for ts in 0..4 {
-                    print!(" ");
+            let start = stage.iter().position(|&b| b != b' ').unwrap_or(stage.len());
+            let end = stage[start..]
+                .iter()
+                .position(|&b| b == b' ')
+                .map_or(stage.len(), |len| start + len);
+            let value = core::str::from_utf8(&stage[start..end])
+                .ok()
+                .and_then(|name| self.aliases.expand(name, used));
+
+            let rest = match value {
+                Some(value) => {
+                    let _ = expanded.write_str(value);
+                    is_expanded = true;
+                    &stage[end..]
                 }
-            }
-            print!(
-                "/{}",
-                core::str::from_utf8(&child_directory.name)
-                    .unwrap()
-                    .trim_matches('\0')
-            );
-
-            self.directory_tree_command(child_directory, tab_count + 1);
-        }
-    }
-
-    fn search_free_index_child_indexes(&mut self) -> usize {
-        for i in 0..MAX_COUNT_CHILDREN_DIRECTORIES {
-            if self.directory_list.directories[self.current_directory].child_indexes[i]
-                == DELETED_INDEX_DIRECTORY
-            {
-                return i;
+                None => stage,
+            };
+            for &symbol in rest {
+                expanded.push(symbol);
             }
         }
 
-        return DELETED_INDEX_DIRECTORY;
+        is_expanded.then_some(expanded)
     }
 
-    fn create_folder_command(&mut self, argv: [u8; ARGV_SIZE]) {
-        let mut name_size = 0;
-        for i in 0..ARGV_SIZE {
-            if argv[i] == b'\0' {
-                break;
+    fn run_startup_script(&mut self) {
+        if let Ok(file_index) = self.fs.resolve_file(ROOT_DIRECTORY, STARTUP_SCRIPT) {
+            let script = Script::from_file(self.fs.file(file_index));
+            if let Err(error) = self.run_script(&script) {
+                print_error(STARTUP_SCRIPT, error);
             }
-            name_size += 1;
         }
+    }
 
-        if name_size == 0
-        {
-            print!("\n[Error] Specify a folder name!");
-            return;
-        }
+    /// Splits `line` into words, expanding variables along the way.
+    fn parse_args(&self, line: &[u8]) -> Result<Argv, ShellError> {
+        let mut status = Buffer::<4>::new();
+        let mut pwd = Buffer::<MAX_SIZE_PATH>::new();
+        let _ = write!(status, "{}", self.last_status);
+        self.write_path(self.current_directory, &mut pwd);
 
-        if name_size > MAX_SIZE_DIRECTORY_NAME {
-            print!("\n[Error] The maximum size of the directory name is 10 characters");
-            return;
-        }
+        let argv = parser::tokenize(line, |name| self.lookup(name, status.as_str(), pwd.as_str()))
+            .map_err(ShellError::Parse)?;
+        self.expand_globs(&argv)
+    }
 
-        if self.get_folder_index_by_name(argv) != DELETED_INDEX_DIRECTORY
-        {
-            print!("\n[Error] Directory \"{}\" already exist", core::str::from_utf8(&argv.clone())
-            .unwrap()
-            .trim_matches('\0'));
-            return;
+    /// Value of a variable, including the ones the shell computes itself.
+    fn lookup<'a>(&'a self, name: &str, status: &'a str, pwd: &'a str) -> Option<&'a str> {
+        match name {
+            "?" => Some(status),
+            "PWD" => Some(pwd),
+            name => self.vars.get(name),
         }
+    }
 
-        let mut dir_index = DELETED_INDEX_DIRECTORY;
+    /// Writes the absolute path of `directory`, e.g. `/root/src`.
+    fn write_path(&self, directory: usize, out: &mut dyn Write) {
+        let current_directory = self.fs.directory(directory);
 
-        for i in 0..MAX_COUNT_DIRECTORIES {
-            if self.directory_list.directories[i].index == DELETED_INDEX_DIRECTORY {
-                dir_index = i;
-                break;
-            }
-        }
-
-        if dir_index == DELETED_INDEX_DIRECTORY {
-            print!("\n[Error] There is not a free space!");
-            return;
+        if current_directory.index != ROOT_DIRECTORY {
+            self.write_path(current_directory.parent_index, out);
         }
 
-        let free_index = self.search_free_index_child_indexes();
+        let _ = write!(out, "/{}", current_directory.name());
+    }
 
-        if free_index == DELETED_INDEX_DIRECTORY {
-            print!("\n[Error] There is not a free space!");
-            return;
+    fn print_prompt(&mut self) {
+        // commands finish their output with a new line, but the prompt must
+        // never end up in the middle of one
+        if SCREEN.lock().col != 0 {
+            println!();
         }
 
-        let mut directory: Directory = Directory {
-            index: dir_index,
-            name: [b'\0'; MAX_SIZE_DIRECTORY_NAME],
-            parent_index: self.current_directory,
-            child_count: 0,
-            child_indexes: [DELETED_INDEX_DIRECTORY; MAX_COUNT_CHILDREN_DIRECTORIES],
-            files_indexes: [DELETED_INDEX_FILE; MAX_COUNT_FILES_IN_FOLDER],
-        };
+        let mut status = Buffer::<4>::new();
+        let mut pwd = Buffer::<MAX_SIZE_PATH>::new();
+        let mut prompt = Buffer::<COMMAND_LINE_SIZE>::new();
+        let _ = write!(status, "{}", self.last_status);
+        self.write_path(self.current_directory, &mut pwd);
 
-        for i in 0..MAX_SIZE_DIRECTORY_NAME {
-            directory.name[i] = argv[i];
+        let ps1 = self.vars.get("PS1").unwrap_or(FORMATING_STRING);
+        let lookup = |name: &str| self.lookup(name, status.as_str(), pwd.as_str());
+        if vars::expand(ps1, lookup, &mut prompt).is_err() {
+            prompt.clear();
+            let _ = prompt.write_str(FORMATING_STRING);
         }
 
-        self.directory_list.directories[dir_index] = directory;
-        self.directory_list.directories[self.current_directory].child_indexes[free_index] =
-            dir_index;
-
-        self.directory_list.directories[self.current_directory].child_count += 1;
-
-        print!(
-            "\n[Ok] Directory \"{}\" created succsessfully!",
-            core::str::from_utf8(&directory.name.clone())
-                .unwrap()
-                .trim_matches('\0')
-        );
+        print!("{}", prompt.as_str());
+        self.prompt_length = SCREEN.lock().col;
     }
 
-    fn current_directory_command(&mut self, current_directory: Directory) -> usize {
-        let parent_directory = self.directory_list.directories[current_directory.parent_index];
-        let mut nesting = 0;
-
-        if current_directory.index > 0 {
-            nesting = self.current_directory_command(parent_directory);
-        } else {
-            println!();
-        }
-
-        print!(
-            "/{}",
-            core::str::from_utf8(&current_directory.name.clone())
-                .unwrap()
-                .trim_matches('\0')
-        );
-
-        return nesting;
+    fn clear_screen(&mut self) {
+        SCREEN.lock().clear();
     }
 
     pub fn new() -> Shell {
-        let mut shell: Shell = Shell {
-            buf: [0; 80],
+        let mut shell = Shell {
+            buf: [0; COMMAND_LINE_SIZE],
             buf_len: 0,
-            directory_list: DirectoryList {
-                directories: ([Directory {
-                    index: DELETED_INDEX_DIRECTORY,
-                    name: [b' '; MAX_SIZE_DIRECTORY_NAME],
-                    parent_index: 0,
-                    child_count: 0,
-                    child_indexes: [DELETED_INDEX_DIRECTORY; MAX_COUNT_CHILDREN_DIRECTORIES],
-                    files_indexes: [DELETED_INDEX_FILE; MAX_COUNT_FILES_IN_FOLDER],
-                }; MAX_COUNT_DIRECTORIES]),
-            },
-            current_directory: 0,
-            files_list: FileList {
-                files: [File {
-                    index: DELETED_INDEX_FILE,
-                    name: [b'\0'; MAX_SIZE_DIRECTORY_NAME],
-                    count_lines: 0,
-                    folder_index: DELETED_INDEX_DIRECTORY,
-                    content: [b' '; BUF_SIZE],
-                }; MAX_COUNT_FILES],
-            },
+            fs: FileSystem::new(),
+            current_directory: ROOT_DIRECTORY,
             is_editing_file: false,
             current_editing_file: DELETED_INDEX_FILE,
+            commands: CommandRegistry::new(),
+            last_status: EXIT_SUCCESS,
+            vars: Variables::new(),
+            aliases: Aliases::new(),
+            script_depth: 0,
+            prompt_length: 0,
+            pending_removal: None,
+            deferred: None,
+            pager: None,
+            mounted: None,
+            fat_disk: None,
         };
 
-        let root_directory = Directory {
-            index: 0,
-            name: [
-                b'r', b'o', b'o', b't', b'\0', b'\0', b'\0', b'\0', b'\0', b'\0',
-            ],
-            parent_index: 0,
-            child_count: 0,
-            child_indexes: [DELETED_INDEX_DIRECTORY; MAX_COUNT_CHILDREN_DIRECTORIES],
-            files_indexes: [DELETED_INDEX_FILE; MAX_COUNT_FILES_IN_FOLDER],
-        };
+        shell
+            .vars
+            .set("PS1", FORMATING_STRING)
+            .expect("the default prompt must be a valid variable");
 
-        shell.directory_list.directories[0] = root_directory;
+        shell
+    }
 
-        return shell;
+    /// Keys without a character, which only the pager uses.
+    pub fn on_raw_key(&mut self, key: KeyCode) {
+        let key = match key {
+            KeyCode::ArrowUp => PagerKey::LineUp,
+            KeyCode::ArrowDown => PagerKey::LineDown,
+            KeyCode::PageUp => PagerKey::PageUp,
+            KeyCode::PageDown => PagerKey::PageDown,
+            KeyCode::Home => PagerKey::Top,
+            KeyCode::End => PagerKey::Bottom,
+            _ => return,
+        };
+        self.on_pager_key(key);
     }
 
     pub fn on_key_pressed(&mut self, key: u8) {
+        if self.pager.is_some() {
+            self.on_pager_key(PagerKey::Char(key));
+            return;
+        }
+
         match key {
             b'\n' => {
                 if self.is_editing_file {
-                    self.files_list.files[self.current_editing_file].count_lines += 1;
+                    self.fs.file_mut(self.current_editing_file).count_lines += 1;
                     println!();
                     return;
                 }
 
-                let argv = parameters_splitter(self.buf, self.buf_len);
-
-                self.execute_command(argv);
+                println!();
+                let line = self.buf;
+                match self.pending_removal.take() {
+                    Some(removal) => {
+                        self.confirm_removal(removal, &line[..self.buf_len]);
+                        self.run_deferred();
+                    }
+                    None => self.execute_line(&line[..self.buf_len]),
+                }
                 self.buf_len = 0;
 
-                if self.is_editing_file {
+                if self.is_editing_file || self.pager.is_some() {
                     return;
                 }
-                println!();
-                good_formatting();
+                if self.pending_removal.is_some() {
+                    self.prompt_length = SCREEN.lock().col;
+                    return;
+                }
+                self.print_prompt();
             }
             37 =>
                 // key code arrow left
@@ -734,7 +552,7 @@ impl Shell {
                     return;
                 }
 
-                SCREEN.lock().delete_last_symbol(FORMATING_STRING_LENGTH);
+                SCREEN.lock().delete_last_symbol(self.prompt_length);
 
                 if self.buf_len > 0 {
                     self.buf_len -= 1;
@@ -742,40 +560,23 @@ impl Shell {
 
                 self.buf[self.buf_len] = 0;
             }
-            32 =>
-            // key code of space button
-            {
-                print!("{}", key as char);
-
-                if self.is_editing_file {
-                    return;
-                }
-
-                self.buf[self.buf_len] = b' ';
-                self.buf_len += 1;
-            }
             9 =>
             // tab key
             {
                 if self.is_editing_file {
                     self.is_editing_file = false;
-                    self.files_list.files[self.current_editing_file].count_lines += 1;
-
-                    self.files_list.files[self.current_editing_file].content =
-                        SCREEN.lock().get_buffer();
-
-                    self.clear_command();
-                    print!(
-                        "\n[Ok] File \"{}\" saved succsessfully!\n",
-                        core::str::from_utf8(
-                            &self.files_list.files[self.current_editing_file]
-                                .name
-                                .clone()
-                        )
-                        .unwrap()
-                        .trim_matches('\0')
+
+                    let file = self.fs.file_mut(self.current_editing_file);
+                    file.count_lines += 1;
+                    file.content = SCREEN.lock().get_buffer();
+                    file.modified = rtc::now();
+
+                    self.clear_screen();
+                    println!(
+                        "[Ok] File \"{}\" saved succsessfully!",
+                        self.fs.file(self.current_editing_file).name()
                     );
-                    good_formatting();
+                    self.print_prompt();
                 }
             }
             _ => {
@@ -784,6 +585,10 @@ impl Shell {
                     return;
                 }
 
+                if self.buf_len == COMMAND_LINE_SIZE {
+                    return;
+                }
+
                 self.buf[self.buf_len] = key;
                 self.buf_len += 1;
                 print!("{}", key as char);
@@ -0,0 +1,223 @@
+use core::fmt;
+
+use super::command::{Argv, Command, Io, ShellError};
+use super::Shell;
+
+pub static COMMANDS: [&dyn Command; 2] = [&Alias, &Unalias];
+
+const MAX_COUNT_ALIASES: usize = 16;
+const MAX_SIZE_ALIAS_NAME: usize = 16;
+const MAX_SIZE_ALIAS_VALUE: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasError {
+    InvalidName,
+    ValueTooLong,
+    NoSpace,
+    NotFound,
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            AliasError::InvalidName => "Invalid alias name",
+            AliasError::ValueTooLong => "The maximum size of an alias is 64 characters",
+            AliasError::NoSpace => "There is not a free space for aliases",
+            AliasError::NotFound => "No such alias",
+        };
+        f.write_str(message)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    name: [u8; MAX_SIZE_ALIAS_NAME],
+    name_len: usize,
+    value: [u8; MAX_SIZE_ALIAS_VALUE],
+    value_len: usize,
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    fn value(&self) -> &str {
+        core::str::from_utf8(&self.value[..self.value_len]).unwrap_or("")
+    }
+}
+
+/// Command names that stand for a longer command line, e.g. `ll` for
+/// `ls -l`. Only the first word of every command of a pipeline is looked up.
+pub struct Aliases {
+    aliases: [Option<Entry>; MAX_COUNT_ALIASES],
+    /// Aliases whose expansion is running, which are not expanded again
+    /// inside of it, so that `alias ls='ls -l'` works as in sh.
+    expanding: Expanding,
+}
+
+/// One flag per slot of `Aliases`.
+pub type Expanding = [bool; MAX_COUNT_ALIASES];
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_SIZE_ALIAS_NAME
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'.')
+}
+
+impl Aliases {
+    pub fn new() -> Aliases {
+        Aliases {
+            aliases: [None; MAX_COUNT_ALIASES],
+            expanding: [false; MAX_COUNT_ALIASES],
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.aliases
+            .iter()
+            .position(|alias| matches!(alias, Some(alias) if alias.name() == name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name)
+            .and_then(|i| self.aliases[i].as_ref())
+            .map(Entry::value)
+    }
+
+    /// Value of the alias `name` unless it is being expanded already,
+    /// marking it in `used`.
+    pub fn expand(&self, name: &str, used: &mut Expanding) -> Option<&str> {
+        let slot = self.position(name).filter(|&slot| !self.expanding[slot])?;
+        used[slot] = true;
+        self.aliases[slot].as_ref().map(Entry::value)
+    }
+
+    /// Marks the aliases in `used` as being expanded, returning the marks
+    /// to put back with `end_expansion` once their expansion has run.
+    pub fn begin_expansion(&mut self, used: &Expanding) -> Expanding {
+        let outer = self.expanding;
+        for (expanding, &used) in self.expanding.iter_mut().zip(used) {
+            *expanding |= used;
+        }
+        outer
+    }
+
+    pub fn end_expansion(&mut self, outer: Expanding) {
+        self.expanding = outer;
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), AliasError> {
+        if !is_valid_name(name) {
+            return Err(AliasError::InvalidName);
+        }
+
+        if value.len() > MAX_SIZE_ALIAS_VALUE {
+            return Err(AliasError::ValueTooLong);
+        }
+
+        let slot = self
+            .position(name)
+            .or_else(|| self.aliases.iter().position(Option::is_none))
+            .ok_or(AliasError::NoSpace)?;
+
+        let mut alias = Entry {
+            name: [0; MAX_SIZE_ALIAS_NAME],
+            name_len: name.len(),
+            value: [0; MAX_SIZE_ALIAS_VALUE],
+            value_len: value.len(),
+        };
+        alias.name[..name.len()].copy_from_slice(name.as_bytes());
+        alias.value[..value.len()].copy_from_slice(value.as_bytes());
+
+        self.aliases[slot] = Some(alias);
+        Ok(())
+    }
+
+    pub fn unset(&mut self, name: &str) -> Result<(), AliasError> {
+        let slot = self.position(name).ok_or(AliasError::NotFound)?;
+        self.aliases[slot] = None;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.aliases = [None; MAX_COUNT_ALIASES];
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases
+            .iter()
+            .flatten()
+            .map(|alias| (alias.name(), alias.value()))
+    }
+}
+
+struct Alias;
+
+impl Command for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn usage(&self) -> &'static str {
+        "alias [name[=command]...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Define aliases or list them"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() < 2 {
+            for (name, value) in sh.aliases.iter() {
+                let _ = writeln!(io.out, "alias {}='{}'", name, value);
+            }
+            return Ok(());
+        }
+
+        for arg in argv.args() {
+            match arg.split_once('=') {
+                Some((name, value)) => sh.aliases.set(name, value)?,
+                None => {
+                    let value = sh.aliases.get(arg).ok_or(AliasError::NotFound)?;
+                    let _ = writeln!(io.out, "alias {}='{}'", arg, value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Unalias;
+
+impl Command for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn usage(&self) -> &'static str {
+        "unalias -a | <name...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove aliases, -a removes all of them"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        match argv.get(1) {
+            None => Err(ShellError::Usage),
+            Some("-a") => {
+                sh.aliases.clear();
+                Ok(())
+            }
+            Some(_) => {
+                for name in argv.args() {
+                    sh.aliases.unset(name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::FsError;
+use super::*;
+
+pub static COMMANDS: [&dyn Command; 13] = [
+    &Help, &Echo, &True, &False, &CurDir, &MakeDir, &Clear, &ChangeDir, &DeleteDir,
+    &MakeFile, &DeleteFile, &ReadFile, &EditFile,
+];
+
+/// The argument at `index`, failing with a usage error if it is missing.
+fn required_arg(argv: &Argv, index: usize) -> Result<&str, ShellError> {
+    argv.get(index).ok_or(ShellError::Usage)
+}
+
+/// Runs `f` for every argument from `first` on. An operand that fails is
+/// reported and the rest are still processed.
+pub fn for_each_operand(
+    sh: &mut Shell,
+    argv: &Argv,
+    first: usize,
+    io: &mut Io,
+    mut f: impl FnMut(&mut Shell, &str, &mut Io) -> Result<(), ShellError>,
+) -> Result<(), ShellError> {
+    let mut failed = false;
+
+    for operand in argv.iter().skip(first) {
+        if let Err(error) = f(sh, operand, io) {
+            let _ = writeln!(io.out, "[Error] {}: {}: {}", argv.get(0).unwrap_or(""), operand, error);
+            failed = true;
+        }
+    }
+
+    match failed {
+        true => Err(ShellError::Failed),
+        false => Ok(()),
+    }
+}
+
+struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn usage(&self) -> &'static str {
+        "help [command]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List commands or describe one of them"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if let Some(name) = argv.get(1) {
+            let command = sh.commands.find(name).ok_or(ShellError::CommandNotFound)?;
+
+            let _ = writeln!(io.out, "Usage: {}", command.usage());
+            if !command.aliases().is_empty() {
+                let _ = write!(io.out, "Aliases:");
+                for alias in command.aliases() {
+                    let _ = write!(io.out, " {}", alias);
+                }
+                let _ = writeln!(io.out);
+            }
+            let _ = writeln!(io.out, "{}", command.help());
+            return Ok(());
+        }
+
+        for command in sh.commands.iter() {
+            let _ = writeln!(io.out, "{:<24}{}", command.usage(), command.help());
+        }
+        Ok(())
+    }
+}
+
+struct Echo;
+
+impl Command for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn usage(&self) -> &'static str {
+        "echo [text...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the arguments"
+    }
+
+    fn run(&self, _sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        for (i, arg) in argv.args().enumerate() {
+            if i > 0 {
+                let _ = write!(io.out, " ");
+            }
+            let _ = write!(io.out, "{}", arg);
+        }
+        let _ = writeln!(io.out);
+        Ok(())
+    }
+}
+
+struct True;
+
+impl Command for True {
+    fn name(&self) -> &'static str {
+        "true"
+    }
+
+    fn usage(&self) -> &'static str {
+        "true"
+    }
+
+    fn help(&self) -> &'static str {
+        "Do nothing, successfully"
+    }
+
+    fn run(&self, _sh: &mut Shell, _argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        Ok(())
+    }
+}
+
+struct False;
+
+impl Command for False {
+    fn name(&self) -> &'static str {
+        "false"
+    }
+
+    fn usage(&self) -> &'static str {
+        "false"
+    }
+
+    fn help(&self) -> &'static str {
+        "Do nothing, unsuccessfully"
+    }
+
+    fn run(&self, _sh: &mut Shell, _argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        Err(ShellError::Failed)
+    }
+}
+
+struct CurDir;
+
+impl Command for CurDir {
+    fn name(&self) -> &'static str {
+        "curdir"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["pwd"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "curdir"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the path of the current directory"
+    }
+
+    fn run(&self, sh: &mut Shell, _argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        sh.write_path(sh.current_directory, io.out);
+        let _ = writeln!(io.out);
+        Ok(())
+    }
+}
+
+struct MakeDir;
+
+impl Command for MakeDir {
+    fn name(&self) -> &'static str {
+        "mkdir"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mkdir <path...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Create directories"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() < 2 {
+            return Err(ShellError::Usage);
+        }
+
+        for_each_operand(sh, argv, 1, io, |sh, path, io| {
+            let (parent, name) = sh.fs.resolve_parent(sh.current_directory, path)?;
+            sh.fs.create_directory(parent, name)?;
+
+            let _ = writeln!(io.out, "[Ok] Directory \"{}\" created succsessfully!", name);
+            Ok(())
+        })
+    }
+}
+
+struct Clear;
+
+impl Command for Clear {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn usage(&self) -> &'static str {
+        "clear"
+    }
+
+    fn help(&self) -> &'static str {
+        "Clear the screen"
+    }
+
+    fn run(&self, sh: &mut Shell, _argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        sh.clear_screen();
+        Ok(())
+    }
+}
+
+struct ChangeDir;
+
+impl Command for ChangeDir {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cd <path|.>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Enter a directory or go back to the parent with ."
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        let path = required_arg(argv, 1)?;
+
+        sh.current_directory = match path {
+            "." => sh.fs.directory(sh.current_directory).parent_index,
+            path => sh.fs.resolve_directory(sh.current_directory, path)?,
+        };
+        Ok(())
+    }
+}
+
+struct DeleteDir;
+
+impl Command for DeleteDir {
+    fn name(&self) -> &'static str {
+        "deldir"
+    }
+
+    fn usage(&self) -> &'static str {
+        "deldir <path...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Delete empty directories"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() < 2 {
+            return Err(ShellError::Usage);
+        }
+
+        for_each_operand(sh, argv, 1, io, |sh, name, io| {
+            let dir_to_delete = sh.fs.resolve_directory(sh.current_directory, name)?;
+            if dir_to_delete == sh.current_directory {
+                return Err(FsError::InUse.into());
+            }
+            sh.fs.delete_directory(dir_to_delete)?;
+
+            let _ = writeln!(io.out, "[Ok] Directory \"{}\" deleted succsessfully!", name);
+            Ok(())
+        })
+    }
+}
+
+struct MakeFile;
+
+impl Command for MakeFile {
+    fn name(&self) -> &'static str {
+        "mkfile"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mkfile <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Create a file and start editing it, Tab saves"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        let path = required_arg(argv, 1)?;
+
+        let (directory, name) = sh.fs.resolve_parent(sh.current_directory, path)?;
+        let file_index = sh.fs.create_file(directory, name)?;
+
+        sh.is_editing_file = true;
+        sh.current_editing_file = file_index;
+
+        sh.clear_screen();
+        Ok(())
+    }
+}
+
+struct DeleteFile;
+
+impl Command for DeleteFile {
+    fn name(&self) -> &'static str {
+        "delfile"
+    }
+
+    fn usage(&self) -> &'static str {
+        "delfile <path...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Delete files"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        required_arg(argv, 1)?;
+
+        for_each_operand(sh, argv, 1, io, |sh, path, _io| {
+            let file_index = sh.fs.resolve_file(sh.current_directory, path)?;
+            sh.fs.delete_file(file_index);
+            Ok(())
+        })
+    }
+}
+
+struct ReadFile;
+
+impl Command for ReadFile {
+    fn name(&self) -> &'static str {
+        "readfile"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["cat"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "readfile <path...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print files, clearing the screen first unless piped"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        required_arg(argv, 1)?;
+
+        // `cat file | grep x` only hands the text on
+        if io.is_console {
+            sh.clear_screen();
+        }
+
+        for_each_operand(sh, argv, 1, io, |sh, path, io| {
+            let file_index = sh.fs.resolve_file(sh.current_directory, path)?;
+
+            for &symbol in sh.fs.file(file_index).text() {
+                let _ = write!(io.out, "{}", symbol as char);
+            }
+            Ok(())
+        })
+    }
+}
+
+struct EditFile;
+
+impl Command for EditFile {
+    fn name(&self) -> &'static str {
+        "editfile"
+    }
+
+    fn usage(&self) -> &'static str {
+        "editfile <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Rewrite a file, Tab saves"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        let path = required_arg(argv, 1)?;
+
+        let file_index = sh.fs.resolve_file(sh.current_directory, path)?;
+
+        sh.is_editing_file = true;
+        sh.current_editing_file = file_index;
+        sh.fs.file_mut(file_index).count_lines = 0;
+
+        sh.clear_screen();
+        Ok(())
+    }
+}
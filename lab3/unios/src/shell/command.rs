@@ -0,0 +1,248 @@
+use core::fmt;
+
+use super::alias::AliasError;
+use super::fs::FsError;
+use super::parser::ParseError;
+use super::script::ScriptError;
+use super::vars::VarError;
+use super::Shell;
+use crate::block::BlockError;
+use crate::fat::FatError;
+use crate::vga_buf::SCREEN;
+use crate::xmodem::XmodemError;
+use unifs::UnifsError;
+
+pub use unish::argv::{Argv, Buffer, MAX_COUNT_ARGS};
+
+const MAX_COUNT_COMMANDS: usize = 64;
+
+/// A command the shell can look up by name and run.
+///
+/// Implementations live in their own modules and are registered in the
+/// shell's [`CommandRegistry`], either by the shell itself or by other
+/// subsystems through [`super::register_command`].
+pub trait Command: Sync {
+    /// Name the command is invoked by.
+    fn name(&self) -> &'static str;
+
+    /// Other names the command can be invoked by.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// One-line synopsis, e.g. `mkdir <name>`.
+    fn usage(&self) -> &'static str;
+
+    /// Short description shown by `help`.
+    fn help(&self) -> &'static str;
+
+    /// Runs the command. `argv[0]` is the name the command was invoked by.
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError>;
+}
+
+/// Reason a command failed. The shell prints it and turns it into the exit
+/// status of the command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellError {
+    CommandNotFound,
+    /// The arguments do not match the usage of the command.
+    Usage,
+    Fs(FsError),
+    Block(BlockError),
+    Unifs(UnifsError),
+    Fat(FatError),
+    Variable(VarError),
+    Alias(AliasError),
+    Parse(ParseError),
+    Script(ScriptError),
+    Xmodem(XmodemError),
+    /// The command needs an answer from the keyboard where nobody can give
+    /// one, e.g. in a script.
+    NotInteractive,
+    /// The command has already reported why it failed, if at all.
+    Failed,
+}
+
+pub const EXIT_SUCCESS: u8 = 0;
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_USAGE: u8 = 2;
+pub const EXIT_COMMAND_NOT_FOUND: u8 = 127;
+
+impl ShellError {
+    pub fn status(&self) -> u8 {
+        match self {
+            ShellError::CommandNotFound => EXIT_COMMAND_NOT_FOUND,
+            ShellError::Usage | ShellError::Parse(_) | ShellError::Script(_) => EXIT_USAGE,
+            ShellError::Fs(_)
+            | ShellError::Block(_)
+            | ShellError::Unifs(_)
+            | ShellError::Fat(_)
+            | ShellError::Variable(_)
+            | ShellError::Alias(_)
+            | ShellError::Xmodem(_)
+            | ShellError::NotInteractive
+            | ShellError::Failed => EXIT_FAILURE,
+        }
+    }
+}
+
+impl From<FsError> for ShellError {
+    fn from(error: FsError) -> ShellError {
+        ShellError::Fs(error)
+    }
+}
+
+impl From<BlockError> for ShellError {
+    fn from(error: BlockError) -> ShellError {
+        ShellError::Block(error)
+    }
+}
+
+impl From<UnifsError> for ShellError {
+    fn from(error: UnifsError) -> ShellError {
+        ShellError::Unifs(error)
+    }
+}
+
+impl From<FatError> for ShellError {
+    fn from(error: FatError) -> ShellError {
+        ShellError::Fat(error)
+    }
+}
+
+impl From<VarError> for ShellError {
+    fn from(error: VarError) -> ShellError {
+        ShellError::Variable(error)
+    }
+}
+
+impl From<AliasError> for ShellError {
+    fn from(error: AliasError) -> ShellError {
+        ShellError::Alias(error)
+    }
+}
+
+impl From<XmodemError> for ShellError {
+    fn from(error: XmodemError) -> ShellError {
+        ShellError::Xmodem(error)
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::CommandNotFound => f.write_str("Command not found"),
+            ShellError::Usage => f.write_str("Invalid arguments"),
+            ShellError::Fs(error) => error.fmt(f),
+            ShellError::Block(error) => error.fmt(f),
+            ShellError::Unifs(error) => error.fmt(f),
+            ShellError::Fat(error) => error.fmt(f),
+            ShellError::Variable(error) => error.fmt(f),
+            ShellError::Alias(error) => error.fmt(f),
+            ShellError::Parse(error) => error.fmt(f),
+            ShellError::Script(error) => error.fmt(f),
+            ShellError::Xmodem(error) => error.fmt(f),
+            ShellError::NotInteractive => f.write_str("Can not ask for confirmation here, use -f"),
+            ShellError::Failed => f.write_str("Failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterError {
+    /// A command with the same name or alias is already registered.
+    Duplicate,
+    /// There is no free slot left in the registry.
+    Full,
+}
+
+pub struct CommandRegistry {
+    commands: [Option<&'static dyn Command>; MAX_COUNT_COMMANDS],
+    count: usize,
+}
+
+impl CommandRegistry {
+    pub const fn new() -> CommandRegistry {
+        CommandRegistry {
+            commands: [None; MAX_COUNT_COMMANDS],
+            count: 0,
+        }
+    }
+
+    pub fn register(&mut self, command: &'static dyn Command) -> Result<(), RegisterError> {
+        if self.find(command.name()).is_some()
+            || command.aliases().iter().any(|alias| self.find(alias).is_some())
+        {
+            return Err(RegisterError::Duplicate);
+        }
+
+        if self.count == MAX_COUNT_COMMANDS {
+            return Err(RegisterError::Full);
+        }
+
+        self.commands[self.count] = Some(command);
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn find(&self, name: &str) -> Option<&'static dyn Command> {
+        self.iter()
+            .find(|command| command.name() == name || command.aliases().contains(&name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static dyn Command> + '_ {
+        self.commands[..self.count].iter().flatten().copied()
+    }
+}
+
+/// Black on yellow, used to mark parts of the output such as matches.
+const HIGHLIGHT_COLOR: u8 = 0xE0;
+
+/// Input and output handles a command runs with.
+pub struct Io<'a> {
+    pub input: Option<&'a [u8]>,
+    pub out: &'a mut dyn fmt::Write,
+    /// Whether `out` is the screen, which can show colours.
+    pub is_console: bool,
+}
+
+impl<'a> Io<'a> {
+    pub fn new(out: &'a mut dyn fmt::Write) -> Io<'a> {
+        Io {
+            input: None,
+            out,
+            is_console: false,
+        }
+    }
+
+    pub fn console(console: &'a mut Console) -> Io<'a> {
+        Io {
+            is_console: true,
+            ..Io::new(console)
+        }
+    }
+
+    /// Writes `text` in the highlight colour if the output is the screen,
+    /// as it is otherwise.
+    pub fn highlight(&mut self, text: &str) {
+        if !self.is_console {
+            let _ = self.out.write_str(text);
+            return;
+        }
+
+        let color = SCREEN.lock().color();
+        SCREEN.lock().set_color(HIGHLIGHT_COLOR);
+        let _ = self.out.write_str(text);
+        SCREEN.lock().set_color(color);
+    }
+}
+
+/// Writes to the VGA screen.
+pub struct Console;
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        crate::vga_buf::_print(format_args!("{}", s));
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::hexdump::{dump, parse_number};
+use super::Shell;
+use crate::block::{BlockDevice, BlockError, SECTOR_SIZE};
+use crate::cache::{CacheStats, CACHE_SECTORS};
+use crate::disks::{disk_name, Disk, DISKS, MAX_COUNT_DISKS};
+
+pub static COMMANDS: [&dyn Command; 4] = [&Disks, &ReadSector, &WriteSector, &Cache];
+
+/// Most sectors `readsector` shows at once.
+const MAX_COUNT_SHOWN_SECTORS: usize = 8;
+
+/// Index in `DISKS` of the disk called `name`, e.g. `hdb`.
+pub fn disk_index(name: &str) -> Result<usize, BlockError> {
+    (0..MAX_COUNT_DISKS)
+        .find(|&index| disk_name(index) == name)
+        .ok_or(BlockError::NoDevice)
+}
+
+/// Runs `f` on the disk at `index`.
+pub fn with_disk<T, E: From<BlockError>>(
+    index: usize,
+    f: impl FnOnce(&mut dyn BlockDevice) -> Result<T, E>,
+) -> Result<T, E> {
+    let mut disks = DISKS.lock();
+    let disk = disks[index].as_mut().ok_or(BlockError::NoDevice)?;
+    f(disk)
+}
+
+struct Disks;
+
+impl Command for Disks {
+    fn name(&self) -> &'static str {
+        "disks"
+    }
+
+    fn usage(&self) -> &'static str {
+        "disks"
+    }
+
+    fn help(&self) -> &'static str {
+        "List the ATA and virtio disks found at boot"
+    }
+
+    fn run(&self, _sh: &mut Shell, _argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let disks = DISKS.lock();
+
+        for (index, disk) in disks.iter().enumerate() {
+            if let Some(disk) = disk {
+                let disk = disk.device();
+                let _ = write!(io.out, "{}  {:<20} {}", disk_name(index), disk.model(), disk);
+                let _ = match disk {
+                    Disk::Ata(ata) if ata.lba48 => writeln!(io.out, ", LBA48"),
+                    Disk::Ata(_) => writeln!(io.out),
+                    Disk::Virtio(virtio) => writeln!(
+                        io.out,
+                        ", PCI {}, IRQ {}{}",
+                        virtio.address,
+                        virtio.irq,
+                        if virtio.read_only { ", read-only" } else { "" }
+                    ),
+                };
+            }
+        }
+
+        if disks.iter().all(Option::is_none) {
+            let _ = writeln!(io.out, "No disks found");
+        }
+        Ok(())
+    }
+}
+
+struct ReadSector;
+
+impl Command for ReadSector {
+    fn name(&self) -> &'static str {
+        "readsector"
+    }
+
+    fn usage(&self) -> &'static str {
+        "readsector <disk> <sector> [count]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show sectors of a disk in hex"
+    }
+
+    fn run(&self, _sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let name = argv.get(1).ok_or(ShellError::Usage)?;
+        let lba = argv.get(2).and_then(parse_number).ok_or(ShellError::Usage)?;
+        let count = match argv.get(3) {
+            Some(count) => parse_number(count).ok_or(ShellError::Usage)? as usize,
+            None => 1,
+        };
+        if count == 0 || count > MAX_COUNT_SHOWN_SECTORS || argv.len() > 4 {
+            return Err(ShellError::Usage);
+        }
+
+        let mut buffer = [0u8; MAX_COUNT_SHOWN_SECTORS * SECTOR_SIZE];
+        let buffer = &mut buffer[..count * SECTOR_SIZE];
+        with_disk(disk_index(name)?, |disk| disk.read(lba, buffer))?;
+
+        let start = lba * SECTOR_SIZE as u64;
+        dump(start, buffer.len(), |offset| buffer[(offset - start) as usize], io);
+        Ok(())
+    }
+}
+
+struct WriteSector;
+
+impl Command for WriteSector {
+    fn name(&self) -> &'static str {
+        "writesector"
+    }
+
+    fn usage(&self) -> &'static str {
+        "writesector <disk> <sector> <text>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Write a text to a sector, zero padded"
+    }
+
+    fn run(&self, _sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        let name = argv.get(1).ok_or(ShellError::Usage)?;
+        let lba = argv.get(2).and_then(parse_number).ok_or(ShellError::Usage)?;
+        let text = argv.get(3).ok_or(ShellError::Usage)?;
+        if argv.len() > 4 || text.len() > SECTOR_SIZE {
+            return Err(ShellError::Usage);
+        }
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        sector[..text.len()].copy_from_slice(text.as_bytes());
+
+        with_disk(disk_index(name)?, |disk| {
+            disk.write(lba, &sector)?;
+            disk.flush()
+        })?;
+        Ok(())
+    }
+}
+
+struct Cache;
+
+impl Command for Cache {
+    fn name(&self) -> &'static str {
+        "cache"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cache [flush | reset]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the sector caches, write them back or reset the counts"
+    }
+
+    fn run(&self, _sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() > 2 {
+            return Err(ShellError::Usage);
+        }
+        let mut disks = DISKS.lock();
+
+        match argv.get(1) {
+            Some("flush") => {
+                for disk in disks.iter_mut().flatten() {
+                    disk.flush()?;
+                }
+                return Ok(());
+            }
+            Some("reset") => {
+                for disk in disks.iter_mut().flatten() {
+                    disk.stats = CacheStats::default();
+                }
+                return Ok(());
+            }
+            Some(_) => return Err(ShellError::Usage),
+            None => {}
+        }
+
+        for (index, disk) in disks.iter().enumerate() {
+            if let Some(disk) = disk {
+                let stats = disk.stats;
+                let total = stats.hits + stats.misses;
+                let _ = writeln!(
+                    io.out,
+                    "{}  {:>2}/{} sectors, {} dirty  hits {} misses {} ({}%)  read ahead {}  written {}",
+                    disk_name(index),
+                    disk.count_cached(),
+                    CACHE_SECTORS,
+                    disk.count_dirty(),
+                    stats.hits,
+                    stats.misses,
+                    if total == 0 { 0 } else { stats.hits * 100 / total },
+                    stats.read_ahead,
+                    stats.written_back
+                );
+            }
+        }
+
+        if disks.iter().all(Option::is_none) {
+            let _ = writeln!(io.out, "No disks found");
+        }
+        Ok(())
+    }
+}
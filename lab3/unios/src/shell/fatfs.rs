@@ -0,0 +1,293 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::disk::{disk_index, with_disk};
+use super::fs::{Entry, FsError, FILE_SIZE, LINE_LENGTH, MAX_COUNT_LINES};
+use super::text::Source;
+use super::Shell;
+use crate::disks::disk_name;
+use crate::fat::{FatError, Volume};
+use crate::rtc;
+
+pub static COMMANDS: [&dyn Command; 7] = [&FatMount, &FatList, &FatRead, &FatPut, &FatGet, &FatMakeDir, &FatRemove];
+
+/// Text of a file of the shell with every line ended by `\n`.
+const MAX_SIZE_PUT: usize = FILE_SIZE + MAX_COUNT_LINES;
+
+/// Runs `f` on the FAT volume chosen with `fatmount`.
+fn with_volume<T>(
+    sh: &Shell,
+    argv: &Argv,
+    io: &mut Io,
+    f: impl FnOnce(&mut Volume, &mut Io) -> Result<T, FatError>,
+) -> Result<T, ShellError> {
+    let index = match sh.fat_disk {
+        Some(index) => index,
+        None => {
+            let _ = writeln!(io.out, "[Error] {}: No FAT disk is chosen, use fatmount", argv.get(0).unwrap_or(""));
+            return Err(ShellError::Failed);
+        }
+    };
+
+    Ok(with_disk(index, |disk| f(&mut Volume::open(disk)?, io))?)
+}
+
+/// Last component of `path`.
+fn base_name(path: &str) -> &str {
+    let path = path.trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+struct FatMount;
+
+impl Command for FatMount {
+    fn name(&self) -> &'static str {
+        "fatmount"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatmount <disk>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Choose the FAT disk the fat commands use"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let name = argv.get(1).filter(|_| argv.len() == 2).ok_or(ShellError::Usage)?;
+        let index = disk_index(name)?;
+
+        with_disk(index, |disk| {
+            let mut volume = Volume::open(disk)?;
+            let free = volume.free_clusters()?;
+            let _ = writeln!(
+                io.out,
+                "{}: {}, {} of {} clusters of {} bytes free",
+                disk_name(index),
+                volume.kind,
+                free,
+                volume.cluster_count,
+                volume.cluster_size()
+            );
+            Ok::<(), FatError>(())
+        })?;
+
+        sh.fat_disk = Some(index);
+        Ok(())
+    }
+}
+
+struct FatList;
+
+impl Command for FatList {
+    fn name(&self) -> &'static str {
+        "fatls"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatls [path]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List a directory of the FAT disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() > 2 {
+            return Err(ShellError::Usage);
+        }
+        let path = argv.get(1).unwrap_or("/");
+
+        with_volume(sh, argv, io, |volume, io| {
+            volume.list(path, &mut |entry| {
+                let _ = match entry.is_directory() {
+                    true => writeln!(io.out, "{:>9}  {}  {}/", "<DIR>", entry.modified, entry.name.as_str()),
+                    false => writeln!(io.out, "{:>9}  {}  {}", entry.size, entry.modified, entry.name.as_str()),
+                };
+            })
+        })
+    }
+}
+
+struct FatRead;
+
+impl Command for FatRead {
+    fn name(&self) -> &'static str {
+        "fatcat"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatcat <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print a file of the FAT disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let path = argv.get(1).filter(|_| argv.len() == 2).ok_or(ShellError::Usage)?;
+
+        with_volume(sh, argv, io, |volume, io| {
+            let mut ends_line = true;
+            volume.read_file(path, &mut |bytes| {
+                for &b in bytes.iter().filter(|&&b| b != b'\r') {
+                    let c = match b {
+                        b'\n' | b'\t' | 0x20..=0x7E => b as char,
+                        _ => '.',
+                    };
+                    let _ = io.out.write_char(c);
+                    ends_line = b == b'\n';
+                }
+            })?;
+
+            // the prompt must not follow the last line
+            if !ends_line {
+                let _ = writeln!(io.out);
+            }
+            Ok(())
+        })
+    }
+}
+
+struct FatPut;
+
+impl Command for FatPut {
+    fn name(&self) -> &'static str {
+        "fatput"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatput <path> [fat path]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Copy a file, or the piped input, to the FAT disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let now = rtc::now();
+
+        // `ls | fatput list.txt` stores the output of `ls`
+        if let (Some(input), Some(target), 2) = (io.input, argv.get(1), argv.len()) {
+            return with_volume(sh, argv, io, |volume, _| volume.write_file(target, input, now));
+        }
+
+        let (path, target) = match (argv.get(1), argv.get(2), argv.len()) {
+            (Some(path), None, 2) => (path, base_name(path)),
+            (Some(path), Some(target), 3) => (path, target),
+            _ => return Err(ShellError::Usage),
+        };
+
+        let file = match sh.fs.resolve(sh.current_directory, path)? {
+            Entry::File(index) => sh.fs.file(index),
+            Entry::Directory(_) => return Err(FsError::IsDirectory.into()),
+        };
+
+        let mut data = [0; MAX_SIZE_PUT];
+        let mut len = 0;
+        for line in Source::File(file.text()).lines() {
+            data[len..len + line.len()].copy_from_slice(line.as_bytes());
+            data[len + line.len()] = b'\n';
+            len += line.len() + 1;
+        }
+
+        with_volume(sh, argv, io, |volume, _| volume.write_file(target, &data[..len], now))
+    }
+}
+
+struct FatGet;
+
+impl Command for FatGet {
+    fn name(&self) -> &'static str {
+        "fatget"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatget <fat path> [path]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Copy a file from the FAT disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (source, path) = match (argv.get(1), argv.get(2), argv.len()) {
+            (Some(source), None, 2) => (source, base_name(source)),
+            (Some(source), Some(path), 3) => (source, path),
+            _ => return Err(ShellError::Usage),
+        };
+
+        // lines as the editor keeps them, longer ones are cut
+        let mut content = [b' '; FILE_SIZE];
+        let (mut line, mut column) = (0, 0);
+        with_volume(sh, argv, io, |volume, _| {
+            volume.read_file(source, &mut |bytes| {
+                for &b in bytes.iter().filter(|&&b| b != b'\r') {
+                    match b {
+                        b'\n' => (line, column) = (line + 1, 0),
+                        _ if line < MAX_COUNT_LINES && column < LINE_LENGTH => {
+                            content[line * LINE_LENGTH + column] = b;
+                            column += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            })
+        })?;
+
+        let index = match sh.fs.resolve(sh.current_directory, path) {
+            Ok(Entry::File(index)) => index,
+            Ok(Entry::Directory(_)) => return Err(FsError::IsDirectory.into()),
+            Err(_) => {
+                let (directory, name) = sh.fs.resolve_parent(sh.current_directory, path)?;
+                sh.fs.create_file(directory, name)?
+            }
+        };
+
+        let file = sh.fs.file_mut(index);
+        file.content = content;
+        file.count_lines = (line + (column > 0) as usize).min(MAX_COUNT_LINES);
+        file.modified = rtc::now();
+        Ok(())
+    }
+}
+
+struct FatMakeDir;
+
+impl Command for FatMakeDir {
+    fn name(&self) -> &'static str {
+        "fatmkdir"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatmkdir <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Create a directory on the FAT disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let path = argv.get(1).filter(|_| argv.len() == 2).ok_or(ShellError::Usage)?;
+        with_volume(sh, argv, io, |volume, _| volume.create_dir(path, rtc::now()))
+    }
+}
+
+struct FatRemove;
+
+impl Command for FatRemove {
+    fn name(&self) -> &'static str {
+        "fatrm"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fatrm <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Delete a file or empty directory on the FAT disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let path = argv.get(1).filter(|_| argv.len() == 2).ok_or(ShellError::Usage)?;
+        with_volume(sh, argv, io, |volume, _| volume.remove(path))
+    }
+}
@@ -0,0 +1,300 @@
+use super::builtins::for_each_operand;
+use super::command::{Argv, Command, Io, ShellError, EXIT_FAILURE, EXIT_SUCCESS, MAX_COUNT_ARGS};
+use super::fs::{name_to_str, Entry, FsError, MAX_SIZE_DIRECTORY_NAME, ROOT_DIRECTORY};
+use super::{print_error, Shell};
+
+pub static COMMANDS: [&dyn Command; 4] = [&MoveFiles, &CopyFiles, &Rename, &Remove];
+
+/// Files and directories `rm` removes once the user has confirmed it.
+#[derive(Debug, Clone, Copy)]
+pub struct Removal {
+    entries: [Entry; MAX_COUNT_ARGS],
+    count: usize,
+    /// Exit status of the operands `rm` already failed on, which stays once
+    /// the rest is removed.
+    status: u8,
+}
+
+impl Removal {
+    fn new() -> Removal {
+        Removal {
+            entries: [Entry::File(0); MAX_COUNT_ARGS],
+            count: 0,
+            status: EXIT_SUCCESS,
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        if self.count < MAX_COUNT_ARGS {
+            self.entries[self.count] = entry;
+            self.count += 1;
+        }
+    }
+
+    fn entries(&self) -> &[Entry] {
+        &self.entries[..self.count]
+    }
+}
+
+impl Shell {
+    fn remove(&mut self, removal: &Removal) -> Result<(), ShellError> {
+        for &entry in removal.entries() {
+            // an earlier operand may have contained this one
+            if !self.fs.exists(entry) {
+                continue;
+            }
+
+            match entry {
+                Entry::File(index) => self.fs.delete_file(index),
+                Entry::Directory(index) => self.fs.delete_tree(index)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the `rm` waiting for confirmation with the line typed as
+    /// `answer`: `y` or `yes` removes everything, anything else nothing.
+    pub fn confirm_removal(&mut self, removal: Removal, answer: &[u8]) {
+        let answer = core::str::from_utf8(answer).unwrap_or("").trim_matches(|c| c == ' ' || c == '\0');
+
+        self.last_status = match answer {
+            "y" | "Y" | "yes" => match self.remove(&removal) {
+                Ok(()) => removal.status,
+                Err(error) => {
+                    print_error("rm", error);
+                    error.status()
+                }
+            },
+            _ => EXIT_FAILURE,
+        };
+    }
+}
+
+fn entry_name(sh: &Shell, entry: Entry) -> [u8; MAX_SIZE_DIRECTORY_NAME] {
+    match entry {
+        Entry::File(index) => sh.fs.file(index).name,
+        Entry::Directory(index) => sh.fs.directory(index).name,
+    }
+}
+
+/// Directory and new name the sources go to: into `target` keeping their
+/// names if it is a directory, otherwise `target` is the new path. With
+/// several sources `target` has to be a directory.
+fn destination<'a>(sh: &Shell, target: &'a str, several: bool) -> Result<(usize, Option<&'a str>), FsError> {
+    match sh.fs.resolve_directory(sh.current_directory, target) {
+        Ok(directory) => Ok((directory, None)),
+        Err(_) if several => Err(FsError::NotDirectory),
+        Err(_) => {
+            let (directory, name) = sh.fs.resolve_parent(sh.current_directory, target)?;
+            Ok((directory, Some(name)))
+        }
+    }
+}
+
+/// Splits the operands into the sources and the target, running `f` for
+/// every source with the directory and name it should get.
+fn transfer(
+    sh: &mut Shell,
+    argv: &Argv,
+    first_operand: usize,
+    io: &mut Io,
+    f: impl Fn(&mut Shell, Entry, usize, &str) -> Result<(), ShellError>,
+) -> Result<(), ShellError> {
+    let count = argv.len().saturating_sub(first_operand);
+    if count < 2 {
+        return Err(ShellError::Usage);
+    }
+
+    let target = argv.get(argv.len() - 1).ok_or(ShellError::Usage)?;
+    let (directory, new_name) = destination(sh, target, count > 2)?;
+
+    let mut sources = Argv::new();
+    for arg in argv.iter().take(argv.len() - 1) {
+        sources.push(arg.as_bytes());
+    }
+
+    for_each_operand(sh, &sources, first_operand, io, |sh, path, _io| {
+        let entry = sh.fs.resolve(sh.current_directory, path)?;
+        let name = entry_name(sh, entry);
+
+        f(sh, entry, directory, new_name.unwrap_or(name_to_str(&name)))
+    })
+}
+
+struct MoveFiles;
+
+impl Command for MoveFiles {
+    fn name(&self) -> &'static str {
+        "mv"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mv <path...> <target>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Move or rename files and directories"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        transfer(sh, argv, 1, io, |sh, entry, directory, name| {
+            match entry {
+                Entry::File(index) => sh.fs.move_file(index, directory, name)?,
+                Entry::Directory(index) => sh.fs.move_directory(index, directory, name)?,
+            }
+            Ok(())
+        })
+    }
+}
+
+struct CopyFiles;
+
+impl Command for CopyFiles {
+    fn name(&self) -> &'static str {
+        "cp"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cp [-r] <path...> <target>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Copy files, -r copies directories too"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let recursive = argv.get(1) == Some("-r");
+        let first_operand = if recursive { 2 } else { 1 };
+
+        transfer(sh, argv, first_operand, io, |sh, entry, directory, name| {
+            match entry {
+                Entry::File(index) => sh.fs.copy_file(index, directory, name)?,
+                Entry::Directory(index) if recursive => sh.fs.copy_directory(index, directory, name)?,
+                Entry::Directory(_) => return Err(FsError::IsDirectory.into()),
+            };
+            Ok(())
+        })
+    }
+}
+
+struct Rename;
+
+impl Command for Rename {
+    fn name(&self) -> &'static str {
+        "rename"
+    }
+
+    fn usage(&self) -> &'static str {
+        "rename <path> <name>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Rename a file or directory in place"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        let (path, name) = match (argv.get(1), argv.get(2), argv.len()) {
+            (Some(path), Some(name), 3) => (path, name),
+            _ => return Err(ShellError::Usage),
+        };
+
+        if name.contains('/') {
+            return Err(ShellError::Usage);
+        }
+
+        match sh.fs.resolve(sh.current_directory, path)? {
+            Entry::File(index) => {
+                let directory = sh.fs.file(index).folder_index;
+                sh.fs.move_file(index, directory, name)?;
+            }
+            Entry::Directory(index) => {
+                let parent = sh.fs.directory(index).parent_index;
+                sh.fs.move_directory(index, parent, name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Remove;
+
+impl Command for Remove {
+    fn name(&self) -> &'static str {
+        "rm"
+    }
+
+    fn usage(&self) -> &'static str {
+        "rm [-r] [-f] <path...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove files, -r removes directories too"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (mut recursive, mut force) = (false, false);
+        let mut first_operand = 1;
+
+        for arg in argv.args() {
+            match arg {
+                "-r" | "-R" => recursive = true,
+                "-f" => force = true,
+                "-rf" | "-fr" => (recursive, force) = (true, true),
+                _ => break,
+            }
+            first_operand += 1;
+        }
+
+        if argv.len() == first_operand {
+            return Err(ShellError::Usage);
+        }
+
+        let mut removal = Removal::new();
+        let (mut directories, mut files) = (0, 0);
+
+        let result = for_each_operand(sh, argv, first_operand, io, |sh, path, _io| {
+            let entry = sh.fs.resolve(sh.current_directory, path)?;
+
+            match entry {
+                Entry::File(_) => files += 1,
+                Entry::Directory(_) if !recursive => return Err(FsError::IsDirectory.into()),
+                Entry::Directory(ROOT_DIRECTORY) => return Err(FsError::IsRoot.into()),
+                Entry::Directory(index) if sh.fs.is_inside(sh.current_directory, index) => {
+                    return Err(FsError::InUse.into())
+                }
+                Entry::Directory(index) => {
+                    let (inner_directories, inner_files) = sh.fs.count_tree(index);
+                    directories += inner_directories + 1;
+                    files += inner_files;
+                }
+            }
+
+            removal.push(entry);
+            Ok(())
+        });
+
+        if directories == 0 || force {
+            sh.remove(&removal)?;
+            return result;
+        }
+
+        // the answer is read from the keyboard, so the question must go to
+        // the screen as well
+        if sh.script_depth > 0 || !io.is_console {
+            return Err(ShellError::NotInteractive);
+        }
+
+        // the shell hands the next line typed to `confirm_removal` and runs
+        // the commands after `rm` then
+        let _ = write!(
+            io.out,
+            "rm: remove {} directories and {} files? [y/N] ",
+            directories, files
+        );
+        if let Err(error) = result {
+            removal.status = error.status();
+        }
+        sh.pending_removal = Some(removal);
+        result
+    }
+}
@@ -0,0 +1,809 @@
+use core::fmt;
+
+use crate::rtc::{self, DateTime};
+
+pub const MAX_COUNT_CHILDREN_DIRECTORIES: usize = 20;
+pub const MAX_COUNT_DIRECTORIES: usize = 100;
+pub const MAX_COUNT_FILES_IN_FOLDER: usize = 5;
+pub const MAX_COUNT_FILES: usize = 10;
+pub const DELETED_INDEX_DIRECTORY: usize = MAX_COUNT_DIRECTORIES + 1;
+pub const DELETED_INDEX_FILE: usize = MAX_COUNT_FILES + 1;
+pub const MAX_SIZE_DIRECTORY_NAME: usize = 10;
+pub const ROOT_DIRECTORY: usize = 0;
+
+pub const LINE_LENGTH: usize = 80;
+pub const MAX_COUNT_LINES: usize = 25;
+pub const FILE_SIZE: usize = LINE_LENGTH * MAX_COUNT_LINES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    AlreadyExists,
+    EmptyName,
+    NameTooLong,
+    NoSpace,
+    DirectoryNotEmpty,
+    IsRoot,
+    /// The directory is the current directory of the shell.
+    InUse,
+    IsDirectory,
+    NotDirectory,
+    /// A directory would be moved or copied into itself.
+    IntoItself,
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            FsError::NotFound => "No such file or directory",
+            FsError::AlreadyExists => "File or directory already exists",
+            FsError::EmptyName => "Specify a name",
+            FsError::NameTooLong => "The maximum size of a name is 10 characters",
+            FsError::NoSpace => "There is not a free space",
+            FsError::DirectoryNotEmpty => "Directory is not empty",
+            FsError::IsRoot => "Root directory can not be changed",
+            FsError::InUse => "Directory is in use",
+            FsError::IsDirectory => "Is a directory",
+            FsError::NotDirectory => "Not a directory",
+            FsError::IntoItself => "Can not move or copy a directory into itself",
+        };
+        f.write_str(message)
+    }
+}
+
+/// A link between the tables that contradicts another one, found by
+/// `FileSystem::check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    /// The parent of a directory is missing or lies below it, or the
+    /// directory of a file is missing.
+    BadParent(Entry),
+    /// A directory lists a directory that has another parent or none.
+    StaleChild { directory: usize, child: usize },
+    /// A directory lists a file that lies elsewhere or does not exist.
+    StaleFile { directory: usize, file: usize },
+    /// A directory or file is not listed by the directory it lies in.
+    Unlisted(Entry),
+    /// The directory an unlisted entry lies in has no room left for it.
+    NoRoom(Entry),
+    WrongChildCount { directory: usize, recorded: usize, found: usize },
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Entry::File(index) => write!(f, "file {}", index),
+            Entry::Directory(index) => write!(f, "directory {}", index),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::BadParent(entry @ Entry::Directory(_)) => write!(f, "{}: Parent is missing or below it", entry),
+            Problem::BadParent(entry) => write!(f, "{}: Directory is missing", entry),
+            Problem::StaleChild { directory, child } => {
+                write!(f, "directory {}: Lists directory {}, which is not its child", directory, child)
+            }
+            Problem::StaleFile { directory, file } => {
+                write!(f, "directory {}: Lists file {}, which is not in it", directory, file)
+            }
+            Problem::Unlisted(entry) => write!(f, "{}: Not listed by its directory", entry),
+            Problem::NoRoom(entry) => write!(f, "{}: No room in its directory, not repaired", entry),
+            Problem::WrongChildCount { directory, recorded, found } => {
+                write!(f, "directory {}: child_count is {}, found {} children", directory, recorded, found)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct File {
+    pub index: usize,
+    pub name: [u8; MAX_SIZE_DIRECTORY_NAME],
+    pub count_lines: usize,
+    pub folder_index: usize,
+    pub content: [u8; FILE_SIZE],
+    pub created: DateTime,
+    pub modified: DateTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Directory {
+    pub index: usize,
+    pub name: [u8; MAX_SIZE_DIRECTORY_NAME],
+    pub parent_index: usize,
+    pub child_count: usize,
+    pub child_indexes: [usize; MAX_COUNT_CHILDREN_DIRECTORIES],
+    pub files_indexes: [usize; MAX_COUNT_FILES_IN_FOLDER],
+    pub created: DateTime,
+    /// Last time an entry was added to or removed from the directory.
+    pub modified: DateTime,
+}
+
+/// A file or a directory a path leads to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    File(usize),
+    Directory(usize),
+}
+
+pub struct DirectoryList {
+    pub directories: [Directory; MAX_COUNT_DIRECTORIES],
+}
+
+pub struct FileList {
+    pub files: [File; MAX_COUNT_FILES],
+}
+
+/// Directories and files of the shell, kept in fixed-size tables and linked
+/// to each other by their indexes.
+pub struct FileSystem {
+    pub directory_list: DirectoryList,
+    pub files_list: FileList,
+}
+
+pub fn name_to_str(name: &[u8; MAX_SIZE_DIRECTORY_NAME]) -> &str {
+    core::str::from_utf8(name)
+        .unwrap_or("")
+        .trim_matches('\0')
+}
+
+pub fn name_from_str(name: &str) -> Result<[u8; MAX_SIZE_DIRECTORY_NAME], FsError> {
+    if name.is_empty() {
+        return Err(FsError::EmptyName);
+    }
+
+    if name.len() > MAX_SIZE_DIRECTORY_NAME {
+        return Err(FsError::NameTooLong);
+    }
+
+    let mut result = [b'\0'; MAX_SIZE_DIRECTORY_NAME];
+    result[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(result)
+}
+
+impl File {
+    const fn empty() -> File {
+        File {
+            index: DELETED_INDEX_FILE,
+            name: [b'\0'; MAX_SIZE_DIRECTORY_NAME],
+            count_lines: 0,
+            folder_index: DELETED_INDEX_DIRECTORY,
+            content: [b' '; FILE_SIZE],
+            created: DateTime::UNKNOWN,
+            modified: DateTime::UNKNOWN,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        name_to_str(&self.name)
+    }
+
+    /// The part of `content` covered by `count_lines`.
+    pub fn text(&self) -> &[u8] {
+        &self.content[..(self.count_lines * LINE_LENGTH).min(FILE_SIZE)]
+    }
+
+    /// Replaces the content with `text`, every line ending with `\n`. Lines
+    /// wider than the screen and the ones behind the last line a file holds
+    /// are cut off.
+    pub fn set_text(&mut self, text: &[u8]) {
+        self.content = [b' '; FILE_SIZE];
+        self.count_lines = 0;
+        if text.is_empty() {
+            return;
+        }
+
+        let lines = text.strip_suffix(b"\n").unwrap_or(text).split(|&b| b == b'\n');
+        for (number, line) in lines.take(MAX_COUNT_LINES).enumerate() {
+            let line = &line[..line.len().min(LINE_LENGTH)];
+            self.content[number * LINE_LENGTH..number * LINE_LENGTH + line.len()].copy_from_slice(line);
+            self.count_lines = number + 1;
+        }
+    }
+
+    /// Size of the text without the blanks padding every line to the width
+    /// of the screen, counting one byte for the end of each line.
+    pub fn size(&self) -> usize {
+        self.text()
+            .chunks(LINE_LENGTH)
+            .map(|line| {
+                let len = line.iter().rposition(|&b| b != b' ' && b != b'\0').map_or(0, |end| end + 1);
+                len + 1
+            })
+            .sum()
+    }
+}
+
+impl Directory {
+    const fn empty() -> Directory {
+        Directory {
+            index: DELETED_INDEX_DIRECTORY,
+            name: [b' '; MAX_SIZE_DIRECTORY_NAME],
+            parent_index: DELETED_INDEX_DIRECTORY,
+            child_count: 0,
+            child_indexes: [DELETED_INDEX_DIRECTORY; MAX_COUNT_CHILDREN_DIRECTORIES],
+            files_indexes: [DELETED_INDEX_FILE; MAX_COUNT_FILES_IN_FOLDER],
+            created: DateTime::UNKNOWN,
+            modified: DateTime::UNKNOWN,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        name_to_str(&self.name)
+    }
+
+    /// Indexes of the child directories.
+    pub fn children(&self) -> impl Iterator<Item = usize> + '_ {
+        self.child_indexes
+            .iter()
+            .copied()
+            .filter(|&index| index != DELETED_INDEX_DIRECTORY)
+    }
+
+    /// Indexes of the files in this directory.
+    pub fn files(&self) -> impl Iterator<Item = usize> + '_ {
+        self.files_indexes
+            .iter()
+            .copied()
+            .filter(|&index| index != DELETED_INDEX_FILE)
+    }
+}
+
+impl FileSystem {
+    pub fn new() -> FileSystem {
+        let now = rtc::now();
+        let mut fs = FileSystem {
+            directory_list: DirectoryList {
+                directories: [Directory::empty(); MAX_COUNT_DIRECTORIES],
+            },
+            files_list: FileList {
+                files: [File::empty(); MAX_COUNT_FILES],
+            },
+        };
+
+        fs.directory_list.directories[ROOT_DIRECTORY] = Directory {
+            index: ROOT_DIRECTORY,
+            name: [
+                b'r', b'o', b'o', b't', b'\0', b'\0', b'\0', b'\0', b'\0', b'\0',
+            ],
+            parent_index: ROOT_DIRECTORY,
+            created: now,
+            modified: now,
+            ..Directory::empty()
+        };
+
+        fs
+    }
+
+    /// Deletes everything below the root directory.
+    pub fn clear(&mut self) {
+        for directory in self.directory_list.directories[ROOT_DIRECTORY + 1..].iter_mut() {
+            *directory = Directory::empty();
+        }
+        for file in self.files_list.files.iter_mut() {
+            *file = File::empty();
+        }
+
+        let root = &mut self.directory_list.directories[ROOT_DIRECTORY];
+        root.child_count = 0;
+        root.child_indexes = [DELETED_INDEX_DIRECTORY; MAX_COUNT_CHILDREN_DIRECTORIES];
+        root.files_indexes = [DELETED_INDEX_FILE; MAX_COUNT_FILES_IN_FOLDER];
+    }
+
+    pub fn directory(&self, index: usize) -> &Directory {
+        &self.directory_list.directories[index]
+    }
+
+    pub fn directory_mut(&mut self, index: usize) -> &mut Directory {
+        &mut self.directory_list.directories[index]
+    }
+
+    pub fn file(&self, index: usize) -> &File {
+        &self.files_list.files[index]
+    }
+
+    pub fn file_mut(&mut self, index: usize) -> &mut File {
+        &mut self.files_list.files[index]
+    }
+
+    /// Records that an entry of `directory` was added or removed.
+    fn touch_directory(&mut self, directory: usize) {
+        self.directory_list.directories[directory].modified = rtc::now();
+    }
+
+    /// Index of the file called `name` in `directory`.
+    pub fn find_file(&self, directory: usize, name: &str) -> Result<usize, FsError> {
+        self.directory(directory)
+            .files()
+            .find(|&index| self.file(index).name() == name)
+            .ok_or(FsError::NotFound)
+    }
+
+    /// Index of the child of `directory` called `name`.
+    pub fn find_directory(&self, directory: usize, name: &str) -> Result<usize, FsError> {
+        self.directory(directory)
+            .children()
+            .find(|&index| self.directory(index).name() == name)
+            .ok_or(FsError::NotFound)
+    }
+
+    /// Directory `path` leads to. Absolute paths start with the name of the
+    /// root directory, e.g. `/root/src`, relative ones start at `from`.
+    pub fn resolve_directory(&self, from: usize, path: &str) -> Result<usize, FsError> {
+        let (mut directory, components) = match path.strip_prefix('/') {
+            Some(rest) => {
+                let mut components = rest.split('/');
+                match components.next() {
+                    Some(name) if name.is_empty() || name == self.directory(ROOT_DIRECTORY).name() => {}
+                    _ => return Err(FsError::NotFound),
+                }
+                (ROOT_DIRECTORY, components)
+            }
+            None => (from, path.split('/')),
+        };
+
+        for component in components {
+            directory = match component {
+                "" | "." => directory,
+                ".." => self.directory(directory).parent_index,
+                name => self.find_directory(directory, name)?,
+            };
+        }
+
+        Ok(directory)
+    }
+
+    /// Splits `path` into the directory its last component lives in and
+    /// the name of that component.
+    pub fn resolve_parent<'a>(&self, from: usize, path: &'a str) -> Result<(usize, &'a str), FsError> {
+        let path = match path.trim_end_matches('/') {
+            "" if path.starts_with('/') => return Err(FsError::IsRoot),
+            path => path,
+        };
+
+        match path.rfind('/') {
+            Some(0) => Err(FsError::IsRoot),
+            Some(slash) => Ok((self.resolve_directory(from, &path[..slash])?, &path[slash + 1..])),
+            None => Ok((from, path)),
+        }
+    }
+
+    pub fn resolve_file(&self, from: usize, path: &str) -> Result<usize, FsError> {
+        let (directory, name) = self.resolve_parent(from, path)?;
+        self.find_file(directory, name)
+    }
+
+    /// File or directory `path` leads to, files first.
+    pub fn resolve(&self, from: usize, path: &str) -> Result<Entry, FsError> {
+        match self.resolve_file(from, path) {
+            Ok(index) => Ok(Entry::File(index)),
+            Err(_) => self.resolve_directory(from, path).map(Entry::Directory),
+        }
+    }
+
+    /// Whether `directory` is `ancestor` itself or lies somewhere below it.
+    pub fn is_inside(&self, directory: usize, ancestor: usize) -> bool {
+        let mut current = directory;
+
+        loop {
+            if current == ancestor {
+                return true;
+            }
+            if current == ROOT_DIRECTORY {
+                return false;
+            }
+            current = self.directory(current).parent_index;
+        }
+    }
+
+    fn check_name_is_free(&self, directory: usize, name: &str) -> Result<(), FsError> {
+        if self.find_file(directory, name).is_ok() || self.find_directory(directory, name).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    pub fn create_directory(&mut self, parent: usize, name: &str) -> Result<usize, FsError> {
+        let name = name_from_str(name)?;
+        self.check_name_is_free(parent, name_to_str(&name))?;
+
+        let dir_index = (0..MAX_COUNT_DIRECTORIES)
+            .find(|&i| self.directory(i).index == DELETED_INDEX_DIRECTORY)
+            .ok_or(FsError::NoSpace)?;
+        let free_index = self
+            .directory(parent)
+            .child_indexes
+            .iter()
+            .position(|&index| index == DELETED_INDEX_DIRECTORY)
+            .ok_or(FsError::NoSpace)?;
+
+        let now = rtc::now();
+        self.directory_list.directories[dir_index] = Directory {
+            index: dir_index,
+            name,
+            parent_index: parent,
+            child_count: 0,
+            created: now,
+            modified: now,
+            ..Directory::empty()
+        };
+
+        let parent = &mut self.directory_list.directories[parent];
+        parent.child_indexes[free_index] = dir_index;
+        parent.child_count += 1;
+        parent.modified = now;
+
+        Ok(dir_index)
+    }
+
+    /// Deletes an empty directory.
+    pub fn delete_directory(&mut self, index: usize) -> Result<(), FsError> {
+        if index == ROOT_DIRECTORY {
+            return Err(FsError::IsRoot);
+        }
+
+        let directory = *self.directory(index);
+
+        if directory.child_count > 0 || directory.files().next().is_some() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        let parent = &mut self.directory_list.directories[directory.parent_index];
+        for child_index in parent.child_indexes.iter_mut() {
+            if *child_index == index {
+                *child_index = DELETED_INDEX_DIRECTORY;
+            }
+        }
+        parent.child_count -= 1;
+        parent.modified = rtc::now();
+
+        self.directory_list.directories[index] = Directory::empty();
+
+        Ok(())
+    }
+
+    /// How many directories and files there are below `index`.
+    pub fn count_tree(&self, index: usize) -> (usize, usize) {
+        let directory = self.directory(index);
+        let mut counts = (0, directory.files().count());
+
+        for child_index in directory.children() {
+            let (directories, files) = self.count_tree(child_index);
+            counts.0 += directories + 1;
+            counts.1 += files;
+        }
+
+        counts
+    }
+
+    /// Deletes a directory together with all its files and subdirectories.
+    pub fn delete_tree(&mut self, index: usize) -> Result<(), FsError> {
+        if index == ROOT_DIRECTORY {
+            return Err(FsError::IsRoot);
+        }
+
+        let directory = *self.directory(index);
+
+        for child_index in directory.children() {
+            self.delete_tree(child_index)?;
+        }
+        for file_index in directory.files() {
+            self.delete_file(file_index);
+        }
+
+        self.delete_directory(index)
+    }
+
+    /// Whether `entry` still refers to a file or directory in use.
+    pub fn exists(&self, entry: Entry) -> bool {
+        match entry {
+            Entry::File(index) => self.file(index).index == index,
+            Entry::Directory(index) => self.directory(index).index == index,
+        }
+    }
+
+    pub fn create_file(&mut self, directory: usize, name: &str) -> Result<usize, FsError> {
+        let name = name_from_str(name)?;
+        self.check_name_is_free(directory, name_to_str(&name))?;
+
+        let file_index = (0..MAX_COUNT_FILES)
+            .find(|&i| self.file(i).index == DELETED_INDEX_FILE)
+            .ok_or(FsError::NoSpace)?;
+        let index_for_folder = self
+            .directory(directory)
+            .files_indexes
+            .iter()
+            .position(|&index| index == DELETED_INDEX_FILE)
+            .ok_or(FsError::NoSpace)?;
+
+        let now = rtc::now();
+        self.files_list.files[file_index] = File {
+            index: file_index,
+            name,
+            folder_index: directory,
+            created: now,
+            modified: now,
+            ..File::empty()
+        };
+        self.directory_list.directories[directory].files_indexes[index_for_folder] = file_index;
+        self.touch_directory(directory);
+
+        Ok(file_index)
+    }
+
+    pub fn delete_file(&mut self, index: usize) {
+        let folder_index = self.file(index).folder_index;
+
+        for file_index in self.directory_list.directories[folder_index]
+            .files_indexes
+            .iter_mut()
+        {
+            if *file_index == index {
+                *file_index = DELETED_INDEX_FILE;
+            }
+        }
+
+        self.files_list.files[index] = File::empty();
+        self.touch_directory(folder_index);
+    }
+
+    /// Moves a file to `directory` under a new `name`.
+    pub fn move_file(&mut self, index: usize, directory: usize, name: &str) -> Result<(), FsError> {
+        let name = name_from_str(name)?;
+        self.check_name_is_free(directory, name_to_str(&name))?;
+
+        let old_directory = self.file(index).folder_index;
+        if old_directory != directory {
+            let free_index = self
+                .directory(directory)
+                .files_indexes
+                .iter()
+                .position(|&index| index == DELETED_INDEX_FILE)
+                .ok_or(FsError::NoSpace)?;
+
+            for file_index in self.directory_list.directories[old_directory]
+                .files_indexes
+                .iter_mut()
+            {
+                if *file_index == index {
+                    *file_index = DELETED_INDEX_FILE;
+                }
+            }
+            self.directory_list.directories[directory].files_indexes[free_index] = index;
+            self.touch_directory(old_directory);
+        }
+        self.touch_directory(directory);
+
+        let file = self.file_mut(index);
+        file.folder_index = directory;
+        file.name = name;
+        Ok(())
+    }
+
+    /// Moves a directory with everything in it below `parent` under a new
+    /// `name`.
+    pub fn move_directory(&mut self, index: usize, parent: usize, name: &str) -> Result<(), FsError> {
+        if index == ROOT_DIRECTORY {
+            return Err(FsError::IsRoot);
+        }
+
+        if self.is_inside(parent, index) {
+            return Err(FsError::IntoItself);
+        }
+
+        let name = name_from_str(name)?;
+        self.check_name_is_free(parent, name_to_str(&name))?;
+
+        let old_parent = self.directory(index).parent_index;
+        if old_parent != parent {
+            let free_index = self
+                .directory(parent)
+                .child_indexes
+                .iter()
+                .position(|&index| index == DELETED_INDEX_DIRECTORY)
+                .ok_or(FsError::NoSpace)?;
+
+            let old = &mut self.directory_list.directories[old_parent];
+            for child_index in old.child_indexes.iter_mut() {
+                if *child_index == index {
+                    *child_index = DELETED_INDEX_DIRECTORY;
+                }
+            }
+            old.child_count -= 1;
+
+            let new = &mut self.directory_list.directories[parent];
+            new.child_indexes[free_index] = index;
+            new.child_count += 1;
+            self.touch_directory(old_parent);
+        }
+        self.touch_directory(parent);
+
+        let directory = &mut self.directory_list.directories[index];
+        directory.parent_index = parent;
+        directory.name = name;
+        Ok(())
+    }
+
+    /// Copies a file to `directory` as `name` and returns the index of the
+    /// copy.
+    pub fn copy_file(&mut self, index: usize, directory: usize, name: &str) -> Result<usize, FsError> {
+        let copy_index = self.create_file(directory, name)?;
+        let source = *self.file(index);
+
+        let copy = self.file_mut(copy_index);
+        copy.count_lines = source.count_lines;
+        copy.content = source.content;
+
+        Ok(copy_index)
+    }
+
+    /// Copies a directory with all its files and subdirectories below
+    /// `parent` as `name`. When the tables run out of space the copy made so
+    /// far is kept.
+    pub fn copy_directory(&mut self, index: usize, parent: usize, name: &str) -> Result<usize, FsError> {
+        if self.is_inside(parent, index) {
+            return Err(FsError::IntoItself);
+        }
+
+        let copy_index = self.create_directory(parent, name)?;
+        let source = *self.directory(index);
+
+        for file_index in source.files() {
+            let name = self.file(file_index).name;
+            self.copy_file(file_index, copy_index, name_to_str(&name))?;
+        }
+
+        for child_index in source.children() {
+            let name = self.directory(child_index).name;
+            self.copy_directory(child_index, copy_index, name_to_str(&name))?;
+        }
+
+        Ok(copy_index)
+    }
+
+    /// Whether `index` is a directory in use.
+    fn is_directory(&self, index: usize) -> bool {
+        index < MAX_COUNT_DIRECTORIES && self.exists(Entry::Directory(index))
+    }
+
+    /// Whether the parents of `index` lead to the root directory.
+    fn reaches_root(&self, index: usize) -> bool {
+        let mut current = index;
+
+        // a longer way would have to pass a directory twice
+        for _ in 0..MAX_COUNT_DIRECTORIES {
+            if current == ROOT_DIRECTORY {
+                return true;
+            }
+            let parent = self.directory(current).parent_index;
+            if parent == current || !self.is_directory(parent) {
+                return false;
+            }
+            current = parent;
+        }
+        false
+    }
+
+    /// Looks for links between the tables that contradict each other and
+    /// hands every problem to `report`. With `repair` the lists of every
+    /// directory are made to follow the parent of each directory and the
+    /// directory of each file, entries whose parent is broken are moved to
+    /// the root directory first.
+    pub fn check(&mut self, repair: bool, report: &mut dyn FnMut(Problem)) {
+        let root = &mut self.directory_list.directories[ROOT_DIRECTORY];
+        if root.index != ROOT_DIRECTORY || root.parent_index != ROOT_DIRECTORY {
+            report(Problem::BadParent(Entry::Directory(ROOT_DIRECTORY)));
+            if repair {
+                root.index = ROOT_DIRECTORY;
+                root.parent_index = ROOT_DIRECTORY;
+            }
+        }
+
+        for index in ROOT_DIRECTORY + 1..MAX_COUNT_DIRECTORIES {
+            if self.is_directory(index) && !self.reaches_root(index) {
+                report(Problem::BadParent(Entry::Directory(index)));
+                if repair {
+                    self.directory_list.directories[index].parent_index = ROOT_DIRECTORY;
+                }
+            }
+        }
+        for index in 0..MAX_COUNT_FILES {
+            if self.exists(Entry::File(index)) && !self.is_directory(self.file(index).folder_index) {
+                report(Problem::BadParent(Entry::File(index)));
+                if repair {
+                    self.files_list.files[index].folder_index = ROOT_DIRECTORY;
+                }
+            }
+        }
+
+        for directory in 0..MAX_COUNT_DIRECTORIES {
+            if !self.is_directory(directory) {
+                continue;
+            }
+
+            for slot in 0..MAX_COUNT_CHILDREN_DIRECTORIES {
+                let child = self.directory(directory).child_indexes[slot];
+                let is_stale = child != DELETED_INDEX_DIRECTORY
+                    && (child == ROOT_DIRECTORY
+                        || !self.is_directory(child)
+                        || self.directory(child).parent_index != directory
+                        || self.directory(directory).child_indexes[..slot].contains(&child));
+
+                if is_stale {
+                    report(Problem::StaleChild { directory, child });
+                    if repair {
+                        self.directory_list.directories[directory].child_indexes[slot] = DELETED_INDEX_DIRECTORY;
+                    }
+                }
+            }
+
+            for slot in 0..MAX_COUNT_FILES_IN_FOLDER {
+                let file = self.directory(directory).files_indexes[slot];
+                let is_stale = file != DELETED_INDEX_FILE
+                    && (file >= MAX_COUNT_FILES
+                        || !self.exists(Entry::File(file))
+                        || self.file(file).folder_index != directory
+                        || self.directory(directory).files_indexes[..slot].contains(&file));
+
+                if is_stale {
+                    report(Problem::StaleFile { directory, file });
+                    if repair {
+                        self.directory_list.directories[directory].files_indexes[slot] = DELETED_INDEX_FILE;
+                    }
+                }
+            }
+        }
+
+        for index in ROOT_DIRECTORY + 1..MAX_COUNT_DIRECTORIES {
+            let parent = self.directory(index).parent_index;
+            if !self.is_directory(index)
+                || !self.is_directory(parent)
+                || self.directory(parent).child_indexes.contains(&index)
+            {
+                continue;
+            }
+
+            report(Problem::Unlisted(Entry::Directory(index)));
+            if repair {
+                let children = &mut self.directory_list.directories[parent].child_indexes;
+                match children.iter().position(|&child| child == DELETED_INDEX_DIRECTORY) {
+                    Some(slot) => children[slot] = index,
+                    None => report(Problem::NoRoom(Entry::Directory(index))),
+                }
+            }
+        }
+        for index in 0..MAX_COUNT_FILES {
+            let directory = self.file(index).folder_index;
+            if !self.exists(Entry::File(index))
+                || !self.is_directory(directory)
+                || self.directory(directory).files_indexes.contains(&index)
+            {
+                continue;
+            }
+
+            report(Problem::Unlisted(Entry::File(index)));
+            if repair {
+                let files = &mut self.directory_list.directories[directory].files_indexes;
+                match files.iter().position(|&file| file == DELETED_INDEX_FILE) {
+                    Some(slot) => files[slot] = index,
+                    None => report(Problem::NoRoom(Entry::File(index))),
+                }
+            }
+        }
+
+        for directory in 0..MAX_COUNT_DIRECTORIES {
+            let recorded = self.directory(directory).child_count;
+            let found = self.directory(directory).children().count();
+            if self.is_directory(directory) && recorded != found {
+                report(Problem::WrongChildCount { directory, recorded, found });
+                if repair {
+                    self.directory_list.directories[directory].child_count = found;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::Problem;
+use super::Shell;
+
+pub static COMMANDS: [&dyn Command; 1] = [&Fsck];
+
+struct Fsck;
+
+impl Command for Fsck {
+    fn name(&self) -> &'static str {
+        "fsck"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fsck [-n]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Check the directory tables and repair them, -n only checks"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let repair = match (argv.get(1), argv.len()) {
+            (None, 1) => true,
+            (Some("-n"), 2) => false,
+            _ => return Err(ShellError::Usage),
+        };
+
+        let mut count = 0;
+        sh.fs.check(repair, &mut |problem| {
+            let _ = writeln!(io.out, "{}", problem);
+            if !matches!(problem, Problem::NoRoom(_)) {
+                count += 1;
+            }
+        });
+
+        let _ = match (count, repair, sh.mounted) {
+            (0, _, _) => writeln!(io.out, "No problems found"),
+            (_, false, _) => writeln!(io.out, "{} problems found", count),
+            (_, true, Some(_)) => writeln!(io.out, "{} problems repaired, sync saves the repairs", count),
+            (_, true, None) => writeln!(io.out, "{} problems repaired", count),
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use core::fmt::Write;
+
+use super::command::{Argv, Buffer, ShellError};
+use super::fs::{name_to_str, MAX_COUNT_CHILDREN_DIRECTORIES, MAX_COUNT_FILES_IN_FOLDER, MAX_SIZE_DIRECTORY_NAME};
+use super::parser::ParseError;
+use super::{Shell, MAX_SIZE_PATH};
+
+const MAX_COUNT_MATCHES: usize = MAX_COUNT_CHILDREN_DIRECTORIES + MAX_COUNT_FILES_IN_FOLDER;
+
+/// Matches `[abc]`, `[a-z]` or `[!abc]` at the start of `pattern` against
+/// `symbol`. Returns whether it matched and the length of the class, or
+/// `None` if the `[` is not closed and so stands for itself.
+fn match_class(pattern: &[u8], symbol: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some(b'!') | Some(b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+
+    loop {
+        let low = *pattern.get(i)?;
+        if low == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some(b'-'), Some(&high)) if high != b']' => {
+                matched |= low <= symbol && symbol <= high;
+                i += 3;
+            }
+            _ => {
+                matched |= low == symbol;
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`: `*` stands for any run of characters,
+/// `?` for exactly one and `[...]` for one of the characters listed.
+pub fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(b'*') => (0..=name.len()).any(|skip| matches(&pattern[1..], &name[skip..])),
+        Some(b'?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some(b'[') => {
+            let symbol = match name.first() {
+                Some(&symbol) => symbol,
+                None => return false,
+            };
+            match match_class(pattern, symbol) {
+                Some((matched, len)) => matched && matches(&pattern[len..], &name[1..]),
+                None => symbol == b'[' && matches(&pattern[1..], &name[1..]),
+            }
+        }
+        Some(&symbol) => name.first() == Some(&symbol) && matches(&pattern[1..], &name[1..]),
+    }
+}
+
+impl Shell {
+    /// Replaces every argument marked as a pattern with the sorted names of
+    /// the files and directories it matches. Only the last component of a
+    /// path may have wildcards; a pattern that matches nothing is kept as
+    /// it is, so that the command can report the missing name.
+    pub fn expand_globs(&self, argv: &Argv) -> Result<Argv, ShellError> {
+        let mut expanded = Argv::new();
+        let too_many = ShellError::Parse(ParseError::TooManyArguments);
+
+        for (i, arg) in argv.iter().enumerate() {
+            let mut names = [[0u8; MAX_SIZE_DIRECTORY_NAME]; MAX_COUNT_MATCHES];
+            let count = match argv.is_pattern(i) && i > 0 {
+                true => self.glob(arg, &mut names),
+                false => 0,
+            };
+
+            if count == 0 {
+                if !expanded.push(arg.as_bytes()) {
+                    return Err(too_many);
+                }
+                continue;
+            }
+
+            let prefix = &arg[..arg.rfind('/').map_or(0, |slash| slash + 1)];
+            for name in names[..count].iter() {
+                let mut path = Buffer::<MAX_SIZE_PATH>::new();
+                let _ = write!(path, "{}{}", prefix, name_to_str(name));
+
+                if !expanded.push(path.as_bytes()) {
+                    return Err(too_many);
+                }
+            }
+        }
+
+        Ok(expanded)
+    }
+
+    /// Collects the names matching `path` into `names`, sorted, and returns
+    /// how many there are. Names starting with `.` only match a pattern that
+    /// starts with `.` too.
+    fn glob(&self, path: &str, names: &mut [[u8; MAX_SIZE_DIRECTORY_NAME]; MAX_COUNT_MATCHES]) -> usize {
+        let (directory, pattern) = match path.rfind('/') {
+            Some(0) => (self.fs.resolve_directory(self.current_directory, "/"), &path[1..]),
+            Some(slash) => (self.fs.resolve_directory(self.current_directory, &path[..slash]), &path[slash + 1..]),
+            None => (Ok(self.current_directory), path),
+        };
+
+        let directory = match directory {
+            Ok(directory) => self.fs.directory(directory),
+            Err(_) => return 0,
+        };
+
+        let entries = directory
+            .children()
+            .map(|index| self.fs.directory(index).name)
+            .chain(directory.files().map(|index| self.fs.file(index).name));
+
+        let mut count = 0;
+        for name in entries {
+            let text = name_to_str(&name).as_bytes();
+            let hidden = text.first() == Some(&b'.') && !pattern.starts_with('.');
+
+            if !hidden && matches(pattern.as_bytes(), text) && count < MAX_COUNT_MATCHES {
+                names[count] = name;
+                count += 1;
+            }
+        }
+
+        names[..count].sort_unstable();
+        count
+    }
+}
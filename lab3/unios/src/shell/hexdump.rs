@@ -0,0 +1,148 @@
+use x86_64::VirtAddr;
+
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::{Entry, FsError};
+use super::Shell;
+use crate::memory::{self, PAGE_SIZE};
+
+pub static COMMANDS: [&dyn Command; 1] = [&HexDump];
+
+const BYTES_PER_LINE: usize = 16;
+const DEFAULT_MEMORY_LENGTH: usize = 256;
+const MAX_MEMORY_LENGTH: usize = 4096;
+/// The first page is never mapped so that null pointers fault.
+const FIRST_MAPPED_ADDRESS: u64 = 0x1000;
+
+/// Reads `123` or `0x7b`.
+pub fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Prints `length` bytes starting at `start` as lines of an offset, the
+/// bytes in hex and the same bytes as text, `.` standing for those that
+/// can not be printed. `read` returns the byte at an offset.
+pub fn dump(start: u64, length: usize, read: impl Fn(u64) -> u8, io: &mut Io) {
+    // 64-bit addresses only leave room for half as many bytes on a line
+    let (offset_width, bytes_per_line) = match start + length as u64 <= u32::MAX as u64 {
+        true => (8, BYTES_PER_LINE),
+        false => (16, BYTES_PER_LINE / 2),
+    };
+
+    let mut line = [0u8; BYTES_PER_LINE];
+    let mut offset = 0;
+
+    while offset < length {
+        let count = bytes_per_line.min(length - offset);
+        for (i, byte) in line[..count].iter_mut().enumerate() {
+            *byte = read(start + (offset + i) as u64);
+        }
+
+        let _ = write!(io.out, "{:0width$x}  ", start + offset as u64, width = offset_width);
+        for i in 0..bytes_per_line {
+            let _ = match i < count {
+                true => write!(io.out, "{:02x} ", line[i]),
+                false => write!(io.out, "   "),
+            };
+            if i + 1 == BYTES_PER_LINE / 2 && bytes_per_line == BYTES_PER_LINE {
+                let _ = write!(io.out, " ");
+            }
+        }
+
+        let _ = write!(io.out, "|");
+        for &byte in &line[..count] {
+            let printable = if (0x20..0x7f).contains(&byte) { byte } else { b'.' };
+            let _ = write!(io.out, "{}", printable as char);
+        }
+        let _ = writeln!(io.out, "|");
+
+        offset += count;
+    }
+}
+
+struct HexDump;
+
+impl Command for HexDump {
+    fn name(&self) -> &'static str {
+        "hexdump"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["xxd"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "hexdump [-s <offset>] [-n <length>] [path | -m <address>]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show bytes in hex"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let mut skip = 0;
+        let mut length = None;
+        let mut address = None;
+        let mut path = None;
+        let mut i = 1;
+
+        while let Some(arg) = argv.get(i) {
+            let mut number = || {
+                i += 1;
+                argv.get(i).and_then(parse_number).ok_or(ShellError::Usage)
+            };
+
+            match arg {
+                "-s" => skip = number()? as usize,
+                "-n" => length = Some(number()? as usize),
+                "-m" => address = Some(number()?),
+                _ if path.is_none() => path = Some(arg),
+                _ => return Err(ShellError::Usage),
+            }
+            i += 1;
+        }
+
+        if let Some(address) = address {
+            if path.is_some() {
+                return Err(ShellError::Usage);
+            }
+
+            let length = length.unwrap_or(DEFAULT_MEMORY_LENGTH).min(MAX_MEMORY_LENGTH);
+            let start = address.checked_add(skip as u64).ok_or(ShellError::Usage)?;
+            let end = start.checked_add(length as u64).ok_or(ShellError::Usage)?;
+
+            // non-canonical addresses would raise a general protection fault
+            if start < FIRST_MAPPED_ADDRESS || VirtAddr::try_new(start).is_err() || VirtAddr::try_new(end).is_err() {
+                return Err(ShellError::Usage);
+            }
+            // there is no page fault handler, an unmapped page would bring
+            // the kernel down with a double fault
+            let first_page = start - start % PAGE_SIZE;
+            let mut pages = (first_page..end).step_by(PAGE_SIZE as usize);
+            if let Some(page) = pages.find(|&page| !memory::is_mapped(VirtAddr::new(page))) {
+                let _ = writeln!(io.out, "[Error] hexdump: {:#x}: Address is not mapped", page.max(start));
+                return Err(ShellError::Failed);
+            }
+
+            let read = |address: u64| unsafe { core::ptr::read_volatile(address as *const u8) };
+            dump(start, length, read, io);
+            return Ok(());
+        }
+
+        let bytes = match path {
+            Some(path) => match sh.fs.resolve(sh.current_directory, path)? {
+                Entry::File(index) => sh.fs.file(index).text(),
+                Entry::Directory(_) => return Err(FsError::IsDirectory.into()),
+            },
+            None => io.input.ok_or(ShellError::Usage)?,
+        };
+
+        let bytes = &bytes[skip.min(bytes.len())..];
+        let bytes = &bytes[..length.unwrap_or(bytes.len()).min(bytes.len())];
+
+        dump(skip as u64, bytes.len(), |offset| bytes[offset as usize - skip], io);
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use super::command::ShellError;
+use super::fs::ROOT_DIRECTORY;
+use super::Shell;
+use crate::println;
+use crate::tar::{Archive, TarEntry, TarKind};
+
+/// Files built into the kernel from the `initrd` directory, see `build.rs`.
+static INITRD: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initrd.tar"));
+
+impl Shell {
+    /// Unpacks the files built into the kernel below the root directory.
+    /// Files that already exist, e.g. from a mounted disk, are kept.
+    pub fn unpack_initrd(&mut self) {
+        for entry in Archive::new(INITRD) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    println!("[Error] initrd: {}", error);
+                    break;
+                }
+            };
+
+            if let Err(error) = self.unpack(&entry) {
+                println!("[Error] initrd: {}: {}", entry.path(), error);
+            }
+        }
+    }
+
+    fn unpack(&mut self, entry: &TarEntry) -> Result<(), ShellError> {
+        let path = entry.path().trim_start_matches("./").trim_end_matches('/');
+        let (parents, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." {
+            return Ok(());
+        }
+
+        // archives need not hold the directories of their files
+        let mut directory = ROOT_DIRECTORY;
+        for component in parents.split('/').filter(|&component| !component.is_empty() && component != ".") {
+            directory = match self.fs.find_directory(directory, component) {
+                Ok(index) => index,
+                Err(_) => self.fs.create_directory(directory, component)?,
+            };
+        }
+
+        match entry.kind {
+            TarKind::Directory if self.fs.find_directory(directory, name).is_err() => {
+                self.fs.create_directory(directory, name)?;
+            }
+            TarKind::File if self.fs.find_file(directory, name).is_err() => {
+                let index = self.fs.create_file(directory, name)?;
+                self.fs.file_mut(index).set_text(entry.data);
+            }
+            // links and devices have no place in the tree
+            _ => {}
+        }
+        Ok(())
+    }
+}
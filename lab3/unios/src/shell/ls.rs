@@ -0,0 +1,341 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::{
+    name_from_str, name_to_str, Entry, LINE_LENGTH, MAX_COUNT_CHILDREN_DIRECTORIES, MAX_COUNT_FILES_IN_FOLDER,
+    MAX_SIZE_DIRECTORY_NAME,
+};
+use super::Shell;
+use crate::rtc::DateTime;
+
+pub static COMMANDS: [&dyn Command; 1] = [&ListFiles];
+
+/// Every entry of a directory together with `.` and `..`.
+const MAX_COUNT_ENTRIES: usize = MAX_COUNT_CHILDREN_DIRECTORIES + MAX_COUNT_FILES_IN_FOLDER + 2;
+const COLUMN_GAP: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Name,
+    /// Newest first.
+    Time,
+    /// Largest first.
+    Size,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    long: bool,
+    all: bool,
+    sort: SortBy,
+    reverse: bool,
+    /// Show and sort by the time of creation rather than of the last change.
+    created: bool,
+}
+
+impl Options {
+    /// Reads flags such as `-l`, `-la` or `-tr`.
+    fn parse(&mut self, flags: &str) -> Result<(), ShellError> {
+        for flag in flags.bytes().skip(1) {
+            match flag {
+                b'l' => self.long = true,
+                b'a' => self.all = true,
+                b't' => self.sort = SortBy::Time,
+                b'S' => self.sort = SortBy::Size,
+                b'r' => self.reverse = true,
+                b'c' => self.created = true,
+                _ => return Err(ShellError::Usage),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Listed {
+    name: [u8; MAX_SIZE_DIRECTORY_NAME],
+    is_directory: bool,
+    /// Bytes of a file or entries of a directory.
+    size: usize,
+    lines: usize,
+    created: DateTime,
+    modified: DateTime,
+}
+
+impl Listed {
+    fn new(sh: &Shell, entry: Entry) -> Listed {
+        match entry {
+            Entry::File(index) => {
+                let file = sh.fs.file(index);
+                Listed {
+                    name: file.name,
+                    is_directory: false,
+                    size: file.size(),
+                    lines: file.count_lines,
+                    created: file.created,
+                    modified: file.modified,
+                }
+            }
+            Entry::Directory(index) => {
+                let directory = sh.fs.directory(index);
+                Listed {
+                    name: directory.name,
+                    is_directory: true,
+                    size: directory.children().count() + directory.files().count(),
+                    lines: 0,
+                    created: directory.created,
+                    modified: directory.modified,
+                }
+            }
+        }
+    }
+
+    fn named(self, name: &str) -> Listed {
+        Listed {
+            name: name_from_str(name).unwrap_or(self.name),
+            ..self
+        }
+    }
+
+    fn name(&self) -> &str {
+        name_to_str(&self.name)
+    }
+
+    fn time(&self, options: &Options) -> DateTime {
+        match options.created {
+            true => self.created,
+            false => self.modified,
+        }
+    }
+
+    /// Width of the name in the short format, which marks directories
+    /// with a trailing `/`.
+    fn width(&self) -> usize {
+        self.name().len() + self.is_directory as usize
+    }
+}
+
+/// Entries to print with one of the formats of `ls`.
+struct Listing {
+    entries: [Option<Listed>; MAX_COUNT_ENTRIES],
+    count: usize,
+}
+
+impl Listing {
+    fn new() -> Listing {
+        Listing {
+            entries: [None; MAX_COUNT_ENTRIES],
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, listed: Listed) {
+        if self.count < MAX_COUNT_ENTRIES {
+            self.entries[self.count] = Some(listed);
+            self.count += 1;
+        }
+    }
+
+    fn of_directory(sh: &Shell, index: usize, options: &Options) -> Listing {
+        let mut listing = Listing::new();
+        let directory = sh.fs.directory(index);
+
+        if options.all {
+            listing.push(Listed::new(sh, Entry::Directory(index)).named("."));
+            listing.push(Listed::new(sh, Entry::Directory(directory.parent_index)).named(".."));
+        }
+
+        let entries = directory
+            .children()
+            .map(Entry::Directory)
+            .chain(directory.files().map(Entry::File));
+
+        for entry in entries {
+            let listed = Listed::new(sh, entry);
+            if options.all || !listed.name().starts_with('.') {
+                listing.push(listed);
+            }
+        }
+
+        listing
+    }
+
+    fn sort(&mut self, options: &Options) {
+        let options = *options;
+
+        self.entries[..self.count].sort_unstable_by(|a, b| {
+            let (a, b) = match (a, b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return core::cmp::Ordering::Equal,
+            };
+            let order = match options.sort {
+                SortBy::Name => a.name().cmp(b.name()),
+                SortBy::Time => b.time(&options).cmp(&a.time(&options)),
+                SortBy::Size => b.size.cmp(&a.size),
+            }
+            .then_with(|| a.name().cmp(b.name()));
+
+            match options.reverse {
+                true => order.reverse(),
+                false => order,
+            }
+        });
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Listed> {
+        self.entries[..self.count].iter().flatten()
+    }
+
+    /// One line per entry with its type, size, number of lines and the
+    /// time it was last changed.
+    fn print_long(&self, options: &Options, io: &mut Io) {
+        for listed in self.iter() {
+            let _ = match listed.is_directory {
+                true => writeln!(
+                    io.out,
+                    "d {:>5} {:>5} {}  {}/",
+                    listed.size,
+                    "-",
+                    listed.time(options),
+                    listed.name()
+                ),
+                false => writeln!(
+                    io.out,
+                    "- {:>5} {:>5} {}  {}",
+                    listed.size,
+                    listed.lines,
+                    listed.time(options),
+                    listed.name()
+                ),
+            };
+        }
+    }
+
+    /// Names in as many columns as fit on a line, filled top to bottom.
+    fn print_columns(&self, io: &mut Io) {
+        if self.count == 0 {
+            return;
+        }
+
+        let width = self.iter().map(Listed::width).max().unwrap_or(0) + COLUMN_GAP;
+        // a line filling the screen completely would wrap onto an empty one
+        let columns = ((LINE_LENGTH - 1) / width).max(1);
+        let rows = (self.count + columns - 1) / columns;
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let listed = match self.entries.get(column * rows + row) {
+                    Some(Some(listed)) if column * rows + row < self.count => listed,
+                    _ => break,
+                };
+                let suffix = if listed.is_directory { "/" } else { "" };
+                let is_last = column + 1 == columns || (column + 1) * rows + row >= self.count;
+
+                let _ = match is_last {
+                    true => write!(io.out, "{}{}", listed.name(), suffix),
+                    false => write!(
+                        io.out,
+                        "{}{}{:pad$}",
+                        listed.name(),
+                        suffix,
+                        "",
+                        pad = width - listed.width()
+                    ),
+                };
+            }
+            let _ = writeln!(io.out);
+        }
+    }
+
+    fn print(&mut self, options: &Options, io: &mut Io) {
+        self.sort(options);
+
+        match (options.long, io.is_console) {
+            (true, _) => self.print_long(options, io),
+            (false, true) => self.print_columns(io),
+            // one name per line for the next command of a pipeline
+            (false, false) => {
+                for listed in self.iter() {
+                    let _ = writeln!(io.out, "{}", listed.name());
+                }
+            }
+        }
+    }
+}
+
+struct ListFiles;
+
+impl Command for ListFiles {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn usage(&self) -> &'static str {
+        "ls [-latScr] [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List a directory, -l in long format, -a with hidden names"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let mut options = Options {
+            long: false,
+            all: false,
+            sort: SortBy::Name,
+            reverse: false,
+            created: false,
+        };
+        let mut first_operand = 1;
+
+        for arg in argv.args() {
+            if !arg.starts_with('-') || arg.len() < 2 {
+                break;
+            }
+            options.parse(arg)?;
+            first_operand += 1;
+        }
+
+        let operands = argv.len() - first_operand;
+        if operands == 0 {
+            Listing::of_directory(sh, sh.current_directory, &options).print(&options, io);
+            return Ok(());
+        }
+
+        // files named on the command line come first, then the directories
+        let mut files = Listing::new();
+        let mut failed = false;
+
+        for path in argv.iter().skip(first_operand) {
+            match sh.fs.resolve(sh.current_directory, path) {
+                Ok(Entry::File(index)) => files.push(Listed::new(sh, Entry::File(index))),
+                Ok(Entry::Directory(_)) => {}
+                Err(error) => {
+                    let _ = writeln!(io.out, "[Error] ls: {}: {}", path, error);
+                    failed = true;
+                }
+            }
+        }
+        files.print(&options, io);
+
+        let mut printed = files.count > 0;
+        for path in argv.iter().skip(first_operand) {
+            let directory = match sh.fs.resolve(sh.current_directory, path) {
+                Ok(Entry::Directory(directory)) => directory,
+                _ => continue,
+            };
+
+            if operands > 1 {
+                if printed {
+                    let _ = writeln!(io.out);
+                }
+                let _ = writeln!(io.out, "{}:", path);
+            }
+            Listing::of_directory(sh, directory, &options).print(&options, io);
+            printed = true;
+        }
+
+        match failed {
+            true => Err(ShellError::Failed),
+            false => Ok(()),
+        }
+    }
+}
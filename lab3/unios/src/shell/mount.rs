@@ -0,0 +1,336 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::disk::{disk_index, with_disk};
+use super::fs::{Entry, MAX_COUNT_DIRECTORIES, MAX_COUNT_FILES, ROOT_DIRECTORY};
+use super::text::Source;
+use super::{print_error, Shell};
+use crate::block::BlockDevice;
+use crate::disks::{disk_name, MAX_COUNT_DISKS};
+use crate::println;
+use crate::rtc;
+use unifs::{
+    self, DirEntry, Inode, InodeKind, Journal, Superblock, UnifsError, BLOCK_SIZE, DIR_ENTRY_SIZE, MAX_DATA_SIZE,
+    ROOT_INODE,
+};
+
+pub static COMMANDS: [&dyn Command; 3] = [&Mount, &Sync, &Format];
+
+/// Inodes `sync` needs: every directory and file keeps the same inode, the
+/// root directory first, the files behind the directories.
+const COUNT_INODES: usize = ROOT_INODE as usize + MAX_COUNT_DIRECTORIES + MAX_COUNT_FILES;
+/// Bytes 510 and 511 of a sector the BIOS can boot from.
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+fn directory_inode(index: usize) -> u32 {
+    ROOT_INODE + index as u32
+}
+
+fn file_inode(index: usize) -> u32 {
+    ROOT_INODE + (MAX_COUNT_DIRECTORIES + index) as u32
+}
+
+impl Shell {
+    /// Replaces the directories and files with the ones on the disk at
+    /// `index` and writes them back there on `sync`. Returns how many blocks
+    /// of a `sync` cut off before had to be copied from the journal.
+    pub fn mount(&mut self, index: usize) -> Result<usize, ShellError> {
+        self.current_directory = ROOT_DIRECTORY;
+        self.mounted = None;
+
+        let replayed = with_disk(index, |disk| self.load(disk))?;
+        self.mounted = Some(index);
+        Ok(replayed)
+    }
+
+    /// Mounts the first disk holding a file system, bringing back the work
+    /// of the last session.
+    pub fn mount_at_boot(&mut self) {
+        let index = (0..MAX_COUNT_DISKS).find(|&index| with_disk(index, unifs::read_superblock).is_ok());
+
+        if let Some(index) = index {
+            match self.mount(index) {
+                Ok(0) => println!("Mounted {}", disk_name(index)),
+                Ok(_) => println!("Mounted {}, finished the last sync from the journal", disk_name(index)),
+                Err(error) => print_error("mount", error),
+            }
+        }
+    }
+
+    fn load(&mut self, disk: &mut dyn BlockDevice) -> Result<usize, ShellError> {
+        let replayed = unifs::replay_journal(disk)?;
+        let superblock = unifs::read_superblock(disk)?;
+        let root = unifs::read_inode(disk, &superblock, superblock.root_inode)?;
+        if root.kind != InodeKind::Directory {
+            return Err(UnifsError::Corrupted.into());
+        }
+
+        self.fs.clear();
+        self.load_directory(disk, &superblock, &root, ROOT_DIRECTORY, 0)?;
+        Ok(replayed)
+    }
+
+    fn load_directory(
+        &mut self,
+        disk: &mut dyn BlockDevice,
+        superblock: &Superblock,
+        inode: &Inode,
+        directory: usize,
+        depth: usize,
+    ) -> Result<(), ShellError> {
+        // a directory containing one of its ancestors would never end
+        if depth >= MAX_COUNT_DIRECTORIES {
+            return Err(UnifsError::Corrupted.into());
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        for index in 0..inode.count_blocks().min(unifs::DIRECT_BLOCKS) {
+            unifs::read_data_block(disk, superblock, inode, index, &mut block)?;
+            let count_entries = (inode.size as usize - index * BLOCK_SIZE).min(BLOCK_SIZE) / DIR_ENTRY_SIZE;
+
+            for bytes in block.chunks(DIR_ENTRY_SIZE).take(count_entries) {
+                let entry = DirEntry::decode(bytes)?;
+                if entry.inode == 0 {
+                    continue;
+                }
+
+                let child = unifs::read_inode(disk, superblock, entry.inode)?;
+                match child.kind {
+                    InodeKind::Directory => {
+                        let index = self.fs.create_directory(directory, entry.name())?;
+                        self.load_directory(disk, superblock, &child, index, depth + 1)?;
+                    }
+                    InodeKind::File => {
+                        let index = self.fs.create_file(directory, entry.name())?;
+                        self.load_file(disk, superblock, &child, index)?;
+                    }
+                    InodeKind::Free => return Err(UnifsError::Corrupted.into()),
+                }
+            }
+        }
+
+        // creating the entries has changed the time
+        let directory = self.fs.directory_mut(directory);
+        directory.created = inode.created;
+        directory.modified = inode.modified;
+        Ok(())
+    }
+
+    fn load_file(
+        &mut self,
+        disk: &mut dyn BlockDevice,
+        superblock: &Superblock,
+        inode: &Inode,
+        index: usize,
+    ) -> Result<(), ShellError> {
+        let mut data = [0; MAX_DATA_SIZE];
+        let size = unifs::read_data(disk, superblock, inode, &mut data)?;
+
+        let file = self.fs.file_mut(index);
+        file.set_text(&data[..size]);
+        file.created = inode.created;
+        file.modified = inode.modified;
+        Ok(())
+    }
+
+    /// Writes all directories and files to `disk`, replacing what it held,
+    /// in one update of the journal.
+    fn save(&self, disk: &mut dyn BlockDevice) -> Result<(), ShellError> {
+        // blocks of a sync cut off before must not end up next to the new ones
+        unifs::replay_journal(disk)?;
+
+        let mut superblock = unifs::read_superblock(disk)?;
+        if (superblock.inode_count as usize) < COUNT_INODES {
+            return Err(UnifsError::NoSpace.into());
+        }
+        let mut journal = Journal::new(disk, &superblock);
+
+        let mut inodes = [Inode::FREE; COUNT_INODES];
+        let mut next_block = superblock.data_start;
+        let mut data = [0; MAX_DATA_SIZE];
+
+        for index in (0..MAX_COUNT_DIRECTORIES).filter(|&index| self.fs.exists(Entry::Directory(index))) {
+            let directory = self.fs.directory(index);
+            let children = directory
+                .children()
+                .map(|child| (directory_inode(child), InodeKind::Directory, self.fs.directory(child).name()));
+            let files = directory
+                .files()
+                .map(|file| (file_inode(file), InodeKind::File, self.fs.file(file).name()));
+
+            let mut len = 0;
+            for (inode, kind, name) in children.chain(files) {
+                DirEntry::new(inode, kind, name)?.encode(&mut data[len..len + DIR_ENTRY_SIZE]);
+                len += DIR_ENTRY_SIZE;
+            }
+
+            let mut inode = Inode {
+                kind: InodeKind::Directory,
+                parent: directory_inode(directory.parent_index),
+                created: directory.created,
+                modified: directory.modified,
+                ..Inode::FREE
+            };
+            unifs::write_data(&mut journal, &superblock, &mut next_block, &data[..len], &mut inode)?;
+            inodes[directory_inode(index) as usize] = inode;
+        }
+
+        for index in (0..MAX_COUNT_FILES).filter(|&index| self.fs.exists(Entry::File(index))) {
+            let file = self.fs.file(index);
+
+            let mut len = 0;
+            for line in Source::File(file.text()).lines() {
+                data[len..len + line.len()].copy_from_slice(line.as_bytes());
+                data[len + line.len()] = b'\n';
+                len += line.len() + 1;
+            }
+
+            let mut inode = Inode {
+                kind: InodeKind::File,
+                parent: directory_inode(file.folder_index),
+                created: file.created,
+                modified: file.modified,
+                ..Inode::FREE
+            };
+            unifs::write_data(&mut journal, &superblock, &mut next_block, &data[..len], &mut inode)?;
+            inodes[file_inode(index) as usize] = inode;
+        }
+
+        let is_used = |number: u32| inodes.get(number as usize).map_or(false, |inode| inode.kind != InodeKind::Free);
+        let count_used = (0..COUNT_INODES as u32).filter(|&number| is_used(number)).count() as u32;
+
+        unifs::write_inode_table(&mut journal, &superblock, |number| {
+            inodes.get(number as usize).copied().unwrap_or(Inode::FREE)
+        })?;
+        unifs::write_bitmap(&mut journal, superblock.inode_bitmap_start, 1, |number| number == 0 || is_used(number))?;
+        unifs::write_bitmap(
+            &mut journal,
+            superblock.block_bitmap_start,
+            superblock.block_bitmap_blocks,
+            |block| block < next_block,
+        )?;
+
+        superblock.root_inode = ROOT_INODE;
+        superblock.free_blocks = superblock.block_count - next_block;
+        superblock.free_inodes = superblock.inode_count - count_used - 1;
+        superblock.synced = rtc::now();
+        unifs::write_superblock(&mut journal, &superblock)?;
+        journal.commit()?;
+        Ok(())
+    }
+}
+
+struct Mount;
+
+impl Command for Mount {
+    fn name(&self) -> &'static str {
+        "mount"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mount [disk]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Load the files of a disk, or show the mounted disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        match (argv.get(1), sh.mounted) {
+            (Some(name), _) if argv.len() == 2 => {
+                let replayed = sh.mount(disk_index(name)?)?;
+                if replayed > 0 {
+                    let _ = writeln!(io.out, "{}: Finished the last sync, {} blocks from the journal", name, replayed);
+                }
+                Ok(())
+            }
+            (None, Some(index)) => {
+                let superblock = with_disk(index, unifs::read_superblock)?;
+                let _ = writeln!(
+                    io.out,
+                    "{} on /, {} of {} blocks free, synced {}",
+                    disk_name(index),
+                    superblock.free_blocks,
+                    superblock.block_count,
+                    superblock.synced
+                );
+                Ok(())
+            }
+            (None, None) => {
+                let _ = writeln!(io.out, "No disk is mounted");
+                Ok(())
+            }
+            _ => Err(ShellError::Usage),
+        }
+    }
+}
+
+struct Sync;
+
+impl Command for Sync {
+    fn name(&self) -> &'static str {
+        "sync"
+    }
+
+    fn usage(&self) -> &'static str {
+        "sync [disk]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Write all files to the mounted or a formatted disk"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let index = match (argv.get(1), sh.mounted) {
+            (Some(name), _) if argv.len() == 2 => disk_index(name)?,
+            (None, Some(index)) => index,
+            (None, None) => {
+                let _ = writeln!(io.out, "[Error] sync: No disk is mounted");
+                return Err(ShellError::Failed);
+            }
+            _ => return Err(ShellError::Usage),
+        };
+
+        with_disk(index, |disk| sh.save(disk))?;
+        sh.mounted = Some(index);
+        Ok(())
+    }
+}
+
+struct Format;
+
+impl Command for Format {
+    fn name(&self) -> &'static str {
+        "format"
+    }
+
+    fn usage(&self) -> &'static str {
+        "format [-f] <disk>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Create an empty file system on a disk"
+    }
+
+    fn run(&self, _sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (force, name) = match (argv.get(1), argv.get(2), argv.len()) {
+            (Some("-f"), Some(name), 3) => (true, name),
+            (Some(name), None, 2) => (false, name),
+            _ => return Err(ShellError::Usage),
+        };
+
+        with_disk(disk_index(name)?, |disk| {
+            let mut sector = [0; BLOCK_SIZE];
+            disk.read(0, &mut sector)?;
+
+            // formatting the disk the kernel was booted from would make it
+            // unbootable
+            if sector[BLOCK_SIZE - 2..] == BOOT_SIGNATURE && !force {
+                let _ = writeln!(io.out, "[Error] format: {}: Disk holds a boot sector, use -f", name);
+                return Err(ShellError::Failed);
+            }
+
+            let superblock = unifs::format(disk, rtc::now())?;
+            let _ = writeln!(io.out, "{}: {} blocks, {} inodes", name, superblock.block_count, superblock.inode_count);
+            Ok(())
+        })
+    }
+}
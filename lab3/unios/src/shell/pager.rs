@@ -0,0 +1,268 @@
+use core::fmt::Write;
+
+use super::command::{Argv, Buffer, Command, Io, ShellError};
+use super::fs::{LINE_LENGTH, MAX_COUNT_LINES};
+use super::search::find_in;
+use super::text::{for_each_input, Source};
+use super::Shell;
+use crate::{print, println};
+use crate::vga_buf::SCREEN;
+
+pub static COMMANDS: [&dyn Command; 1] = [&More];
+
+/// How much text the pager holds, the rest is dropped.
+const PAGER_SIZE: usize = 4096;
+/// The last line of the screen shows the status of the pager.
+const PAGE_LINES: usize = MAX_COUNT_LINES - 1;
+/// Longer lines are wrapped, a line filling the screen completely would
+/// be followed by an empty one.
+const MAX_LINE_WIDTH: usize = LINE_LENGTH - 1;
+const MAX_SIZE_PATTERN: usize = 32;
+
+/// Black on light grey.
+const STATUS_COLOR: u8 = 0x70;
+const MATCH_COLOR: u8 = 0xE0;
+
+/// Keys the pager reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagerKey {
+    Char(u8),
+    LineUp,
+    LineDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+}
+
+/// Shows text one screen at a time. While it is open the shell hands every
+/// key to it instead of the command line.
+pub struct Pager {
+    text: [u8; PAGER_SIZE],
+    len: usize,
+    count_lines: usize,
+    /// First line on the screen.
+    top: usize,
+    /// Set while a pattern is typed after `/`.
+    searching: bool,
+    pattern: Buffer<MAX_SIZE_PATTERN>,
+    message: &'static str,
+}
+
+impl Pager {
+    fn new() -> Pager {
+        Pager {
+            text: [0; PAGER_SIZE],
+            len: 0,
+            count_lines: 0,
+            top: 0,
+            searching: false,
+            pattern: Buffer::new(),
+            message: "",
+        }
+    }
+
+    /// Appends a line, wrapping it if it is wider than the screen.
+    fn push_line(&mut self, line: &str) {
+        let mut chunks = line.as_bytes().chunks(MAX_LINE_WIDTH);
+        let first = chunks.next().unwrap_or(&[]);
+
+        for chunk in core::iter::once(first).chain(chunks) {
+            if self.len + chunk.len() + 1 > PAGER_SIZE {
+                return;
+            }
+            self.text[self.len..self.len + chunk.len()].copy_from_slice(chunk);
+            self.text[self.len + chunk.len()] = b'\n';
+            self.len += chunk.len() + 1;
+            self.count_lines += 1;
+        }
+    }
+
+    fn line(&self, index: usize) -> &str {
+        let text = core::str::from_utf8(&self.text[..self.len]).unwrap_or("");
+        text.lines().nth(index).unwrap_or("")
+    }
+
+    fn last_top(&self) -> usize {
+        self.count_lines.saturating_sub(PAGE_LINES)
+    }
+
+    fn scroll_to(&mut self, top: usize) {
+        self.top = top.min(self.last_top());
+    }
+
+    /// Moves to the next line after the top one that contains the pattern.
+    fn search(&mut self) {
+        let pattern = self.pattern.as_str();
+        let found = (self.top + 1..self.count_lines).find(|&i| find_in(self.line(i), pattern, false).is_some());
+
+        match found {
+            Some(line) => {
+                self.top = line.min(self.last_top());
+                self.message = "";
+            }
+            None => self.message = "Pattern not found",
+        }
+    }
+
+    fn print_line(&self, line: &str) {
+        let mut rest = line;
+        let pattern = self.pattern.as_str();
+
+        while let (false, Some(start)) = (pattern.is_empty(), find_in(rest, pattern, false)) {
+            print!("{}", &rest[..start]);
+
+            let color = SCREEN.lock().color();
+            SCREEN.lock().set_color(MATCH_COLOR);
+            print!("{}", &rest[start..start + pattern.len()]);
+            SCREEN.lock().set_color(color);
+
+            rest = &rest[start + pattern.len()..];
+        }
+        println!("{}", rest);
+    }
+
+    fn render(&self) {
+        SCREEN.lock().clear();
+
+        for i in self.top..(self.top + PAGE_LINES).min(self.count_lines) {
+            self.print_line(self.line(i));
+        }
+        for _ in self.count_lines.saturating_sub(self.top)..PAGE_LINES {
+            println!("~");
+        }
+
+        let mut status = Buffer::<MAX_LINE_WIDTH>::new();
+        let _ = match (self.searching, self.message) {
+            (true, _) => write!(status, "/{}", self.pattern.as_str()),
+            (false, "") => write!(
+                status,
+                "lines {}-{} of {}  Space Enter b / n q",
+                self.top + 1,
+                (self.top + PAGE_LINES).min(self.count_lines),
+                self.count_lines
+            ),
+            (false, message) => write!(status, "{}", message),
+        };
+
+        let color = SCREEN.lock().color();
+        SCREEN.lock().set_color(STATUS_COLOR);
+        print!("{}", status.as_str());
+        SCREEN.lock().set_color(color);
+    }
+
+    /// Reacts to `key`, returning `false` once the pager is closed.
+    pub fn on_key(&mut self, key: PagerKey) -> bool {
+        self.message = "";
+
+        if self.searching {
+            match key {
+                PagerKey::Char(b'\n') => {
+                    self.searching = false;
+                    self.search();
+                }
+                // backspace
+                PagerKey::Char(8) => {
+                    let len = self.pattern.len();
+                    self.pattern.truncate(len.saturating_sub(1));
+                }
+                // escape
+                PagerKey::Char(27) => {
+                    self.searching = false;
+                    self.pattern.clear();
+                }
+                PagerKey::Char(symbol) => self.pattern.push(symbol),
+                _ => {}
+            }
+            self.render();
+            return true;
+        }
+
+        match key {
+            PagerKey::Char(b'q') => return false,
+            PagerKey::Char(b' ') | PagerKey::Char(b'f') | PagerKey::PageDown => {
+                self.scroll_to(self.top + PAGE_LINES)
+            }
+            PagerKey::Char(b'b') | PagerKey::PageUp => self.scroll_to(self.top.saturating_sub(PAGE_LINES)),
+            PagerKey::Char(b'\n') | PagerKey::Char(b'j') | PagerKey::LineDown => self.scroll_to(self.top + 1),
+            PagerKey::Char(b'k') | PagerKey::LineUp => self.scroll_to(self.top.saturating_sub(1)),
+            PagerKey::Char(b'g') | PagerKey::Top => self.scroll_to(0),
+            PagerKey::Char(b'G') | PagerKey::Bottom => self.scroll_to(self.last_top()),
+            PagerKey::Char(b'/') => {
+                self.searching = true;
+                self.pattern.clear();
+            }
+            PagerKey::Char(b'n') if !self.pattern.as_str().is_empty() => self.search(),
+            _ => {}
+        }
+
+        self.render();
+        true
+    }
+}
+
+impl Shell {
+    /// Hands `key` to the open pager, returning to the prompt once it is
+    /// closed.
+    pub fn on_pager_key(&mut self, key: PagerKey) {
+        let is_open = match self.pager.as_mut() {
+            Some(pager) => pager.on_key(key),
+            None => return,
+        };
+
+        if !is_open {
+            self.pager = None;
+            self.clear_screen();
+            self.print_prompt();
+        }
+    }
+}
+
+struct More;
+
+impl Command for More {
+    fn name(&self) -> &'static str {
+        "more"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["less"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "more [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show text page by page, q quits"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if !io.is_console {
+            // in the middle of a pipeline there is nothing to page
+            return for_each_input(sh, io.input, argv, 1, io, |source, _, io| {
+                for line in source.lines() {
+                    let _ = writeln!(io.out, "{}", line);
+                }
+            });
+        }
+
+        let mut pager = Pager::new();
+        let result = for_each_input(sh, io.input, argv, 1, io, |source: Source, _, _| {
+            for line in source.lines() {
+                pager.push_line(line);
+            }
+        });
+
+        if pager.count_lines <= PAGE_LINES {
+            for i in 0..pager.count_lines {
+                let _ = writeln!(io.out, "{}", pager.line(i));
+            }
+            return result;
+        }
+
+        pager.render();
+        sh.pager = Some(pager);
+        result
+    }
+}
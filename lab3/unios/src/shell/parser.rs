@@ -0,0 +1,4 @@
+//! The command line syntax lives in `unish`, which builds for the host so
+//! that the parser is tested there.
+
+pub use unish::{tokenize, CommandList, ParseError, Pipeline, Separator};
@@ -0,0 +1,90 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::Shell;
+use crate::pci::{Bar, PciDevice, DEVICES};
+
+pub static COMMANDS: [&dyn Command; 1] = [&Lspci];
+
+struct Lspci;
+
+impl Command for Lspci {
+    fn name(&self) -> &'static str {
+        "lspci"
+    }
+
+    fn usage(&self) -> &'static str {
+        "lspci [-v]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List the PCI devices found at boot, -v with BARs and capabilities"
+    }
+
+    fn run(&self, _sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let verbose = match (argv.get(1), argv.len()) {
+            (None, 1) => false,
+            (Some("-v"), 2) => true,
+            _ => return Err(ShellError::Usage),
+        };
+        let devices = DEVICES.lock();
+
+        for (device, driver) in devices.iter() {
+            print_device(device, io);
+            match (verbose, driver) {
+                (true, _) => print_details(device, *driver, io),
+                (false, Some(driver)) => {
+                    let _ = writeln!(io.out, ", driver {}", driver);
+                }
+                (false, None) => {
+                    let _ = writeln!(io.out);
+                }
+            }
+        }
+
+        if devices.iter().next().is_none() {
+            let _ = writeln!(io.out, "No PCI devices found");
+        }
+        Ok(())
+    }
+}
+
+/// The line `lspci` shows for every device, without its end.
+fn print_device(device: &PciDevice, io: &mut Io) {
+    let _ = write!(
+        io.out,
+        "{} {} [{:02x}{:02x}]: ",
+        device.address,
+        device.class_name(),
+        device.class,
+        device.subclass
+    );
+    if let Some(vendor) = device.vendor_name() {
+        let _ = write!(io.out, "{} ", vendor);
+    }
+    let _ = write!(io.out, "{:04x}:{:04x}", device.vendor_id, device.device_id);
+    if device.revision != 0 {
+        let _ = write!(io.out, " (rev {:02x})", device.revision);
+    }
+    if device.prog_if != 0 {
+        let _ = write!(io.out, " (prog-if {:02x})", device.prog_if);
+    }
+}
+
+fn print_details(device: &PciDevice, driver: Option<&str>, io: &mut Io) {
+    let _ = writeln!(io.out);
+
+    if device.interrupt_pin != 0 {
+        let pin = (b'A' + device.interrupt_pin - 1) as char;
+        let _ = writeln!(io.out, "    Interrupt: pin {} routed to IRQ {}", pin, device.interrupt_line);
+    }
+    for (index, bar) in device.bars.iter().enumerate() {
+        if *bar != Bar::None {
+            let _ = writeln!(io.out, "    BAR{}: {}", index, bar);
+        }
+    }
+    for capability in device.address.capabilities() {
+        let _ = writeln!(io.out, "    Capabilities: [{:02x}] {}", capability.offset, capability.name());
+    }
+    if let Some(driver) = driver {
+        let _ = writeln!(io.out, "    Kernel driver in use: {}", driver);
+    }
+}
@@ -0,0 +1,335 @@
+use core::fmt;
+
+use super::command::{Argv, Command, Io, ShellError, EXIT_SUCCESS};
+use super::fs::{File, FILE_SIZE, LINE_LENGTH, MAX_COUNT_LINES};
+use super::Shell;
+
+pub static COMMANDS: [&dyn Command; 2] = [&Source, &Test];
+
+const MAX_SCRIPT_DEPTH: usize = 4;
+const MAX_LOOP_ITERATIONS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptError {
+    /// `if`, `while` or `for` without its `fi` or `done`.
+    UnterminatedBlock,
+    /// `else`, `fi` or `done` outside of a block it belongs to.
+    UnexpectedKeyword,
+    MalformedFor,
+    TooManyIterations,
+    TooDeep,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ScriptError::UnterminatedBlock => "Block is not closed with fi or done",
+            ScriptError::UnexpectedKeyword => "Unexpected else, fi or done",
+            ScriptError::MalformedFor => "Expected for <name> in <words...>",
+            ScriptError::TooManyIterations => "Loop ran for too many iterations",
+            ScriptError::TooDeep => "Scripts are nested too deeply",
+        };
+        f.write_str(message)
+    }
+}
+
+/// A script split into lines, one per line of the file it was read from.
+pub struct Script {
+    content: [u8; FILE_SIZE],
+    count_lines: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Empty,
+    Command(&'a [u8]),
+    If(&'a [u8]),
+    Else,
+    Fi,
+    While(&'a [u8]),
+    For(&'a [u8]),
+    Done,
+}
+
+fn trim(line: &[u8]) -> &[u8] {
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\0';
+    let start = line.iter().position(|b| !is_blank(b)).unwrap_or(line.len());
+    let end = line.iter().rposition(|b| !is_blank(b)).map_or(start, |end| end + 1);
+    &line[start..end]
+}
+
+/// Removes a trailing `; then` or `; do` so that the condition can be
+/// written on the same line as its keyword.
+fn strip_suffix<'a>(line: &'a [u8], keyword: &[u8]) -> &'a [u8] {
+    match line.strip_suffix(keyword) {
+        Some(rest) if rest.ends_with(b";") || rest.ends_with(b" ") => {
+            trim(rest.strip_suffix(b";").unwrap_or(rest))
+        }
+        _ => line,
+    }
+}
+
+fn classify(line: &[u8]) -> Line<'_> {
+    let line = trim(line);
+    let (word, rest) = match line.iter().position(|&b| b == b' ') {
+        Some(space) => (&line[..space], trim(&line[space..])),
+        None => (line, &line[line.len()..]),
+    };
+
+    match word {
+        b"" | b"then" | b"do" => Line::Empty,
+        _ if word.starts_with(b"#") => Line::Empty,
+        b"if" => Line::If(strip_suffix(rest, b"then")),
+        b"else" => Line::Else,
+        b"fi" => Line::Fi,
+        b"while" => Line::While(strip_suffix(rest, b"do")),
+        b"for" => Line::For(strip_suffix(rest, b"do")),
+        b"done" => Line::Done,
+        _ => Line::Command(line),
+    }
+}
+
+impl Script {
+    pub fn from_file(file: &File) -> Script {
+        Script {
+            content: file.content,
+            count_lines: file.count_lines.min(MAX_COUNT_LINES),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count_lines
+    }
+
+    fn line(&self, index: usize) -> Line<'_> {
+        classify(&self.content[index * LINE_LENGTH..(index + 1) * LINE_LENGTH])
+    }
+
+    /// Finds the `else` (if any) and the line closing the block opened at
+    /// `start`.
+    fn block_end(&self, start: usize, end: usize) -> Result<(Option<usize>, usize), ScriptError> {
+        let mut depth = 0;
+        let mut else_line = None;
+
+        for i in start + 1..end {
+            match self.line(i) {
+                Line::If(_) | Line::While(_) | Line::For(_) => depth += 1,
+                Line::Else if depth == 0 => {
+                    if !matches!(self.line(start), Line::If(_)) || else_line.is_some() {
+                        return Err(ScriptError::UnexpectedKeyword);
+                    }
+                    else_line = Some(i);
+                }
+                Line::Fi | Line::Done if depth == 0 => {
+                    let matches_opening = match self.line(start) {
+                        Line::If(_) => self.line(i) == Line::Fi,
+                        _ => self.line(i) == Line::Done,
+                    };
+                    if !matches_opening {
+                        return Err(ScriptError::UnexpectedKeyword);
+                    }
+                    return Ok((else_line, i));
+                }
+                Line::Fi | Line::Done => depth -= 1,
+                _ => {}
+            }
+        }
+
+        Err(ScriptError::UnterminatedBlock)
+    }
+}
+
+impl Shell {
+    /// Runs `script` line by line through the same parser as the prompt.
+    pub fn run_script(&mut self, script: &Script) -> Result<(), ShellError> {
+        if self.script_depth == MAX_SCRIPT_DEPTH {
+            return Err(ShellError::Script(ScriptError::TooDeep));
+        }
+
+        self.script_depth += 1;
+        let result = self.run_block(script, 0, script.len());
+        self.script_depth -= 1;
+
+        result.map_err(ShellError::Script)
+    }
+
+    fn run_block(&mut self, script: &Script, start: usize, end: usize) -> Result<(), ScriptError> {
+        let mut i = start;
+
+        while i < end && !self.waits_for_keys() {
+            match script.line(i) {
+                Line::Empty => {}
+                Line::Command(line) => self.execute_line(line),
+                Line::If(condition) => {
+                    let (else_line, fi_line) = script.block_end(i, end)?;
+
+                    self.execute_line(condition);
+                    if self.last_status == EXIT_SUCCESS {
+                        self.run_block(script, i + 1, else_line.unwrap_or(fi_line))?;
+                    } else if let Some(else_line) = else_line {
+                        self.run_block(script, else_line + 1, fi_line)?;
+                    } else {
+                        self.last_status = EXIT_SUCCESS;
+                    }
+
+                    i = fi_line;
+                }
+                Line::While(condition) => {
+                    let (_, done_line) = script.block_end(i, end)?;
+                    let mut iterations = 0;
+
+                    loop {
+                        self.execute_line(condition);
+                        if self.last_status != EXIT_SUCCESS || self.waits_for_keys() {
+                            break;
+                        }
+
+                        iterations += 1;
+                        if iterations > MAX_LOOP_ITERATIONS {
+                            return Err(ScriptError::TooManyIterations);
+                        }
+
+                        self.run_block(script, i + 1, done_line)?;
+                    }
+                    self.last_status = EXIT_SUCCESS;
+
+                    i = done_line;
+                }
+                Line::For(header) => {
+                    let (_, done_line) = script.block_end(i, end)?;
+                    let words = match self.parse_args(header) {
+                        Ok(words) => words,
+                        Err(_) => return Err(ScriptError::MalformedFor),
+                    };
+
+                    let name = match (words.get(0), words.get(1)) {
+                        (Some(name), Some("in")) => name,
+                        _ => return Err(ScriptError::MalformedFor),
+                    };
+
+                    for value in words.iter().skip(2) {
+                        if self.vars.set(name, value).is_err() {
+                            return Err(ScriptError::MalformedFor);
+                        }
+                        self.run_block(script, i + 1, done_line)?;
+                    }
+
+                    i = done_line;
+                }
+                Line::Else | Line::Fi | Line::Done => return Err(ScriptError::UnexpectedKeyword),
+            }
+
+            i += 1;
+        }
+
+        Ok(())
+    }
+}
+
+struct Source;
+
+impl Command for Source {
+    fn name(&self) -> &'static str {
+        "source"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["run"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "source <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run the commands of a script file"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        let path = argv.get(1).ok_or(ShellError::Usage)?;
+
+        let file_index = sh.fs.resolve_file(sh.current_directory, path)?;
+        let script = Script::from_file(sh.fs.file(file_index));
+
+        sh.run_script(&script)?;
+
+        match sh.last_status {
+            EXIT_SUCCESS => Ok(()),
+            // the failing command has already reported its error
+            _ => Err(ShellError::Failed),
+        }
+    }
+}
+
+struct Test;
+
+impl Test {
+    fn evaluate(sh: &Shell, args: &[&str]) -> Result<bool, ShellError> {
+        let number = |arg: &str| arg.parse::<i64>().map_err(|_| ShellError::Usage);
+
+        match *args {
+            ["!", ref rest @ ..] => Ok(!Self::evaluate(sh, rest)?),
+            [] => Ok(false),
+            [string] => Ok(!string.is_empty()),
+            ["-n", string] => Ok(!string.is_empty()),
+            ["-z", string] => Ok(string.is_empty()),
+            ["-f", path] => Ok(sh.fs.resolve_file(sh.current_directory, path).is_ok()),
+            ["-d", path] => Ok(sh.fs.resolve_directory(sh.current_directory, path).is_ok()),
+            ["-e", path] => Ok(sh.fs.resolve_file(sh.current_directory, path).is_ok()
+                || sh.fs.resolve_directory(sh.current_directory, path).is_ok()),
+            [left, "=", right] => Ok(left == right),
+            [left, "!=", right] => Ok(left != right),
+            [left, "-eq", right] => Ok(number(left)? == number(right)?),
+            [left, "-ne", right] => Ok(number(left)? != number(right)?),
+            [left, "-lt", right] => Ok(number(left)? < number(right)?),
+            [left, "-le", right] => Ok(number(left)? <= number(right)?),
+            [left, "-gt", right] => Ok(number(left)? > number(right)?),
+            [left, "-ge", right] => Ok(number(left)? >= number(right)?),
+            _ => Err(ShellError::Usage),
+        }
+    }
+}
+
+impl Command for Test {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["["]
+    }
+
+    fn usage(&self) -> &'static str {
+        "test [!] <expression>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Check strings (= != -n -z), numbers (-eq -lt ...) or names (-f -d -e)"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        // `!`, an expression of up to three words and the closing `]`
+        let mut args = [""; 5];
+        let mut count = 0;
+
+        for arg in argv.args() {
+            if count == args.len() {
+                return Err(ShellError::Usage);
+            }
+            args[count] = arg;
+            count += 1;
+        }
+
+        if argv.get(0) == Some("[") {
+            if count == 0 || args[count - 1] != "]" {
+                return Err(ShellError::Usage);
+            }
+            count -= 1;
+        }
+
+        match Self::evaluate(sh, &args[..count])? {
+            true => Ok(()),
+            false => Err(ShellError::Failed),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use core::fmt::Write;
+
+use super::command::{Argv, Buffer, Command, Io, ShellError};
+use super::fs::{
+    name_to_str, Entry, FsError, MAX_COUNT_CHILDREN_DIRECTORIES, MAX_COUNT_FILES_IN_FOLDER,
+    MAX_SIZE_DIRECTORY_NAME,
+};
+use super::glob;
+use super::text::Source;
+use super::{Shell, MAX_SIZE_PATH};
+
+pub static COMMANDS: [&dyn Command; 2] = [&Find, &Grep];
+
+const MAX_COUNT_ENTRIES: usize = MAX_COUNT_CHILDREN_DIRECTORIES + MAX_COUNT_FILES_IN_FOLDER;
+
+/// Calls `visit` for every entry below `directory` in the order of their
+/// names, a directory before what is inside it. `path` leads to
+/// `directory` and is extended by the name of every entry visited.
+fn walk(
+    sh: &Shell,
+    directory: usize,
+    path: &mut Buffer<MAX_SIZE_PATH>,
+    visit: &mut dyn FnMut(&Shell, Entry, &str),
+) {
+    let mut entries = [([0; MAX_SIZE_DIRECTORY_NAME], Entry::File(0)); MAX_COUNT_ENTRIES];
+    let mut count = 0;
+
+    let directory = sh.fs.directory(directory);
+    let children = directory
+        .children()
+        .map(|index| (sh.fs.directory(index).name, Entry::Directory(index)));
+    let files = directory
+        .files()
+        .map(|index| (sh.fs.file(index).name, Entry::File(index)));
+
+    for entry in children.chain(files).take(MAX_COUNT_ENTRIES) {
+        entries[count] = entry;
+        count += 1;
+    }
+    entries[..count].sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let path_len = path.len();
+    for (name, entry) in entries[..count].iter() {
+        if !path.as_str().ends_with('/') {
+            path.push(b'/');
+        }
+        let _ = path.write_str(name_to_str(name));
+
+        visit(sh, *entry, path.as_str());
+        if let Entry::Directory(index) = *entry {
+            walk(sh, index, path, visit);
+        }
+
+        path.truncate(path_len);
+    }
+}
+
+/// Last component of `path`, the name `find -name` looks at.
+fn base_name(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path.rsplit('/').next().unwrap_or(path),
+    }
+}
+
+struct Find;
+
+impl Command for Find {
+    fn name(&self) -> &'static str {
+        "find"
+    }
+
+    fn usage(&self) -> &'static str {
+        "find [path] [-name <pattern>] [-type f|d]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Search for names"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let mut start = ".";
+        let mut pattern = None;
+        let mut wanted_type = None;
+        let mut i = 1;
+
+        if let Some(path) = argv.get(1).filter(|arg| !arg.starts_with('-')) {
+            start = path;
+            i += 1;
+        }
+
+        while let Some(option) = argv.get(i) {
+            let value = argv.get(i + 1).ok_or(ShellError::Usage)?;
+            match (option, value) {
+                ("-name", pattern_value) => pattern = Some(pattern_value),
+                ("-type", "f") => wanted_type = Some(false),
+                ("-type", "d") => wanted_type = Some(true),
+                _ => return Err(ShellError::Usage),
+            }
+            i += 2;
+        }
+
+        let is_wanted = |entry: Entry, path: &str| {
+            let is_directory = matches!(entry, Entry::Directory(_));
+            wanted_type.map_or(true, |wanted| wanted == is_directory)
+                && pattern.map_or(true, |pattern| {
+                    glob::matches(pattern.as_bytes(), base_name(path).as_bytes())
+                })
+        };
+
+        let entry = sh.fs.resolve(sh.current_directory, start)?;
+        if is_wanted(entry, start) {
+            let _ = writeln!(io.out, "{}", start);
+        }
+
+        if let Entry::Directory(directory) = entry {
+            let mut path = Buffer::<MAX_SIZE_PATH>::new();
+            let _ = path.write_str(start);
+
+            walk(sh, directory, &mut path, &mut |_, entry, path| {
+                if is_wanted(entry, path) {
+                    let _ = writeln!(io.out, "{}", path);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct GrepOptions {
+    ignore_case: bool,
+    line_numbers: bool,
+    recursive: bool,
+    /// Put the path of the file in front of every line found.
+    with_path: bool,
+}
+
+fn bytes_equal(a: &[u8], b: &[u8], ignore_case: bool) -> bool {
+    match ignore_case {
+        true => a.eq_ignore_ascii_case(b),
+        false => a == b,
+    }
+}
+
+/// Position of the first occurrence of `pattern` in `line`.
+pub fn find_in(line: &str, pattern: &str, ignore_case: bool) -> Option<usize> {
+    let (line, pattern) = (line.as_bytes(), pattern.as_bytes());
+
+    if pattern.len() > line.len() {
+        return None;
+    }
+    (0..=line.len() - pattern.len()).find(|&i| bytes_equal(&line[i..i + pattern.len()], pattern, ignore_case))
+}
+
+/// Prints the lines of `source` that contain `pattern`, with every
+/// occurrence highlighted. Returns whether there were any.
+fn grep_source(source: Source, path: &str, pattern: &str, options: &GrepOptions, io: &mut Io) -> bool {
+    let mut found = false;
+
+    for (number, line) in source.lines().enumerate() {
+        if find_in(line, pattern, options.ignore_case).is_none() {
+            continue;
+        }
+        found = true;
+
+        if options.with_path {
+            let _ = write!(io.out, "{}:", path);
+        }
+        if options.line_numbers {
+            let _ = write!(io.out, "{}:", number + 1);
+        }
+
+        let mut rest = line;
+        while let Some(start) = find_in(rest, pattern, options.ignore_case) {
+            let end = start + pattern.len();
+            let _ = io.out.write_str(&rest[..start]);
+            io.highlight(&rest[start..end]);
+            rest = &rest[end..];
+
+            if pattern.is_empty() {
+                break;
+            }
+        }
+        let _ = writeln!(io.out, "{}", rest);
+    }
+
+    found
+}
+
+struct Grep;
+
+impl Command for Grep {
+    fn name(&self) -> &'static str {
+        "grep"
+    }
+
+    fn usage(&self) -> &'static str {
+        "grep [-inr] <text> [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print lines of files that contain a text"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let mut options = GrepOptions::default();
+        let mut first = 1;
+
+        for arg in argv.args() {
+            if !arg.starts_with('-') || arg.len() < 2 {
+                break;
+            }
+            for flag in arg.bytes().skip(1) {
+                match flag {
+                    b'i' => options.ignore_case = true,
+                    b'n' => options.line_numbers = true,
+                    b'r' => options.recursive = true,
+                    _ => return Err(ShellError::Usage),
+                }
+            }
+            first += 1;
+        }
+
+        let pattern = argv.get(first).ok_or(ShellError::Usage)?;
+        let operands = argv.len() - first - 1;
+        if let (0, false, Some(input)) = (operands, options.recursive, io.input) {
+            return match grep_source(Source::Piped(input), "", pattern, &options, io) {
+                true => Ok(()),
+                false => Err(ShellError::Failed),
+            };
+        }
+        if operands == 0 && !options.recursive {
+            return Err(ShellError::Usage);
+        }
+        options.with_path = operands > 1 || options.recursive;
+
+        let (mut found, mut failed) = (false, false);
+        let mut search = |sh: &Shell, path: &str, io: &mut Io| match sh.fs.resolve(sh.current_directory, path) {
+            Ok(Entry::File(index)) => {
+                let source = Source::File(sh.fs.file(index).text());
+                found |= grep_source(source, path, pattern, &options, io);
+            }
+            Ok(Entry::Directory(directory)) if options.recursive => {
+                let mut walked = Buffer::<MAX_SIZE_PATH>::new();
+                let _ = walked.write_str(path);
+
+                walk(sh, directory, &mut walked, &mut |sh, entry, path| {
+                    if let Entry::File(index) = entry {
+                        let source = Source::File(sh.fs.file(index).text());
+                        found |= grep_source(source, path, pattern, &options, io);
+                    }
+                });
+            }
+            Ok(Entry::Directory(_)) => {
+                let _ = writeln!(io.out, "[Error] grep: {}: {}", path, FsError::IsDirectory);
+                failed = true;
+            }
+            Err(error) => {
+                let _ = writeln!(io.out, "[Error] grep: {}: {}", path, error);
+                failed = true;
+            }
+        };
+
+        if operands == 0 {
+            search(sh, ".", io);
+        }
+        for path in argv.iter().skip(first + 1) {
+            search(sh, path, io);
+        }
+
+        match found && !failed {
+            true => Ok(()),
+            false => Err(ShellError::Failed),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::{Entry, FsError, LINE_LENGTH};
+use super::Shell;
+
+pub static COMMANDS: [&dyn Command; 5] = [&WordCount, &Head, &Tail, &Sort, &Uniq];
+
+const DEFAULT_COUNT_LINES: usize = 10;
+const MAX_COUNT_SORTED_LINES: usize = 256;
+
+/// Text a filter reads: the content of a file or the output of the command
+/// before it in a pipeline.
+#[derive(Debug, Clone, Copy)]
+pub enum Source<'a> {
+    /// Lines of `LINE_LENGTH` bytes padded with blanks, as files are stored.
+    File(&'a [u8]),
+    /// Lines ending with `\n`.
+    Piped(&'a [u8]),
+}
+
+impl<'a> Source<'a> {
+    pub fn lines(&self) -> Lines<'a> {
+        Lines { source: *self }
+    }
+
+    /// Size in bytes, counting one for the end of every line.
+    pub fn len(&self) -> usize {
+        match self {
+            Source::File(_) => self.lines().map(|line| line.len() + 1).sum(),
+            Source::Piped(text) => text.len(),
+        }
+    }
+}
+
+/// Lines of a `Source` without their end of line or padding.
+pub struct Lines<'a> {
+    source: Source<'a>,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match &mut self.source {
+            Source::File(text) => {
+                if text.is_empty() {
+                    return None;
+                }
+                let (line, rest) = text.split_at(LINE_LENGTH.min(text.len()));
+                *text = rest;
+
+                let is_blank = |b: &u8| *b == b' ' || *b == b'\0';
+                &line[..line.iter().rposition(|b| !is_blank(b)).map_or(0, |end| end + 1)]
+            }
+            Source::Piped(text) => {
+                if text.is_empty() {
+                    return None;
+                }
+                let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+                let line = &text[..end];
+                *text = &text[(end + 1).min(text.len())..];
+                line
+            }
+        };
+
+        Some(core::str::from_utf8(line).unwrap_or(""))
+    }
+}
+
+/// Runs `f` on every file named from `first` on, or on the piped `input`
+/// if no file is named. `f` also gets the path, if the text came from a
+/// file.
+pub fn for_each_input<'a>(
+    sh: &'a Shell,
+    input: Option<&'a [u8]>,
+    argv: &Argv,
+    first: usize,
+    io: &mut Io,
+    mut f: impl FnMut(Source<'a>, Option<&str>, &mut Io),
+) -> Result<(), ShellError> {
+    if argv.len() <= first {
+        let input = input.ok_or(ShellError::Usage)?;
+        f(Source::Piped(input), None, io);
+        return Ok(());
+    }
+
+    let mut failed = false;
+
+    for path in argv.iter().skip(first) {
+        let result = match sh.fs.resolve(sh.current_directory, path) {
+            Ok(Entry::File(index)) => Ok(index),
+            Ok(Entry::Directory(_)) => Err(FsError::IsDirectory),
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(index) => f(Source::File(sh.fs.file(index).text()), Some(path), io),
+            Err(error) => {
+                let _ = writeln!(io.out, "[Error] {}: {}: {}", argv.get(0).unwrap_or(""), path, error);
+                failed = true;
+            }
+        }
+    }
+
+    match failed {
+        true => Err(ShellError::Failed),
+        false => Ok(()),
+    }
+}
+
+/// Reads `-n <count>` in front of the operands, returning the count and
+/// where the operands start.
+fn line_count(argv: &Argv) -> Result<(usize, usize), ShellError> {
+    match argv.get(1) {
+        Some("-n") => {
+            let count = argv.get(2).and_then(|count| count.parse().ok());
+            Ok((count.ok_or(ShellError::Usage)?, 3))
+        }
+        _ => Ok((DEFAULT_COUNT_LINES, 1)),
+    }
+}
+
+/// Prints `==> path <==` above every file when there are several.
+fn print_header(path: Option<&str>, several: bool, is_first: &mut bool, io: &mut Io) {
+    if let (Some(path), true) = (path, several) {
+        if !*is_first {
+            let _ = writeln!(io.out);
+        }
+        let _ = writeln!(io.out, "==> {} <==", path);
+    }
+    *is_first = false;
+}
+
+struct WordCount;
+
+impl Command for WordCount {
+    fn name(&self) -> &'static str {
+        "wc"
+    }
+
+    fn usage(&self) -> &'static str {
+        "wc [-lwc] [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Count lines, words and bytes"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (mut lines, mut words, mut bytes) = (false, false, false);
+        let mut first = 1;
+
+        for arg in argv.args() {
+            if !arg.starts_with('-') || arg.len() < 2 {
+                break;
+            }
+            for flag in arg.bytes().skip(1) {
+                match flag {
+                    b'l' => lines = true,
+                    b'w' => words = true,
+                    b'c' => bytes = true,
+                    _ => return Err(ShellError::Usage),
+                }
+            }
+            first += 1;
+        }
+
+        if !(lines || words || bytes) {
+            (lines, words, bytes) = (true, true, true);
+        }
+
+        let print = |counts: [usize; 3], name: Option<&str>, io: &mut Io| {
+            for (count, _) in counts.iter().zip([lines, words, bytes]).filter(|(_, shown)| *shown) {
+                let _ = write!(io.out, "{:>6} ", count);
+            }
+            let _ = writeln!(io.out, "{}", name.unwrap_or(""));
+        };
+
+        let mut total = [0; 3];
+        let result = for_each_input(sh, io.input, argv, first, io, |source, path, io| {
+            let counts = [
+                source.lines().count(),
+                source.lines().map(|line| line.split_whitespace().count()).sum(),
+                source.len(),
+            ];
+            for (total, count) in total.iter_mut().zip(counts) {
+                *total += count;
+            }
+            print(counts, path, io);
+        });
+
+        if argv.len() > first + 1 {
+            print(total, Some("total"), io);
+        }
+        result
+    }
+}
+
+struct Head;
+
+impl Command for Head {
+    fn name(&self) -> &'static str {
+        "head"
+    }
+
+    fn usage(&self) -> &'static str {
+        "head [-n <count>] [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the first lines"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (count, first) = line_count(argv)?;
+        let several = argv.len() > first + 1;
+        let mut is_first = true;
+
+        for_each_input(sh, io.input, argv, first, io, |source, path, io| {
+            print_header(path, several, &mut is_first, io);
+            for line in source.lines().take(count) {
+                let _ = writeln!(io.out, "{}", line);
+            }
+        })
+    }
+}
+
+struct Tail;
+
+impl Command for Tail {
+    fn name(&self) -> &'static str {
+        "tail"
+    }
+
+    fn usage(&self) -> &'static str {
+        "tail [-n <count>] [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the last lines"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (count, first) = line_count(argv)?;
+        let several = argv.len() > first + 1;
+        let mut is_first = true;
+
+        for_each_input(sh, io.input, argv, first, io, |source, path, io| {
+            print_header(path, several, &mut is_first, io);
+            let skip = source.lines().count().saturating_sub(count);
+            for line in source.lines().skip(skip) {
+                let _ = writeln!(io.out, "{}", line);
+            }
+        })
+    }
+}
+
+struct Sort;
+
+impl Sort {
+    /// Leading number of `line`, lines without one count as zero.
+    fn number(line: &str) -> i64 {
+        let line = line.trim_start();
+        let end = line
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+            .map_or(line.len(), |(i, _)| i);
+        line[..end].parse().unwrap_or(0)
+    }
+}
+
+impl Command for Sort {
+    fn name(&self) -> &'static str {
+        "sort"
+    }
+
+    fn usage(&self) -> &'static str {
+        "sort [-rnu] [path...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print lines sorted, -n by number, -r reversed"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (mut reverse, mut numeric, mut unique) = (false, false, false);
+        let mut first = 1;
+
+        for arg in argv.args() {
+            if !arg.starts_with('-') || arg.len() < 2 {
+                break;
+            }
+            for flag in arg.bytes().skip(1) {
+                match flag {
+                    b'r' => reverse = true,
+                    b'n' => numeric = true,
+                    b'u' => unique = true,
+                    _ => return Err(ShellError::Usage),
+                }
+            }
+            first += 1;
+        }
+
+        // every line with its position, which keeps equal lines in order
+        let mut lines = [("", 0); MAX_COUNT_SORTED_LINES];
+        let mut count = 0;
+
+        let result = for_each_input(sh, io.input, argv, first, io, |source, _, _| {
+            for line in source.lines() {
+                if count < MAX_COUNT_SORTED_LINES {
+                    lines[count] = (line, count);
+                    count += 1;
+                }
+            }
+        });
+
+        let lines = &mut lines[..count];
+        lines.sort_unstable_by(|a, b| {
+            let order = match numeric {
+                true => Self::number(a.0).cmp(&Self::number(b.0)),
+                false => a.0.cmp(b.0),
+            };
+            match reverse {
+                true => order.reverse(),
+                false => order,
+            }
+            .then(a.1.cmp(&b.1))
+        });
+
+        let mut previous = None;
+        for &(line, _) in lines.iter() {
+            if unique && previous == Some(line) {
+                continue;
+            }
+            let _ = writeln!(io.out, "{}", line);
+            previous = Some(line);
+        }
+        result
+    }
+}
+
+struct Uniq;
+
+impl Command for Uniq {
+    fn name(&self) -> &'static str {
+        "uniq"
+    }
+
+    fn usage(&self) -> &'static str {
+        "uniq [-cdu] [path]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print lines without adjacent repeats"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let (mut counts, mut repeated_only, mut unique_only) = (false, false, false);
+        let mut first = 1;
+
+        for arg in argv.args() {
+            if !arg.starts_with('-') || arg.len() < 2 {
+                break;
+            }
+            for flag in arg.bytes().skip(1) {
+                match flag {
+                    b'c' => counts = true,
+                    b'd' => repeated_only = true,
+                    b'u' => unique_only = true,
+                    _ => return Err(ShellError::Usage),
+                }
+            }
+            first += 1;
+        }
+
+        if argv.len() > first + 1 {
+            return Err(ShellError::Usage);
+        }
+
+        let print = |line: &str, count: usize, io: &mut Io| {
+            if (repeated_only && count == 1) || (unique_only && count > 1) {
+                return;
+            }
+            let _ = match counts {
+                true => writeln!(io.out, "{:>6} {}", count, line),
+                false => writeln!(io.out, "{}", line),
+            };
+        };
+
+        for_each_input(sh, io.input, argv, first, io, |source, _, io| {
+            let mut current = None;
+            let mut count = 0;
+
+            for line in source.lines() {
+                if current == Some(line) {
+                    count += 1;
+                    continue;
+                }
+                if let Some(current) = current {
+                    print(current, count, io);
+                }
+                current = Some(line);
+                count = 1;
+            }
+
+            if let Some(current) = current {
+                print(current, count, io);
+            }
+        })
+    }
+}
@@ -0,0 +1,128 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::{Entry, FsError, FILE_SIZE, LINE_LENGTH, MAX_COUNT_LINES};
+use super::text::Source;
+use super::Shell;
+use crate::rtc;
+use crate::xmodem;
+
+pub static COMMANDS: [&dyn Command; 2] = [&Receive, &Send];
+
+/// Room for a file a bit larger than the shell keeps, so that carriage
+/// returns and a cut off tail still fit.
+const RECEIVE_BUFFER_SIZE: usize = 4096;
+/// Text of a file of the shell with every line ended by `\n`.
+const MAX_SIZE_SEND: usize = FILE_SIZE + MAX_COUNT_LINES;
+
+struct Receive;
+
+impl Command for Receive {
+    fn name(&self) -> &'static str {
+        "rx"
+    }
+
+    fn usage(&self) -> &'static str {
+        "rx <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Receive a file over COM1 with XMODEM"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let path = match (argv.get(1), argv.len()) {
+            (Some(path), 2) => path,
+            _ => return Err(ShellError::Usage),
+        };
+
+        // the file is made before the transfer, so that a missing directory
+        // or a full file table is found out before the sender is started
+        let (index, is_new) = match sh.fs.resolve(sh.current_directory, path) {
+            Ok(Entry::File(index)) => (index, false),
+            Ok(Entry::Directory(_)) => return Err(FsError::IsDirectory.into()),
+            Err(_) => {
+                let (directory, name) = sh.fs.resolve_parent(sh.current_directory, path)?;
+                (sh.fs.create_file(directory, name)?, true)
+            }
+        };
+
+        let _ = writeln!(io.out, "Waiting a minute for an XMODEM sender on COM1...");
+        let mut buffer = [0; RECEIVE_BUFFER_SIZE];
+        let received = match xmodem::receive(&mut buffer) {
+            Ok(received) => received,
+            Err(error) => {
+                if is_new {
+                    sh.fs.delete_file(index);
+                }
+                return Err(error.into());
+            }
+        };
+
+        // files sent from DOS or Windows end their lines with `\r\n`
+        let mut len = 0;
+        for i in 0..received.min(buffer.len()) {
+            if buffer[i] != b'\r' {
+                buffer[len] = buffer[i];
+                len += 1;
+            }
+        }
+        let text = &buffer[..len];
+        let lines = text.strip_suffix(b"\n").unwrap_or(text).split(|&b| b == b'\n');
+        let is_cut = received > buffer.len()
+            || lines.enumerate().any(|(number, line)| number >= MAX_COUNT_LINES || line.len() > LINE_LENGTH);
+
+        let file = sh.fs.file_mut(index);
+        file.set_text(text);
+        file.modified = rtc::now();
+
+        let _ = write!(io.out, "Received {} bytes", received);
+        if is_cut {
+            let _ = write!(io.out, ", cut to {} lines of {} characters", MAX_COUNT_LINES, LINE_LENGTH);
+        }
+        let _ = writeln!(io.out);
+        Ok(())
+    }
+}
+
+struct Send;
+
+impl Command for Send {
+    fn name(&self) -> &'static str {
+        "sx"
+    }
+
+    fn usage(&self) -> &'static str {
+        "sx <path>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Send a file, or the piped input, over COM1 with XMODEM"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let mut data = [0; MAX_SIZE_SEND];
+        let mut len = 0;
+
+        // `ls | sx` sends the output of `ls`
+        let data = match (io.input, argv.get(1), argv.len()) {
+            (Some(input), None, 1) => input,
+            (_, Some(path), 2) => {
+                let file = match sh.fs.resolve(sh.current_directory, path)? {
+                    Entry::File(index) => sh.fs.file(index),
+                    Entry::Directory(_) => return Err(FsError::IsDirectory.into()),
+                };
+                for line in Source::File(file.text()).lines() {
+                    data[len..len + line.len()].copy_from_slice(line.as_bytes());
+                    data[len + line.len()] = b'\n';
+                    len += line.len() + 1;
+                }
+                &data[..len]
+            }
+            _ => return Err(ShellError::Usage),
+        };
+
+        let _ = writeln!(io.out, "Waiting a minute for an XMODEM receiver on COM1...");
+        xmodem::send(data)?;
+        let _ = writeln!(io.out, "Sent {} bytes", data.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use super::command::{Argv, Command, Io, ShellError};
+use super::fs::{
+    name_to_str, Entry, FsError, MAX_COUNT_CHILDREN_DIRECTORIES, MAX_COUNT_DIRECTORIES, MAX_COUNT_FILES_IN_FOLDER,
+    MAX_SIZE_DIRECTORY_NAME,
+};
+use super::Shell;
+
+pub static COMMANDS: [&dyn Command; 1] = [&Tree];
+
+const MAX_COUNT_ENTRIES: usize = MAX_COUNT_CHILDREN_DIRECTORIES + MAX_COUNT_FILES_IN_FOLDER;
+
+struct Options {
+    /// How many levels below the start to show, all of them if `None`.
+    max_depth: Option<usize>,
+    all: bool,
+}
+
+/// Number of directories and files printed so far.
+struct Counts {
+    directories: usize,
+    files: usize,
+}
+
+/// Entries of `directory`, directories first, each group sorted by name.
+fn sorted_entries(
+    sh: &Shell,
+    directory: usize,
+    entries: &mut [(bool, [u8; MAX_SIZE_DIRECTORY_NAME], Entry); MAX_COUNT_ENTRIES],
+) -> usize {
+    let directory = sh.fs.directory(directory);
+    let children = directory
+        .children()
+        .map(|index| (false, sh.fs.directory(index).name, Entry::Directory(index)));
+    let files = directory
+        .files()
+        .map(|index| (true, sh.fs.file(index).name, Entry::File(index)));
+
+    let mut count = 0;
+    for entry in children.chain(files).take(MAX_COUNT_ENTRIES) {
+        entries[count] = entry;
+        count += 1;
+    }
+
+    entries[..count].sort_unstable_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    count
+}
+
+/// Prints the entries of `directory` below each other. `is_last[d]` tells
+/// whether the ancestor at depth `d` was the last entry of its directory,
+/// which decides if a `│` still has to be drawn in its column.
+fn print_tree(
+    sh: &Shell,
+    directory: usize,
+    depth: usize,
+    is_last: &mut [bool; MAX_COUNT_DIRECTORIES],
+    options: &Options,
+    counts: &mut Counts,
+    io: &mut Io,
+) {
+    if options.max_depth.map_or(false, |max_depth| depth >= max_depth) || depth >= is_last.len() {
+        return;
+    }
+
+    let mut entries = [(false, [0; MAX_SIZE_DIRECTORY_NAME], Entry::File(0)); MAX_COUNT_ENTRIES];
+    let count = sorted_entries(sh, directory, &mut entries);
+
+    let visible = |entry: &&(bool, [u8; MAX_SIZE_DIRECTORY_NAME], Entry)| {
+        options.all || !name_to_str(&entry.1).starts_with('.')
+    };
+    let visible_count = entries[..count].iter().filter(visible).count();
+
+    for (i, (_, name, entry)) in entries[..count].iter().filter(visible).enumerate() {
+        for &ancestor_is_last in is_last[..depth].iter() {
+            let _ = io.out.write_str(if ancestor_is_last { "    " } else { "│   " });
+        }
+
+        is_last[depth] = i + 1 == visible_count;
+        let _ = io.out.write_str(if is_last[depth] { "└── " } else { "├── " });
+
+        match *entry {
+            Entry::File(_) => {
+                let _ = writeln!(io.out, "{}", name_to_str(name));
+                counts.files += 1;
+            }
+            Entry::Directory(index) => {
+                let _ = writeln!(io.out, "{}/", name_to_str(name));
+                counts.directories += 1;
+                print_tree(sh, index, depth + 1, is_last, options, counts, io);
+            }
+        }
+    }
+}
+
+struct Tree;
+
+impl Command for Tree {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["dirtree"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "tree [-a] [-L <depth>] [path]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show directories and files below a directory"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let mut options = Options {
+            max_depth: None,
+            all: false,
+        };
+        let mut path = None;
+        let mut i = 1;
+
+        while let Some(arg) = argv.get(i) {
+            match arg {
+                "-a" => options.all = true,
+                "-L" => {
+                    i += 1;
+                    let depth = argv.get(i).and_then(|depth| depth.parse().ok());
+                    options.max_depth = Some(depth.filter(|&depth| depth > 0).ok_or(ShellError::Usage)?);
+                }
+                _ if path.is_none() => path = Some(arg),
+                _ => return Err(ShellError::Usage),
+            }
+            i += 1;
+        }
+
+        let directory = match sh.fs.resolve(sh.current_directory, path.unwrap_or("."))? {
+            Entry::Directory(directory) => directory,
+            Entry::File(_) => return Err(FsError::NotDirectory.into()),
+        };
+
+        let mut counts = Counts {
+            directories: 0,
+            files: 0,
+        };
+        let mut is_last = [false; MAX_COUNT_DIRECTORIES];
+
+        let _ = writeln!(io.out, "{}", path.unwrap_or("."));
+        print_tree(sh, directory, 0, &mut is_last, &options, &mut counts, io);
+
+        let _ = writeln!(
+            io.out,
+            "\n{} {}, {} {}",
+            counts.directories,
+            if counts.directories == 1 { "directory" } else { "directories" },
+            counts.files,
+            if counts.files == 1 { "file" } else { "files" }
+        );
+        Ok(())
+    }
+}
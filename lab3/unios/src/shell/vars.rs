@@ -0,0 +1,327 @@
+use core::fmt::{self, Write};
+
+use super::command::{Argv, Buffer, Command, Io, ShellError};
+use super::Shell;
+
+pub static COMMANDS: [&dyn Command; 4] = [&Set, &Unset, &Export, &Env];
+
+const MAX_COUNT_VARIABLES: usize = 32;
+const MAX_SIZE_VARIABLE_NAME: usize = 16;
+const MAX_SIZE_VARIABLE_VALUE: usize = 64;
+
+/// Variables computed by the shell itself rather than stored.
+const READ_ONLY_VARIABLES: [&str; 1] = ["PWD"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarError {
+    InvalidName,
+    ReadOnly,
+    ValueTooLong,
+    NoSpace,
+    /// `${` without its closing `}` or with an invalid name inside.
+    BadSubstitution,
+}
+
+impl fmt::Display for VarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            VarError::InvalidName => "Invalid variable name",
+            VarError::ReadOnly => "Variable is read-only",
+            VarError::ValueTooLong => "The maximum size of a value is 64 characters",
+            VarError::NoSpace => "There is not a free space for variables",
+            VarError::BadSubstitution => "Bad substitution",
+        };
+        f.write_str(message)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Variable {
+    name: [u8; MAX_SIZE_VARIABLE_NAME],
+    name_len: usize,
+    value: [u8; MAX_SIZE_VARIABLE_VALUE],
+    value_len: usize,
+    exported: bool,
+}
+
+impl Variable {
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    fn value(&self) -> &str {
+        core::str::from_utf8(&self.value[..self.value_len]).unwrap_or("")
+    }
+}
+
+/// Shell variables, set with `NAME=value` or `set` and read back with
+/// `$NAME` or `${NAME}`.
+pub struct Variables {
+    variables: [Option<Variable>; MAX_COUNT_VARIABLES],
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut bytes = name.bytes();
+
+    match bytes.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == b'_' => {}
+        _ => return false,
+    }
+
+    name.len() <= MAX_SIZE_VARIABLE_NAME && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Splits `NAME=value` into its parts, if `word` is an assignment at all.
+pub fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+
+    if !is_valid_name(name) {
+        return None;
+    }
+
+    Some((name, value))
+}
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables {
+            variables: [None; MAX_COUNT_VARIABLES],
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.variables
+            .iter()
+            .position(|variable| matches!(variable, Some(variable) if variable.name() == name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name)
+            .and_then(|i| self.variables[i].as_ref())
+            .map(Variable::value)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), VarError> {
+        if !is_valid_name(name) {
+            return Err(VarError::InvalidName);
+        }
+
+        if READ_ONLY_VARIABLES.contains(&name) {
+            return Err(VarError::ReadOnly);
+        }
+
+        if value.len() > MAX_SIZE_VARIABLE_VALUE {
+            return Err(VarError::ValueTooLong);
+        }
+
+        let slot = self
+            .position(name)
+            .or_else(|| self.variables.iter().position(Option::is_none))
+            .ok_or(VarError::NoSpace)?;
+
+        let mut variable = Variable {
+            name: [0; MAX_SIZE_VARIABLE_NAME],
+            name_len: name.len(),
+            value: [0; MAX_SIZE_VARIABLE_VALUE],
+            value_len: value.len(),
+            exported: self.variables[slot].map_or(false, |variable| variable.exported),
+        };
+        variable.name[..name.len()].copy_from_slice(name.as_bytes());
+        variable.value[..value.len()].copy_from_slice(value.as_bytes());
+
+        self.variables[slot] = Some(variable);
+        Ok(())
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        if let Some(slot) = self.position(name) {
+            self.variables[slot] = None;
+        }
+    }
+
+    /// Marks a variable to be listed by `env`, creating it empty if needed.
+    pub fn export(&mut self, name: &str) -> Result<(), VarError> {
+        if self.position(name).is_none() {
+            self.set(name, "")?;
+        }
+
+        if let Some(Some(variable)) = self.position(name).map(|slot| &mut self.variables[slot]) {
+            variable.exported = true;
+        }
+        Ok(())
+    }
+
+    /// Name, value and whether it is exported, for every variable.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, bool)> {
+        self.variables
+            .iter()
+            .flatten()
+            .map(|variable| (variable.name(), variable.value(), variable.exported))
+    }
+}
+
+/// Writes `word` to `out` with every `$NAME` or `${NAME}` replaced by what
+/// `lookup` returns for it. Unknown variables expand to nothing.
+pub fn expand<'a, const N: usize>(
+    word: &str,
+    lookup: impl Fn(&str) -> Option<&'a str>,
+    out: &mut Buffer<N>,
+) -> Result<(), VarError> {
+    let mut rest = word;
+
+    while let Some(dollar) = rest.find('$') {
+        let _ = out.write_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        let (name, len) = unish::variable_name(rest.as_bytes()).map_err(|_| VarError::BadSubstitution)?;
+        if len == 0 {
+            // a lone `$` stays as it is
+            let _ = out.write_str("$");
+            continue;
+        }
+
+        if let Some(value) = lookup(name) {
+            let _ = out.write_str(value);
+        }
+        rest = &rest[len..];
+    }
+
+    let _ = out.write_str(rest);
+    Ok(())
+}
+
+struct Set;
+
+impl Command for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn usage(&self) -> &'static str {
+        "set [name[=value] | name value...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Set a variable or list all of them"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let name = match argv.get(1) {
+            Some(name) => name,
+            None => {
+                for (name, value, _) in sh.vars.iter() {
+                    let _ = writeln!(io.out, "{}={}", name, value);
+                }
+                return Ok(());
+            }
+        };
+
+        if let Some((name, value)) = name.split_once('=') {
+            sh.vars.set(name, value)?;
+            return Ok(());
+        }
+
+        // the remaining words make up the value, separated by single spaces
+        let mut value = Buffer::<MAX_SIZE_VARIABLE_VALUE>::new();
+        for (i, word) in argv.args().skip(1).enumerate() {
+            if i > 0 {
+                let _ = value.write_str(" ");
+            }
+            value.write_str(word).map_err(|_| VarError::ValueTooLong)?;
+        }
+
+        sh.vars.set(name, value.as_str())?;
+        Ok(())
+    }
+}
+
+struct Unset;
+
+impl Command for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn usage(&self) -> &'static str {
+        "unset <name...>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove variables"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, _io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() < 2 {
+            return Err(ShellError::Usage);
+        }
+
+        for name in argv.args() {
+            sh.vars.unset(name);
+        }
+        Ok(())
+    }
+}
+
+struct Export;
+
+impl Command for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn usage(&self) -> &'static str {
+        "export [name[=value]...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Mark variables to be shown by env"
+    }
+
+    fn run(&self, sh: &mut Shell, argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        if argv.len() < 2 {
+            for (name, value, _) in sh.vars.iter().filter(|(_, _, exported)| *exported) {
+                let _ = writeln!(io.out, "export {}={}", name, value);
+            }
+            return Ok(());
+        }
+
+        for arg in argv.args() {
+            let name = match arg.split_once('=') {
+                Some((name, value)) => {
+                    sh.vars.set(name, value)?;
+                    name
+                }
+                None => arg,
+            };
+            sh.vars.export(name)?;
+        }
+        Ok(())
+    }
+}
+
+struct Env;
+
+impl Command for Env {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn usage(&self) -> &'static str {
+        "env"
+    }
+
+    fn help(&self) -> &'static str {
+        "List exported variables"
+    }
+
+    fn run(&self, sh: &mut Shell, _argv: &Argv, io: &mut Io) -> Result<(), ShellError> {
+        let _ = write!(io.out, "PWD=");
+        sh.write_path(sh.current_directory, io.out);
+        let _ = writeln!(io.out);
+
+        for (name, value, _) in sh.vars.iter().filter(|(_, _, exported)| *exported) {
+            let _ = writeln!(io.out, "{}={}", name, value);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+//! Reading archives in the USTAR format, the one `tar` writes by default.
+//! Every entry is a 512-byte header followed by its data, padded to whole
+//! blocks, and two zero blocks end the archive.
+//!
+//! ```text
+//!   0  name (100)       148  checksum (8)     257  magic "ustar"
+//! 100  mode (8)         156  type             345  prefix of the name (155)
+//! 124  size (12)
+//! 136  modified (12)
+//! ```
+//!
+//! Numbers are written as octal text. The checksum is the sum of all bytes
+//! of the header, counting the checksum itself as spaces. The type is `0`
+//! (or a zero byte) for a file and `5` for a directory.
+
+use core::fmt;
+
+pub const BLOCK_SIZE: usize = 512;
+/// The prefix, a slash and the name.
+const MAX_PATH_LEN: usize = 155 + 1 + 100;
+const MAGIC: &[u8] = b"ustar";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarError {
+    /// The archive ends inside an entry.
+    Truncated,
+    /// The header is not a USTAR header.
+    NotUstar,
+    BadChecksum,
+    BadNumber,
+}
+
+impl fmt::Display for TarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            TarError::Truncated => "Archive is truncated",
+            TarError::NotUstar => "Not a USTAR archive",
+            TarError::BadChecksum => "Header checksum does not match",
+            TarError::BadNumber => "Header holds a malformed number",
+        };
+        f.write_str(message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarKind {
+    File,
+    Directory,
+    /// Links, devices and anything else.
+    Other,
+}
+
+pub struct TarEntry<'a> {
+    path: [u8; MAX_PATH_LEN],
+    path_len: usize,
+    pub kind: TarKind,
+    pub data: &'a [u8],
+}
+
+impl TarEntry<'_> {
+    pub fn path(&self) -> &str {
+        core::str::from_utf8(&self.path[..self.path_len]).unwrap_or("")
+    }
+}
+
+/// The entries of an archive in the order they were added. After an error
+/// nothing more is read, the headers behind it can not be found.
+pub struct Archive<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> Archive<'a> {
+    pub fn new(bytes: &'a [u8]) -> Archive<'a> {
+        Archive {
+            bytes,
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    fn read_entry(&mut self, header: &[u8]) -> Result<TarEntry<'a>, TarError> {
+        if &header[257..262] != MAGIC {
+            return Err(TarError::NotUstar);
+        }
+
+        let sum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(offset, &b)| if (148..156).contains(&offset) { b' ' as u64 } else { b as u64 })
+            .sum();
+        if parse_octal(&header[148..156])? != sum {
+            return Err(TarError::BadChecksum);
+        }
+
+        let size = parse_octal(&header[124..136])? as usize;
+        let start = self.offset + BLOCK_SIZE;
+        let data = self.bytes.get(start..start + size).ok_or(TarError::Truncated)?;
+        self.offset = start + (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+
+        let mut entry = TarEntry {
+            path: [0; MAX_PATH_LEN],
+            path_len: 0,
+            kind: match header[156] {
+                b'0' | 0 => TarKind::File,
+                b'5' => TarKind::Directory,
+                _ => TarKind::Other,
+            },
+            data,
+        };
+
+        let prefix = field_str(&header[345..500]);
+        let name = field_str(&header[0..100]);
+        for part in [prefix, if prefix.is_empty() { &[][..] } else { b"/" }, name] {
+            entry.path[entry.path_len..entry.path_len + part.len()].copy_from_slice(part);
+            entry.path_len += part.len();
+        }
+        Ok(entry)
+    }
+}
+
+impl<'a> Iterator for Archive<'a> {
+    type Item = Result<TarEntry<'a>, TarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        // the zero blocks at the end may be missing
+        let bytes = self.bytes;
+        let header = bytes.get(self.offset..self.offset + BLOCK_SIZE)?;
+        if header.iter().all(|&b| b == 0) {
+            return None;
+        }
+
+        let entry = self.read_entry(header);
+        self.failed = entry.is_err();
+        Some(entry)
+    }
+}
+
+/// Bytes of a text field up to the first zero byte.
+fn field_str(field: &[u8]) -> &[u8] {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..end]
+}
+
+/// Octal number of a field, which may be padded with spaces and end with a
+/// zero byte or a space.
+fn parse_octal(field: &[u8]) -> Result<u64, TarError> {
+    let digits = field.iter().skip_while(|&&b| b == b' ').take_while(|&&b| b != 0 && b != b' ');
+
+    digits.fold(Ok(0), |value: Result<u64, TarError>, &b| match b {
+        b'0'..=b'7' => value?
+            .checked_mul(8)
+            .map(|value| value + (b - b'0') as u64)
+            .ok_or(TarError::BadNumber),
+        _ => Err(TarError::BadNumber),
+    })
+}
@@ -60,7 +60,42 @@ impl core::fmt::Write for Screen {
     }
 }
 
+/// Byte of code page 437, the character set of the VGA text mode, that
+/// shows `symbol`. Characters it does not have are shown as a small square.
+fn to_code_page_437(symbol: char) -> u8 {
+    match symbol {
+        symbol if symbol.is_ascii() => symbol as u8,
+        '░' => 0xB0,
+        '▒' => 0xB1,
+        '▓' => 0xB2,
+        '│' => 0xB3,
+        '┤' => 0xB4,
+        '┐' => 0xBF,
+        '└' => 0xC0,
+        '┴' => 0xC1,
+        '┬' => 0xC2,
+        '├' => 0xC3,
+        '─' => 0xC4,
+        '┼' => 0xC5,
+        '┘' => 0xD9,
+        '┌' => 0xDA,
+        '█' => 0xDB,
+        '·' => 0xFA,
+        _ => 0xFE,
+    }
+}
+
 impl Screen {
+    pub fn color(&self) -> u8 {
+        self.color
+    }
+
+    /// Sets the colour of the text printed from now on, the foreground in
+    /// the low nibble and the background in the high one.
+    pub fn set_color(&mut self, color: u8) {
+        self.color = color;
+    }
+
 
     pub fn delete_last_symbol(&mut self, min_index: u32)
     {
@@ -107,8 +142,8 @@ impl Screen {
     }
 
     pub fn print(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
+        for symbol in s.chars() {
+            match to_code_page_437(symbol) {
                 b'\n' => {
                     if self.line == BUF_HEIGHT - 1 {
                         self.scroll_up();
@@ -0,0 +1,341 @@
+//! Disks of QEMU's `-device virtio-blk-pci`, driven through the legacy
+//! virtio interface the device offers in the I/O space of BAR 0.
+//!
+//! Requests go through a single virtqueue as a chain of three descriptors:
+//! a header naming the kind of request and the first sector, the data, and
+//! a status byte the device writes back. The device finds them by their
+//! physical addresses, so the queue, the header and a bounce buffer for the
+//! data live in frames taken from `memory`. Only one request is on its way
+//! at a time.
+//!
+//! While a request is on its way the driver sleeps until the interrupt of
+//! the device arrives. Requests must therefore be made with interrupts on,
+//! which they are everywhere but inside interrupt handlers.
+
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::block::{sectors_of, BlockDevice, BlockError, SECTOR_SIZE};
+use crate::disks;
+use crate::memory::{self, PAGE_SIZE};
+use crate::pci::{Bar, PciAddress, PciDevice, ProbeError};
+
+pub const VENDOR_ID: u16 = 0x1AF4;
+/// Block device of the legacy or transitional interface, modern-only ones
+/// have 0x1042 and no I/O space.
+pub const DEVICE_ID_BLOCK: u16 = 0x1001;
+
+// registers relative to the I/O base
+const REGISTER_DEVICE_FEATURES: u16 = 0x00;
+const REGISTER_GUEST_FEATURES: u16 = 0x04;
+/// Physical frame number of the selected queue.
+const REGISTER_QUEUE_ADDRESS: u16 = 0x08;
+const REGISTER_QUEUE_SIZE: u16 = 0x0C;
+const REGISTER_QUEUE_SELECT: u16 = 0x0E;
+const REGISTER_QUEUE_NOTIFY: u16 = 0x10;
+const REGISTER_DEVICE_STATUS: u16 = 0x12;
+/// Reading it tells whether the device interrupted and clears the line.
+const REGISTER_ISR_STATUS: u16 = 0x13;
+/// Sectors of the disk, the first field of the configuration of a block
+/// device. It moves back by 4 bytes with MSI-X, which is never turned on.
+const REGISTER_CAPACITY: u16 = 0x14;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FAILED: u8 = 0x80;
+const ISR_QUEUE: u8 = 1;
+
+const FEATURE_READ_ONLY: u32 = 1 << 5;
+const FEATURE_FLUSH: u32 = 1 << 9;
+
+const REQUEST_READ: u32 = 0;
+const REQUEST_WRITE: u32 = 1;
+const REQUEST_FLUSH: u32 = 4;
+const REQUEST_OK: u8 = 0;
+/// Written to the status byte before a request, the device replaces it.
+const REQUEST_PENDING: u8 = 0xFF;
+
+const DESCRIPTOR_NEXT: u16 = 1;
+/// The device writes to the buffer instead of reading it.
+const DESCRIPTOR_WRITE: u16 = 2;
+const DESCRIPTOR_SIZE: u64 = 16;
+/// The used ring of a legacy queue starts on the next page after the
+/// available ring.
+const QUEUE_ALIGN: u64 = PAGE_SIZE;
+
+/// Sectors the bounce buffer holds, a request for more is split.
+const BOUNCE_SECTORS: usize = (PAGE_SIZE as usize) / SECTOR_SIZE;
+const HEADER_SIZE: u64 = 16;
+/// Timer ticks the driver sleeps before giving up, about 5 seconds.
+const TIMEOUT_TICKS: u32 = 91;
+
+pub const MAX_COUNT_DEVICES: usize = 4;
+
+/// Interrupt line and I/O base of every device, `io_base << 8 | line`, for
+/// the interrupt handler, which must not wait for the lock on the disks.
+static LINES: [AtomicU32; MAX_COUNT_DEVICES] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+/// Interrupts taken from all devices since boot.
+pub static INTERRUPTS: AtomicU32 = AtomicU32::new(0);
+
+fn align_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+/// Where the available and the used ring start in a queue of `size`
+/// descriptors, and the bytes it takes.
+fn queue_layout(size: u16) -> (u64, u64, u64) {
+    let size = size as u64;
+    let available = DESCRIPTOR_SIZE * size;
+    let used = align_up(available + 6 + 2 * size, QUEUE_ALIGN);
+    (available, used, align_up(used + 6 + 8 * size, QUEUE_ALIGN))
+}
+
+/// A virtio block device with its queue set up.
+pub struct VirtioBlk {
+    pub address: PciAddress,
+    io_base: u16,
+    pub irq: u8,
+    sectors: u64,
+    pub read_only: bool,
+    /// Whether the device keeps written data in a cache of its own that
+    /// needs a flush.
+    can_flush: bool,
+    queue_size: u16,
+    queue: VirtAddr,
+    /// Header and status byte of the request, followed by the bounce buffer
+    /// on the next page.
+    request: VirtAddr,
+    request_physical: PhysAddr,
+    /// Value of the index of the available ring, which the device copies to
+    /// the used ring once it is done.
+    next_index: u16,
+}
+
+impl VirtioBlk {
+    /// Resets `device` and sets up its queue. `slot` tells the interrupt
+    /// handler where to find the device.
+    pub fn new(device: &PciDevice, slot: usize) -> Result<VirtioBlk, ProbeError> {
+        // the legacy interface lives in I/O space only
+        let io_base = match device.bars[0] {
+            Bar::Io { port, .. } => port,
+            _ => return Err(ProbeError::Unsupported),
+        };
+        device.address.enable_io_and_bus_master();
+
+        let mut disk = VirtioBlk {
+            address: device.address,
+            io_base,
+            irq: device.interrupt_line,
+            sectors: 0,
+            read_only: false,
+            can_flush: false,
+            queue_size: 0,
+            queue: VirtAddr::zero(),
+            request: VirtAddr::zero(),
+            request_physical: PhysAddr::zero(),
+            next_index: 0,
+        };
+
+        disk.write_u8(REGISTER_DEVICE_STATUS, 0);
+        disk.write_u8(REGISTER_DEVICE_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        let features = disk.read_u32(REGISTER_DEVICE_FEATURES);
+        disk.write_u32(REGISTER_GUEST_FEATURES, features & (FEATURE_READ_ONLY | FEATURE_FLUSH));
+        disk.read_only = features & FEATURE_READ_ONLY != 0;
+        disk.can_flush = features & FEATURE_FLUSH != 0;
+
+        if let Err(error) = disk.set_up_queue() {
+            disk.write_u8(REGISTER_DEVICE_STATUS, STATUS_FAILED);
+            return Err(error);
+        }
+
+        disk.sectors = disk.read_u32(REGISTER_CAPACITY) as u64 | (disk.read_u32(REGISTER_CAPACITY + 4) as u64) << 32;
+        LINES[slot].store((disk.io_base as u32) << 8 | disk.irq as u32, Ordering::Relaxed);
+        disk.write_u8(REGISTER_DEVICE_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK);
+        Ok(disk)
+    }
+
+    /// Hands queue 0 and the memory it needs to the device, together with
+    /// two pages for the request and the bounce buffer.
+    fn set_up_queue(&mut self) -> Result<(), ProbeError> {
+        let mut select: Port<u16> = Port::new(self.io_base + REGISTER_QUEUE_SELECT);
+        unsafe { select.write(0) }
+        let mut size: Port<u16> = Port::new(self.io_base + REGISTER_QUEUE_SIZE);
+        self.queue_size = unsafe { size.read() };
+        if self.queue_size == 0 {
+            return Err(ProbeError::DeviceError);
+        }
+
+        let (_, _, queue_bytes) = queue_layout(self.queue_size);
+        let frames = memory::allocate_frames(queue_bytes / PAGE_SIZE + 2).ok_or(ProbeError::NoMemory)?;
+        self.queue = memory::phys_to_virt(frames);
+        self.request_physical = frames + queue_bytes;
+        self.request = memory::phys_to_virt(self.request_physical);
+
+        self.write_u32(REGISTER_QUEUE_ADDRESS, (frames.as_u64() / PAGE_SIZE) as u32);
+        Ok(())
+    }
+
+    fn read_u32(&self, register: u16) -> u32 {
+        let mut port: Port<u32> = Port::new(self.io_base + register);
+        unsafe { port.read() }
+    }
+
+    fn write_u32(&self, register: u16, value: u32) {
+        let mut port: Port<u32> = Port::new(self.io_base + register);
+        unsafe { port.write(value) }
+    }
+
+    fn write_u8(&self, register: u16, value: u8) {
+        let mut port: Port<u8> = Port::new(self.io_base + register);
+        unsafe { port.write(value) }
+    }
+
+    fn bounce(&mut self) -> &mut [u8] {
+        let bounce = (self.request + PAGE_SIZE).as_mut_ptr::<u8>();
+        unsafe { core::slice::from_raw_parts_mut(bounce, BOUNCE_SECTORS * SECTOR_SIZE) }
+    }
+
+    fn set_descriptor(&mut self, index: u16, address: PhysAddr, len: usize, flags: u16) {
+        let descriptor = self.queue + DESCRIPTOR_SIZE * index as u64;
+        let next = match flags & DESCRIPTOR_NEXT {
+            0 => 0,
+            _ => index + 1,
+        };
+
+        unsafe {
+            write_volatile(descriptor.as_mut_ptr::<u64>(), address.as_u64());
+            write_volatile((descriptor + 8u64).as_mut_ptr::<u32>(), len as u32);
+            write_volatile((descriptor + 12u64).as_mut_ptr::<u16>(), flags);
+            write_volatile((descriptor + 14u64).as_mut_ptr::<u16>(), next);
+        }
+    }
+
+    /// Sends a request for the first `len` bytes of the bounce buffer and
+    /// waits until the device is done with it.
+    fn send(&mut self, kind: u32, lba: u64, len: usize) -> Result<(), BlockError> {
+        let header = self.request;
+        let status = self.request + HEADER_SIZE;
+        unsafe {
+            write_volatile(header.as_mut_ptr::<u32>(), kind);
+            write_volatile((header + 4u64).as_mut_ptr::<u32>(), 0);
+            write_volatile((header + 8u64).as_mut_ptr::<u64>(), lba);
+            write_volatile(status.as_mut_ptr::<u8>(), REQUEST_PENDING);
+        }
+
+        let header_physical = self.request_physical;
+        let status_physical = self.request_physical + HEADER_SIZE;
+        self.set_descriptor(0, header_physical, HEADER_SIZE as usize, DESCRIPTOR_NEXT);
+        let status_index = match len {
+            0 => 1,
+            _ => {
+                let flags = match kind {
+                    REQUEST_READ => DESCRIPTOR_NEXT | DESCRIPTOR_WRITE,
+                    _ => DESCRIPTOR_NEXT,
+                };
+                self.set_descriptor(1, self.request_physical + PAGE_SIZE, len, flags);
+                2
+            }
+        };
+        self.set_descriptor(status_index, status_physical, 1, DESCRIPTOR_WRITE);
+
+        // the chain starting at descriptor 0 goes into the available ring,
+        // then its index tells the device there is something new
+        let (available, used, _) = queue_layout(self.queue_size);
+        let slot = self.next_index % self.queue_size;
+        self.next_index = self.next_index.wrapping_add(1);
+        unsafe {
+            write_volatile((self.queue + available + 4u64 + 2 * slot as u64).as_mut_ptr::<u16>(), 0);
+            fence(Ordering::SeqCst);
+            write_volatile((self.queue + available + 2u64).as_mut_ptr::<u16>(), self.next_index);
+            fence(Ordering::SeqCst);
+        }
+        let mut notify: Port<u16> = Port::new(self.io_base + REGISTER_QUEUE_NOTIFY);
+        unsafe { notify.write(0) }
+
+        self.wait((self.queue + used + 2u64).as_ptr::<u16>())?;
+        match unsafe { read_volatile(status.as_ptr::<u8>()) } {
+            REQUEST_OK => Ok(()),
+            _ => Err(BlockError::DeviceError),
+        }
+    }
+
+    /// Waits until the index of the used ring at `used_index` has caught up
+    /// with the available ring.
+    fn wait(&self, used_index: *const u16) -> Result<(), BlockError> {
+        let is_done = || unsafe { read_volatile(used_index) } == self.next_index;
+
+        let start = disks::ticks();
+        while disks::ticks().wrapping_sub(start) < TIMEOUT_TICKS {
+            // an interrupt arriving between the check and `hlt` would be
+            // missed, so they are only allowed again together
+            interrupts::disable();
+            if is_done() {
+                interrupts::enable();
+                fence(Ordering::SeqCst);
+                return Ok(());
+            }
+            interrupts::enable_and_hlt();
+        }
+        Err(BlockError::Timeout)
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn sector_count(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+
+        for (i, chunk) in buffer.chunks_mut(BOUNCE_SECTORS * SECTOR_SIZE).enumerate() {
+            self.send(REQUEST_READ, lba + (i * BOUNCE_SECTORS) as u64, chunk.len())?;
+            chunk.copy_from_slice(&self.bounce()[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        sectors_of(self, lba, buffer)?;
+        if self.read_only {
+            return Err(BlockError::DeviceError);
+        }
+
+        for (i, chunk) in buffer.chunks(BOUNCE_SECTORS * SECTOR_SIZE).enumerate() {
+            self.bounce()[..chunk.len()].copy_from_slice(chunk);
+            self.send(REQUEST_WRITE, lba + (i * BOUNCE_SECTORS) as u64, chunk.len())?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        match self.can_flush {
+            true => self.send(REQUEST_FLUSH, 0, 0),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Called on the interrupt at `irq`, tells the devices on that line their
+/// interrupt was taken. Lines are shared, so every device on it is asked.
+pub fn on_interrupt(irq: u8) {
+    for line in LINES.iter().map(|line| line.load(Ordering::Relaxed)).filter(|&line| line != 0) {
+        if line as u8 != irq {
+            continue;
+        }
+
+        let mut isr: Port<u8> = Port::new((line >> 8) as u16 + REGISTER_ISR_STATUS);
+        if unsafe { isr.read() } & ISR_QUEUE != 0 {
+            INTERRUPTS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
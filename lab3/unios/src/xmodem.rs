@@ -0,0 +1,261 @@
+//! XMODEM over COM1, with a CRC-16 and, for senders that do not know it,
+//! with the older 8-bit checksum.
+//!
+//! A block is SOH or STX, its number, the complement of the number, 128 or
+//! 1024 bytes of data and the CRC or checksum. The receiver asks for the
+//! first block with `C` for a CRC or NAK for a checksum, then answers ACK or
+//! NAK to every block and ACK to the EOT ending the file. The last block is
+//! padded with SUB bytes, which the receiver strips again. Two CAN in a row
+//! cancel the transfer from either side.
+
+use core::fmt;
+
+use crate::serial;
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1A;
+const CRC_REQUEST: u8 = b'C';
+
+const BLOCK_SIZE: usize = 128;
+const BLOCK_SIZE_1K: usize = 1024;
+/// Times the first block is asked for, 3 seconds apart, giving the user a
+/// minute to start the sender. The last ones ask for a checksum.
+const START_ATTEMPTS: usize = 20;
+const CRC_START_ATTEMPTS: usize = 15;
+const START_TIMEOUT_SECONDS: u32 = 3;
+/// How long `send` waits for the receiver to ask for the first block.
+const SEND_START_SECONDS: usize = 60;
+const BLOCK_TIMEOUT_SECONDS: u32 = 10;
+const BYTE_TIMEOUT_SECONDS: u32 = 1;
+/// Errors in a row after which the transfer is given up.
+const MAX_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmodemError {
+    NoSerialPort,
+    /// The other side did not answer in time.
+    Timeout,
+    Cancelled,
+    /// The same block went wrong too often.
+    TooManyErrors,
+    /// The sender skipped a block.
+    OutOfOrder,
+}
+
+impl fmt::Display for XmodemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            XmodemError::NoSerialPort => "There is no COM1",
+            XmodemError::Timeout => "The other side does not answer",
+            XmodemError::Cancelled => "Cancelled by the other side",
+            XmodemError::TooManyErrors => "Too many errors on the line",
+            XmodemError::OutOfOrder => "Blocks arrived out of order",
+        };
+        f.write_str(message)
+    }
+}
+
+/// CRC-16 with the polynomial 0x1021 and 0 to start with, as XMODEM uses it.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => crc << 1 ^ 0x1021,
+        })
+    })
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+fn cancel() {
+    serial::write_bytes(&[CAN, CAN, CAN]);
+}
+
+/// Waits for the line to fall silent, so that the rest of a broken block is
+/// not taken for the start of the next one.
+fn purge() {
+    while serial::read_byte(BYTE_TIMEOUT_SECONDS).is_some() {}
+}
+
+/// Reads the rest of a block after its SOH or STX, as many data bytes as
+/// `data` holds, returning its number if it arrived intact.
+fn read_block(data: &mut [u8], use_crc: bool) -> Option<u8> {
+    let number = serial::read_byte(BYTE_TIMEOUT_SECONDS)?;
+    let complement = serial::read_byte(BYTE_TIMEOUT_SECONDS)?;
+    for byte in data.iter_mut() {
+        *byte = serial::read_byte(BYTE_TIMEOUT_SECONDS)?;
+    }
+
+    let is_intact = match use_crc {
+        true => {
+            let high = serial::read_byte(BYTE_TIMEOUT_SECONDS)?;
+            let low = serial::read_byte(BYTE_TIMEOUT_SECONDS)?;
+            u16::from_be_bytes([high, low]) == crc16(data)
+        }
+        false => serial::read_byte(BYTE_TIMEOUT_SECONDS)? == checksum(data),
+    };
+
+    match is_intact && number == !complement {
+        true => Some(number),
+        false => None,
+    }
+}
+
+/// Receives a file into `buffer`. Returns how many bytes the sender sent,
+/// which may be more than `buffer` holds; the rest is dropped. The padding
+/// of the last block is removed.
+pub fn receive(buffer: &mut [u8]) -> Result<usize, XmodemError> {
+    if !serial::is_present() {
+        return Err(XmodemError::NoSerialPort);
+    }
+    serial::drain();
+
+    let mut use_crc = true;
+    let mut header = None;
+    for attempt in 0..START_ATTEMPTS {
+        use_crc = attempt < CRC_START_ATTEMPTS;
+        serial::write_byte(if use_crc { CRC_REQUEST } else { NAK });
+        header = serial::read_byte(START_TIMEOUT_SECONDS);
+        if header.is_some() {
+            break;
+        }
+    }
+    if header.is_none() {
+        cancel();
+        return Err(XmodemError::Timeout);
+    }
+
+    let mut block = [0; BLOCK_SIZE_1K];
+    let mut len = 0;
+    let mut expected: u8 = 1;
+    let mut errors = 0;
+
+    loop {
+        let answer = match header.take().or_else(|| serial::read_byte(BLOCK_TIMEOUT_SECONDS)) {
+            Some(EOT) => {
+                serial::write_byte(ACK);
+                break;
+            }
+            Some(CAN) if serial::read_byte(BYTE_TIMEOUT_SECONDS) == Some(CAN) => return Err(XmodemError::Cancelled),
+            Some(start @ (SOH | STX)) => {
+                let data = &mut block[..if start == SOH { BLOCK_SIZE } else { BLOCK_SIZE_1K }];
+                match read_block(data, use_crc) {
+                    Some(number) if number == expected => {
+                        let stored = len.min(buffer.len());
+                        let count = data.len().min(buffer.len() - stored);
+                        buffer[stored..stored + count].copy_from_slice(&data[..count]);
+                        len += data.len();
+                        expected = expected.wrapping_add(1);
+                        errors = 0;
+                        ACK
+                    }
+                    // the sender missed the ACK of the last block
+                    Some(number) if number == expected.wrapping_sub(1) => ACK,
+                    Some(_) => {
+                        cancel();
+                        return Err(XmodemError::OutOfOrder);
+                    }
+                    None => {
+                        errors += 1;
+                        purge();
+                        NAK
+                    }
+                }
+            }
+            _ => {
+                errors += 1;
+                purge();
+                NAK
+            }
+        };
+
+        if errors == MAX_ERRORS {
+            cancel();
+            return Err(XmodemError::TooManyErrors);
+        }
+        serial::write_byte(answer);
+    }
+
+    if len <= buffer.len() {
+        while len > 0 && buffer[len - 1] == SUB {
+            len -= 1;
+        }
+    }
+    Ok(len)
+}
+
+/// Waits for the receiver to ask for the first block, telling whether it
+/// wants a CRC.
+fn wait_for_receiver() -> Result<bool, XmodemError> {
+    for _ in 0..SEND_START_SECONDS {
+        match serial::read_byte(BYTE_TIMEOUT_SECONDS) {
+            Some(CRC_REQUEST) => return Ok(true),
+            Some(NAK) => return Ok(false),
+            Some(CAN) if serial::read_byte(BYTE_TIMEOUT_SECONDS) == Some(CAN) => return Err(XmodemError::Cancelled),
+            _ => {}
+        }
+    }
+    Err(XmodemError::Timeout)
+}
+
+/// Sends `bytes` with the start `start`, e.g. a block or EOT, until the
+/// receiver answers ACK.
+fn send_until_acknowledged(start: u8, bytes: &[u8]) -> Result<(), XmodemError> {
+    for _ in 0..MAX_ERRORS {
+        serial::write_byte(start);
+        serial::write_bytes(bytes);
+
+        match serial::read_byte(BLOCK_TIMEOUT_SECONDS) {
+            Some(ACK) => return Ok(()),
+            Some(CAN) if serial::read_byte(BYTE_TIMEOUT_SECONDS) == Some(CAN) => return Err(XmodemError::Cancelled),
+            // a NAK, noise or nothing, the block goes again
+            _ => serial::drain(),
+        }
+    }
+    cancel();
+    Err(XmodemError::TooManyErrors)
+}
+
+/// Sends `data` in blocks of 128 bytes.
+pub fn send(data: &[u8]) -> Result<(), XmodemError> {
+    if !serial::is_present() {
+        return Err(XmodemError::NoSerialPort);
+    }
+    serial::drain();
+
+    let use_crc = wait_for_receiver()?;
+    // more requests may have piled up while the user started the receiver
+    serial::drain();
+
+    for (index, chunk) in data.chunks(BLOCK_SIZE).enumerate() {
+        let number = (index + 1) as u8;
+        let mut block = [0; 2 + BLOCK_SIZE + 2];
+        block[0] = number;
+        block[1] = !number;
+        let data = &mut block[2..2 + BLOCK_SIZE];
+        data.fill(SUB);
+        data[..chunk.len()].copy_from_slice(chunk);
+
+        let len = match use_crc {
+            true => {
+                let crc = crc16(data);
+                block[2 + BLOCK_SIZE..].copy_from_slice(&crc.to_be_bytes());
+                block.len()
+            }
+            false => {
+                block[2 + BLOCK_SIZE] = checksum(data);
+                block.len() - 1
+            }
+        };
+        send_until_acknowledged(SOH, &block[..len])?;
+    }
+
+    send_until_acknowledged(EOT, &[])
+}
@@ -0,0 +1,151 @@
+use core::fmt;
+
+pub const MAX_COUNT_ARGS: usize = 16;
+const ARGS_BUF_SIZE: usize = 256;
+
+/// Arguments of a single command. `argv[0]` is the command name.
+pub struct Argv {
+    buf: [u8; ARGS_BUF_SIZE],
+    buf_len: usize,
+    args: [(usize, usize); MAX_COUNT_ARGS],
+    /// Which arguments contain unquoted wildcards.
+    patterns: [bool; MAX_COUNT_ARGS],
+    count: usize,
+}
+
+impl Argv {
+    pub fn new() -> Argv {
+        Argv {
+            buf: [0; ARGS_BUF_SIZE],
+            buf_len: 0,
+            args: [(0, 0); MAX_COUNT_ARGS],
+            patterns: [false; MAX_COUNT_ARGS],
+            count: 0,
+        }
+    }
+
+    /// Appends an argument, dropping it if the buffer is full.
+    pub fn push(&mut self, arg: &[u8]) -> bool {
+        self.push_word(arg, false)
+    }
+
+    /// Appends an argument that has unquoted wildcards in it and is still
+    /// to be matched against file names.
+    pub fn push_pattern(&mut self, arg: &[u8]) -> bool {
+        self.push_word(arg, true)
+    }
+
+    fn push_word(&mut self, arg: &[u8], is_pattern: bool) -> bool {
+        if self.count == MAX_COUNT_ARGS || self.buf_len + arg.len() > ARGS_BUF_SIZE {
+            return false;
+        }
+
+        self.buf[self.buf_len..self.buf_len + arg.len()].copy_from_slice(arg);
+        self.args[self.count] = (self.buf_len, arg.len());
+        self.patterns[self.count] = is_pattern;
+        self.buf_len += arg.len();
+        self.count += 1;
+        true
+    }
+
+    pub fn is_pattern(&self, index: usize) -> bool {
+        index < self.count && self.patterns[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        if index >= self.count {
+            return None;
+        }
+
+        let (start, len) = self.args[index];
+        core::str::from_utf8(&self.buf[start..start + len]).ok()
+    }
+
+    /// All arguments, starting with the command name.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        (0..self.count).filter_map(move |i| self.get(i))
+    }
+
+    /// Arguments after the command name.
+    pub fn args(&self) -> impl Iterator<Item = &str> {
+        self.iter().skip(1)
+    }
+}
+
+impl Default for Argv {
+    fn default() -> Argv {
+        Argv::new()
+    }
+}
+
+/// Collects text in a fixed-size buffer, dropping whatever does not fit.
+pub struct Buffer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Buffer<N> {
+    pub fn new() -> Buffer<N> {
+        Buffer { buf: [0; N], len: 0 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(self.as_bytes()).unwrap_or("")
+    }
+
+    /// Appends a single byte, dropping it if the buffer is full.
+    pub fn push(&mut self, byte: u8) {
+        if self.len < N {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Drops everything after the first `len` bytes.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+impl<const N: usize> Default for Buffer<N> {
+    fn default() -> Buffer<N> {
+        Buffer::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for Buffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.len().min(N - self.len);
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+
+        if len < s.len() {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
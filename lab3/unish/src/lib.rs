@@ -0,0 +1,315 @@
+//! The command line syntax of the unios shell: splitting a line into
+//! commands and pipeline stages and a command into words.
+//!
+//! The kernel uses this crate, which only needs `core`, and it builds for
+//! the host so that the parser is tested with plain `cargo test`.
+
+#![no_std]
+
+pub mod argv;
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+
+use argv::{Argv, Buffer};
+
+const MAX_SIZE_WORD: usize = 128;
+
+/// How a command is joined to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Separator {
+    /// `;` or the start of the line: always run.
+    Sequence,
+    /// `&&`: run if the previous command succeeded.
+    And,
+    /// `||`: run if the previous command failed.
+    Or,
+}
+
+impl Separator {
+    pub fn should_run(&self, last_status: u8) -> bool {
+        match self {
+            Separator::Sequence => true,
+            Separator::And => last_status == 0,
+            Separator::Or => last_status != 0,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Separator::Sequence => ";",
+            Separator::And => "&&",
+            Separator::Or => "||",
+        }
+    }
+}
+
+/// Splits a command line on `;`, `&&` and `||`, yielding every command
+/// together with the separator in front of it.
+pub struct CommandList<'a> {
+    line: &'a [u8],
+    separator: Separator,
+}
+
+impl<'a> CommandList<'a> {
+    pub fn new(line: &'a [u8]) -> CommandList<'a> {
+        CommandList {
+            line,
+            separator: Separator::Sequence,
+        }
+    }
+
+    /// The commands of `line`, the first one joined by `separator` to a
+    /// command that ran before.
+    pub fn after(separator: Separator, line: &'a [u8]) -> CommandList<'a> {
+        CommandList { line, separator }
+    }
+
+    /// The commands not yielded yet and the separator in front of them.
+    pub fn rest(&self) -> (Separator, &'a [u8]) {
+        (self.separator, self.line)
+    }
+}
+
+impl<'a> Iterator for CommandList<'a> {
+    type Item = (Separator, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.line.is_empty() {
+            return None;
+        }
+
+        let mut end = 0;
+        let mut next = (self.line.len(), Separator::Sequence);
+        let mut quote = None;
+
+        while end < self.line.len() {
+            let rest = &self.line[end..];
+
+            match (quote, rest[0]) {
+                (None, b'\'') | (None, b'"') => quote = Some(rest[0]),
+                (Some(q), symbol) if q == symbol => quote = None,
+                // `tokenize` also takes `\"` inside double quotes literally
+                (None | Some(b'"'), b'\\') if end + 1 < self.line.len() => end += 1,
+                _ => {}
+            }
+
+            if quote.is_some() {
+                end += 1;
+                continue;
+            }
+
+            if rest.starts_with(b"&&") {
+                next = (end + 2, Separator::And);
+                break;
+            }
+            if rest.starts_with(b"||") {
+                next = (end + 2, Separator::Or);
+                break;
+            }
+            if rest[0] == b';' {
+                next = (end + 1, Separator::Sequence);
+                break;
+            }
+            end += 1;
+        }
+
+        let item = (self.separator, &self.line[..end]);
+        self.line = &self.line[next.0..];
+        self.separator = next.1;
+        Some(item)
+    }
+}
+
+/// Splits a command on `|` into the stages of a pipeline.
+pub struct Pipeline<'a> {
+    line: Option<&'a [u8]>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(line: &'a [u8]) -> Pipeline<'a> {
+        Pipeline { line: Some(line) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.line.is_none()
+    }
+}
+
+impl<'a> Iterator for Pipeline<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.line?;
+        let mut quote = None;
+        let mut i = 0;
+
+        while i < line.len() {
+            match (quote, line[i]) {
+                (None, b'\'') | (None, b'"') => quote = Some(line[i]),
+                (Some(q), symbol) if q == symbol => quote = None,
+                (None | Some(b'"'), b'\\') => i += 1,
+                (None, b'|') => {
+                    self.line = Some(&line[i + 1..]);
+                    return Some(&line[..i]);
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        self.line = None;
+        Some(line)
+    }
+}
+
+/// Splits a single command into words. Words are separated by spaces,
+/// `'...'` keeps its content as it is, `"..."` still expands variables
+/// and `\` takes the next character literally. `$NAME` and `${NAME}` are
+/// replaced by what `lookup` returns; outside of quotes their value is
+/// split into words again.
+pub fn tokenize<'a>(command: &[u8], lookup: impl Fn(&str) -> Option<&'a str>) -> Result<Argv, ParseError> {
+    let mut argv = Argv::new();
+    let mut word = Word {
+        text: Buffer::new(),
+        in_word: false,
+        is_pattern: false,
+    };
+    let mut quote = None;
+    let mut i = 0;
+
+    let mut finish_word = |word: &mut Word| {
+        let pushed = match (word.in_word, word.is_pattern) {
+            (false, _) => true,
+            (true, false) => argv.push(word.text.as_bytes()),
+            (true, true) => argv.push_pattern(word.text.as_bytes()),
+        };
+        if !pushed {
+            return Err(ParseError::TooManyArguments);
+        }
+
+        word.text.clear();
+        word.in_word = false;
+        word.is_pattern = false;
+        Ok(())
+    };
+
+    while i < command.len() {
+        let symbol = command[i];
+
+        match (quote, symbol) {
+            (None, b' ') | (None, b'\0') => finish_word(&mut word)?,
+            (None, b'\'') | (None, b'"') => {
+                quote = Some(symbol);
+                word.in_word = true;
+            }
+            (Some(q), _) if q == symbol => quote = None,
+            (Some(b'\''), _) => word.push(symbol, quote)?,
+            (_, b'\\') if i + 1 < command.len() => {
+                i += 1;
+                word.push(command[i], Some(b'\\'))?;
+            }
+            (_, b'$') => {
+                let (name, len) = variable_name(&command[i + 1..])?;
+
+                if len == 0 {
+                    word.push(b'$', quote)?;
+                } else {
+                    for &byte in lookup(name).unwrap_or("").as_bytes() {
+                        if byte == b' ' && quote.is_none() {
+                            finish_word(&mut word)?;
+                        } else {
+                            word.push(byte, quote)?;
+                        }
+                    }
+                    i += len;
+                }
+            }
+            _ => word.push(symbol, quote)?,
+        }
+
+        i += 1;
+    }
+
+    if quote.is_some() {
+        return Err(ParseError::UnterminatedQuote);
+    }
+
+    finish_word(&mut word)?;
+    Ok(argv)
+}
+
+/// Reads the variable name following a `$`: `NAME`, `{NAME}` or `?`.
+/// Returns the name and how many bytes it took, zero if there is no name.
+pub fn variable_name(rest: &[u8]) -> Result<(&str, usize), ParseError> {
+    let name_len = |bytes: &[u8]| match bytes.first() {
+        Some(b'?') => 1,
+        Some(b) if b.is_ascii_alphabetic() || *b == b'_' => bytes
+            .iter()
+            .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+            .unwrap_or(bytes.len()),
+        _ => 0,
+    };
+    let as_str = |bytes| core::str::from_utf8(bytes).map_err(|_| ParseError::BadSubstitution);
+
+    if rest.first() == Some(&b'{') {
+        let len = name_len(&rest[1..]);
+        if len == 0 || rest.get(len + 1) != Some(&b'}') {
+            return Err(ParseError::BadSubstitution);
+        }
+        return Ok((as_str(&rest[1..len + 1])?, len + 2));
+    }
+
+    let len = name_len(rest);
+    Ok((as_str(&rest[..len])?, len))
+}
+
+pub fn is_wildcard(symbol: u8) -> bool {
+    symbol == b'*' || symbol == b'?' || symbol == b'['
+}
+
+/// The word `tokenize` is currently collecting.
+struct Word {
+    text: Buffer<MAX_SIZE_WORD>,
+    in_word: bool,
+    /// Whether the word has a wildcard that was neither quoted nor escaped.
+    is_pattern: bool,
+}
+
+impl Word {
+    fn push(&mut self, symbol: u8, quote: Option<u8>) -> Result<(), ParseError> {
+        if self.text.len() == MAX_SIZE_WORD {
+            return Err(ParseError::TooLong);
+        }
+
+        self.text.push(symbol);
+        self.in_word = true;
+        self.is_pattern |= quote.is_none() && is_wildcard(symbol);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedQuote,
+    /// A `|` with nothing before or after it.
+    MissingCommand,
+    TooManyArguments,
+    /// A word longer than `MAX_SIZE_WORD`, e.g. after expanding a variable.
+    TooLong,
+    /// A `${` without a name or the closing `}`.
+    BadSubstitution,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote => f.write_str("Quote is not closed"),
+            ParseError::MissingCommand => f.write_str("Missing command around |"),
+            ParseError::TooManyArguments => f.write_str("Too many arguments"),
+            ParseError::TooLong => write!(f, "Word is longer than {} characters", MAX_SIZE_WORD),
+            ParseError::BadSubstitution => f.write_str("Bad substitution"),
+        }
+    }
+}
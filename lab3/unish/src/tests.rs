@@ -0,0 +1,133 @@
+extern crate std;
+
+use std::string::String;
+use std::vec::Vec;
+
+use super::argv::MAX_COUNT_ARGS;
+use super::*;
+
+fn lookup(name: &str) -> Option<&'static str> {
+    match name {
+        "?" => Some("0"),
+        "X" => Some("1 2"),
+        _ => None,
+    }
+}
+
+fn commands(line: &str) -> Vec<(Separator, &str)> {
+    CommandList::new(line.as_bytes())
+        .map(|(separator, command)| (separator, core::str::from_utf8(command).unwrap()))
+        .collect()
+}
+
+fn stages(line: &str) -> Vec<&str> {
+    Pipeline::new(line.as_bytes())
+        .map(|stage| core::str::from_utf8(stage).unwrap())
+        .collect()
+}
+
+fn words(line: &str) -> Result<Vec<String>, ParseError> {
+    let argv = tokenize(line.as_bytes(), lookup)?;
+    Ok(argv.iter().map(String::from).collect())
+}
+
+#[test]
+fn command_list_splits_on_separators() {
+    assert_eq!(
+        commands("a; b && c || d"),
+        [
+            (Separator::Sequence, "a"),
+            (Separator::Sequence, " b "),
+            (Separator::And, " c "),
+            (Separator::Or, " d"),
+        ]
+    );
+    assert_eq!(commands(""), []);
+}
+
+#[test]
+fn command_list_keeps_separators_in_quotes() {
+    assert_eq!(commands("echo 'a;b' \"c && d\""), [(Separator::Sequence, "echo 'a;b' \"c && d\"")]);
+    assert_eq!(commands("echo a\\;b"), [(Separator::Sequence, "echo a\\;b")]);
+}
+
+#[test]
+fn command_list_skips_escaped_quotes_in_double_quotes() {
+    assert_eq!(
+        commands("test \"a\\\";b\" = x; echo"),
+        [(Separator::Sequence, "test \"a\\\";b\" = x"), (Separator::Sequence, " echo")]
+    );
+}
+
+#[test]
+fn command_list_continues_after_its_rest() {
+    let mut list = CommandList::new(b"rm -r a && echo done; ls");
+    list.next();
+    let (separator, rest) = list.rest();
+
+    assert_eq!(separator, Separator::And);
+    assert_eq!(rest, b" echo done; ls");
+    let rest: Vec<_> = CommandList::after(separator, rest).map(|(separator, _)| separator).collect();
+    assert_eq!(rest, [Separator::And, Separator::Sequence]);
+}
+
+#[test]
+fn pipeline_splits_on_bars() {
+    assert_eq!(stages("ls | sort -r | head"), ["ls ", " sort -r ", " head"]);
+    assert_eq!(stages("ls"), ["ls"]);
+}
+
+#[test]
+fn pipeline_keeps_quoted_and_escaped_bars() {
+    assert_eq!(stages("echo 'a|b' x\\|y"), ["echo 'a|b' x\\|y"]);
+    assert_eq!(stages("echo \"x\\\"|y\" | wc"), ["echo \"x\\\"|y\" ", " wc"]);
+}
+
+#[test]
+fn pipeline_is_empty_after_its_last_stage() {
+    let mut pipeline = Pipeline::new(b"ls | wc");
+    pipeline.next();
+    assert!(!pipeline.is_empty());
+    pipeline.next();
+    assert!(pipeline.is_empty());
+}
+
+#[test]
+fn tokenize_splits_words_and_keeps_quoted_text() {
+    assert_eq!(words("  echo 'a  b' \"c d\" e\\ f ''").unwrap(), ["echo", "a  b", "c d", "e f", ""]);
+}
+
+#[test]
+fn tokenize_expands_variables_outside_of_single_quotes() {
+    assert_eq!(words("echo \"$X\" '$X' ${X}").unwrap(), ["echo", "1 2", "$X", "1", "2"]);
+    assert_eq!(words("echo $? $NONE $ end").unwrap(), ["echo", "0", "$", "end"]);
+}
+
+#[test]
+fn tokenize_marks_unquoted_wildcards() {
+    let argv = tokenize(b"ls *.txt '*.md' \\* x", lookup).unwrap();
+
+    assert!(argv.is_pattern(1));
+    assert!(!argv.is_pattern(2));
+    assert!(!argv.is_pattern(3));
+    assert!(!argv.is_pattern(4));
+}
+
+#[test]
+fn tokenize_reports_errors() {
+    assert_eq!(words("echo 'a"), Err(ParseError::UnterminatedQuote));
+    assert_eq!(words("echo ${X"), Err(ParseError::BadSubstitution));
+    assert_eq!(words("echo ${}"), Err(ParseError::BadSubstitution));
+
+    let many: String = (0..MAX_COUNT_ARGS + 1).map(|_| "a ").collect();
+    assert_eq!(words(&many), Err(ParseError::TooManyArguments));
+}
+
+#[test]
+fn tokenize_refuses_words_that_do_not_fit() {
+    let word: String = (0..MAX_SIZE_WORD).map(|_| 'a').collect();
+    assert_eq!(words(&word).unwrap(), [word.as_str()]);
+
+    let long = word + "a";
+    assert_eq!(words(&long), Err(ParseError::TooLong));
+}